@@ -0,0 +1,190 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! `--self-benchmark N`: time the engine on a synthetic tree
+//!
+//! N unlicensed files in a few languages are written to a fresh directory
+//! under the system temp dir, then licensed and checked at `-j 1, 2, 4...`
+//! up to the usual job count, and once more with `--parallel-walk`. the tree
+//! is rebuilt before every apply and removed at the end, on errors too; no
+//! path the user gave is ever read or written.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::engine::{self, LiceEngine};
+use crate::{Config, Report};
+
+/// the header every benchmark run writes
+pub(crate) const TEMPLATE: &str = "\
+Copyright {year} lice benchmark
+
+SPDX-License-Identifier: MIT
+";
+
+/// files per directory of the synthetic tree
+const FILES_PER_DIR: usize = 100;
+
+/// the languages of the tree, with a body typical of each
+const SOURCES: [(&str, &str); 6] = [
+    (
+        "rs",
+        "use std::io;\n\nfn main() -> io::Result<()> {\n    println!(\"hello\");\n    Ok(())\n}\n",
+    ),
+    (
+        "py",
+        "import sys\n\n\ndef main():\n    print(sys.argv)\n\n\nif __name__ == \"__main__\":\n    main()\n",
+    ),
+    (
+        "c",
+        "#include <stdio.h>\n\nint main(void) {\n    puts(\"hello\");\n    return 0;\n}\n",
+    ),
+    (
+        "go",
+        "package main\n\nimport \"fmt\"\n\nfunc main() {\n\tfmt.Println(\"hello\")\n}\n",
+    ),
+    (
+        "js",
+        "'use strict';\n\nfunction main() {\n  console.log('hello');\n}\n\nmain();\n",
+    ),
+    ("sh", "#!/bin/sh\nset -eu\n\necho hello\n"),
+];
+
+/// the temp directory, removed however the benchmark ends
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// run the benchmark on `files` files, with up to `max_jobs` workers
+pub(crate) fn run(files: usize, max_jobs: usize) -> io::Result<()> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let scratch =
+        Scratch(std::env::temp_dir().join(format!("lice-bench-{}-{}", process::id(), nanos)));
+    // `create_dir`, not `_all`: never reuse a directory that already exists
+    fs::create_dir(&scratch.0)?;
+    let header = scratch.0.join("HEADER.txt");
+    fs::write(&header, TEMPLATE)?;
+    let tree = scratch.0.join("tree");
+
+    let names: Vec<&str> = SOURCES.iter().map(|(ext, _)| *ext).collect();
+    println!(
+        "Benchmark: {} files ({}) in {}",
+        files,
+        names.join(", "),
+        tree.display()
+    );
+    println!(
+        "{:<24} {:>14} {:>14}",
+        "run", "apply files/s", "check files/s"
+    );
+
+    let mut jobs = Vec::new();
+    let mut n = 1;
+    while n < max_jobs {
+        jobs.push(n);
+        n *= 2;
+    }
+    jobs.push(max_jobs);
+    let mut runs: Vec<(String, Vec<String>)> = jobs
+        .iter()
+        .map(|j| (format!("-j {}", j), vec!["-j".to_string(), j.to_string()]))
+        .collect();
+    if max_jobs > 1 {
+        let walkers = (max_jobs / 2).max(2).to_string();
+        runs.push((
+            format!("-j {} --parallel-walk {}", max_jobs, walkers),
+            vec![
+                "-j".to_string(),
+                max_jobs.to_string(),
+                "--parallel-walk".to_string(),
+                walkers,
+            ],
+        ));
+    }
+
+    // the engine's per-file lines would drown the table
+    engine::set_silent(true);
+    let result = runs.iter().try_for_each(|(label, flags)| {
+        generate(&tree, files)?;
+        let apply = timed(&header, &tree, flags, None)?;
+        let check = timed(&header, &tree, flags, Some("--check"))?;
+        println!(
+            "{:<24} {:>14.0} {:>14.0}",
+            label,
+            rate(files, apply),
+            rate(files, check)
+        );
+        Ok(())
+    });
+    engine::set_silent(false);
+    result
+}
+
+/// Helper: `files` files spread over the languages, in directories of
+/// `FILES_PER_DIR`; whatever `tree` held before is replaced
+fn generate(tree: &Path, files: usize) -> io::Result<()> {
+    if tree.exists() {
+        fs::remove_dir_all(tree)?;
+    }
+    for i in 0..files {
+        let dir = tree.join(format!("d{:04}", i / FILES_PER_DIR));
+        if i % FILES_PER_DIR == 0 {
+            fs::create_dir_all(&dir)?;
+        }
+        let (ext, body) = SOURCES[i % SOURCES.len()];
+        fs::write(dir.join(format!("f{}.{}", i, ext)), body)?;
+    }
+    Ok(())
+}
+
+/// Helper: one engine run over `tree`, in seconds; a check that finds
+/// anything to fix means the apply before it did not do its job
+fn timed(header: &Path, tree: &Path, flags: &[String], mode: Option<&str>) -> io::Result<f64> {
+    let mut args = vec![
+        "-f".to_string(),
+        header.display().to_string(),
+        "-q".to_string(),
+    ];
+    args.extend(flags.iter().cloned());
+    args.extend(mode.map(str::to_string));
+    args.push(tree.display().to_string());
+    let config = Config::from_args(args).map_err(io::Error::other)?;
+
+    let started = Instant::now();
+    let report: Report = LiceEngine::new(config)?.run()?;
+    let secs = started.elapsed().as_secs_f64();
+    if report.violations > 0 {
+        return Err(io::Error::other(format!(
+            "benchmark run {} reported {} violations",
+            mode.unwrap_or("apply"),
+            report.violations
+        )));
+    }
+    Ok(secs)
+}
+
+/// Helper: files per second, none for a run too quick to time
+fn rate(files: usize, secs: f64) -> f64 {
+    if secs > 0.0 { files as f64 / secs } else { 0.0 }
+}
@@ -0,0 +1,280 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! `--cache`: remember compliant files between runs
+//!
+//! The file is plain UTF-8 text, one record per line:
+//!
+//! ```text
+//! lice-cache 1
+//! template <fingerprint>
+//! <mtime secs>.<mtime nanos>\t<size>\t<content hash>\t<path>
+//! ```
+//!
+//! Hashes are 16 hex digit FNV-1a 64, stable across builds. The fingerprint
+//! covers the formatted headers and every setting that changes what
+//! "compliant" means; when it differs the whole cache is dropped. Only files
+//! that were compliant at the end of a run are listed, paths as given on the
+//! command line.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+const MAGIC: &str = "lice-cache 1";
+
+/// what is known about a file that was compliant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    mtime: (u64, u32),
+    size: u64,
+    hash: u64,
+}
+
+pub(crate) struct Cache {
+    path: PathBuf,
+    fingerprint: u64,
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+}
+
+impl Cache {
+    /// read the cache at `path`; missing, unreadable or stale files start empty
+    pub(crate) fn load(path: &Path, fingerprint: u64) -> Self {
+        let entries = fs::read_to_string(path)
+            .ok()
+            .and_then(|raw| parse(&raw, fingerprint))
+            .unwrap_or_default();
+        Self {
+            path: path.to_path_buf(),
+            fingerprint,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// was `path` compliant last time, and untouched since?
+    pub(crate) fn is_fresh(&self, path: &Path, meta: &fs::Metadata) -> bool {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(path)
+            .is_some_and(|e| Some(e.mtime) == mtime(meta) && e.size == meta.len())
+    }
+
+    /// remember `path` as compliant with `content`
+    pub(crate) fn record(&self, path: &Path, meta: &fs::Metadata, content: &str) {
+        let Some(mtime) = mtime(meta) else {
+            return;
+        };
+        // the path is the last field, but a line break would end the record
+        if path.to_str().is_none_or(|p| p.contains(['\n', '\r'])) {
+            return;
+        }
+        let entry = Entry {
+            mtime,
+            size: meta.len(),
+            hash: fnv1a(content.as_bytes()),
+        };
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), entry);
+    }
+
+    /// `path` is no longer known to be compliant
+    pub(crate) fn forget(&self, path: &Path) {
+        self.entries.lock().unwrap().remove(path);
+    }
+
+    /// write the cache back, through a temporary file so a crash can't truncate it
+    pub(crate) fn save(&self) -> io::Result<()> {
+        let entries = self.entries.lock().unwrap();
+        let mut sorted: Vec<_> = entries.iter().collect();
+        sorted.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut out = format!("{}\ntemplate {:016x}\n", MAGIC, self.fingerprint);
+        for (path, e) in sorted {
+            let Some(path) = path.to_str() else {
+                continue;
+            };
+            out.push_str(&format!(
+                "{}.{:09}\t{}\t{:016x}\t{}\n",
+                e.mtime.0, e.mtime.1, e.size, e.hash, path
+            ));
+        }
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, out)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// stable 64-bit FNV-1a, for the content hash and the template fingerprint
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn mtime(meta: &fs::Metadata) -> Option<(u64, u32)> {
+    let since = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((since.as_secs(), since.subsec_nanos()))
+}
+
+/// `None` for a foreign file or a different template, which both mean "start over"
+fn parse(raw: &str, fingerprint: u64) -> Option<HashMap<PathBuf, Entry>> {
+    let mut lines = raw.lines();
+    if lines.next()? != MAGIC {
+        return None;
+    }
+    let stored = lines.next()?.strip_prefix("template ")?;
+    if u64::from_str_radix(stored, 16).ok()? != fingerprint {
+        return None;
+    }
+
+    let mut entries = HashMap::new();
+    for line in lines {
+        let mut fields = line.splitn(4, '\t');
+        let (Some(mtime), Some(size), Some(hash), Some(path)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let Some((secs, nanos)) = mtime.split_once('.') else {
+            continue;
+        };
+        let (Ok(secs), Ok(nanos), Ok(size), Ok(hash)) = (
+            secs.parse(),
+            nanos.parse(),
+            size.parse(),
+            u64::from_str_radix(hash, 16),
+        ) else {
+            continue;
+        };
+        let entry = Entry {
+            mtime: (secs, nanos),
+            size,
+            hash,
+        };
+        entries.insert(PathBuf::from(path), entry);
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lice-cache-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn saved_entries_load_back_while_the_file_is_unchanged() {
+        let dir = scratch("roundtrip");
+        let file = dir.join("a.py");
+        fs::write(&file, "# Copyright\n\nx = 1\n").unwrap();
+        let meta = fs::metadata(&file).unwrap();
+        let path = dir.join("cache");
+
+        let cache = Cache::load(&path, 7);
+        assert!(!cache.is_fresh(&file, &meta));
+        cache.record(&file, &meta, "# Copyright\n\nx = 1\n");
+        cache.save().unwrap();
+
+        let raw = fs::read_to_string(&path).unwrap();
+        let mut lines = raw.lines();
+        assert_eq!(lines.next(), Some(MAGIC));
+        assert_eq!(lines.next(), Some("template 0000000000000007"));
+        let record: Vec<&str> = lines.next().unwrap().split('\t').collect();
+        assert_eq!(record[1], meta.len().to_string());
+        assert_eq!(
+            record[2],
+            format!("{:016x}", fnv1a(b"# Copyright\n\nx = 1\n"))
+        );
+        assert_eq!(record[3], file.to_str().unwrap());
+        assert_eq!(lines.next(), None);
+        assert!(!dir.join("cache.tmp").exists());
+
+        assert!(Cache::load(&path, 7).is_fresh(&file, &meta));
+        // a different size is a different file
+        fs::write(&file, "# Copyright\n\nx = 12\n").unwrap();
+        let meta = fs::metadata(&file).unwrap();
+        assert!(!Cache::load(&path, 7).is_fresh(&file, &meta));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn another_fingerprint_or_format_starts_over() {
+        let record = "1700000000.000000001\t10\t00000000000000ff\tsrc/a.py";
+        let raw = format!("{}\ntemplate 000000000000002a\n{}\n", MAGIC, record);
+        let entries = parse(&raw, 42).unwrap();
+        assert_eq!(
+            entries[Path::new("src/a.py")],
+            Entry {
+                mtime: (1_700_000_000, 1),
+                size: 10,
+                hash: 0xff
+            }
+        );
+        assert!(parse(&raw, 43).is_none());
+        assert!(parse(&raw.replace(MAGIC, "lice-cache 2"), 42).is_none());
+        assert!(parse("", 42).is_none());
+        assert!(parse(MAGIC, 42).is_none());
+    }
+
+    #[test]
+    fn malformed_records_are_skipped() {
+        let raw = format!(
+            "{}\ntemplate 0000000000000001\n\
+             garbage\n\
+             1.0\tx\t00\tsrc/size.py\n\
+             1\t3\t00\tsrc/mtime.py\n\
+             1.0\t3\tzz\tsrc/hash.py\n\
+             1.0\t3\t00\tsrc/with\ttab.py\n",
+            MAGIC
+        );
+        let entries = parse(&raw, 1).unwrap();
+        let paths: Vec<&Path> = entries.keys().map(|p| p.as_path()).collect();
+        assert_eq!(paths, [Path::new("src/with\ttab.py")]);
+    }
+
+    #[test]
+    fn forgotten_and_unrecordable_paths_are_not_saved() {
+        let dir = scratch("forget");
+        let file = dir.join("a.py");
+        fs::write(&file, "x").unwrap();
+        let meta = fs::metadata(&file).unwrap();
+        let cache = Cache::load(&dir.join("cache"), 1);
+        cache.record(&file, &meta, "x");
+        cache.record(Path::new("src/line\nbreak.py"), &meta, "x");
+        cache.forget(&file);
+        cache.save().unwrap();
+        let raw = fs::read_to_string(dir.join("cache")).unwrap();
+        assert_eq!(raw.lines().count(), 2, "{raw}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,2177 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! command line configuration
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::thread;
+
+use crate::regex::Regex;
+use crate::style::{BUILTIN_STYLES, STYLE_NAMES, accepts_style};
+use crate::style::{
+    LanguageProfile, STYLE_RAW, get_interpreter_style, get_language_style, line_comment_style,
+    shebang_interpreter,
+};
+use crate::{glob, json, licenses};
+
+pub const USAGE_INFO: &str = r#"lice - Automate source code license headers
+    
+USAGE:
+  lice [OPTIONS] [PATHS...]
+  lice init [--force] [--license-id <ID> --author <NAME>] [PATHS...]
+
+ARGUMENTS:
+  [PATHS...]               Directories or files to process.
+                           If omitted, the current directory is used (.).
+                           Glob patterns ('src/*.rs', '**') are expanded.
+
+OPTIONS:
+  -f, --file <PATH>        Path to the license header file. (Required)
+                           Use '-' to read it from stdin, or an https:// URL
+                           (builds with the 'http' feature). '{year}' in the
+                           text becomes the current year. Given more than
+                           once, the files are joined in order, a blank line
+                           between (e.g. -f COMMON.txt -f PROJECT.txt).
+      --header-from <FILE> Instead of -f, reuse the header already present in
+                           FILE (comment markers stripped) as the template.
+      --license-id <ID>    Instead of -f, use the bundled notice for SPDX
+                           license ID: Apache-2.0, MIT, BSD-3-Clause or
+                           GPL-3.0-or-later. Needs --author.
+      --author <NAME>      Copyright holder named in the --license-id notice.
+      --header-var <KEY=VALUE>
+                           Replace '{KEY}' with VALUE in every template (e.g.
+                           department=Platform). Can be specified multiple
+                           times; 'year' pins '{year}' to VALUE.
+      --warn-unset-vars    Warn about '{name}' placeholders a template still
+                           holds after --header-var (typos).
+      --project-root <DIR> Use DIR/.lice/HEADER.<EXT> as the template for files
+                           ending in .EXT, where it exists; the -f template
+                           covers every other file.
+      --dep5 <PATH>        Give files named in a REUSE .reuse/dep5 the SPDX
+                           header of their paragraph (last match wins); the
+                           other templates cover the rest.
+      --rule <GLOB=>FILE>  Give files whose path (relative, '/' separated)
+                           matches GLOB the template in FILE instead, e.g.
+                           'third_party/**=>THIRD_PARTY.txt'. Can be specified
+                           multiple times; the first matching rule wins.
+  -e, --exclude <PATTERN>  Exclude file/directory matching this pattern, or
+                           only directories with a trailing '/' (build/).
+                           Can be specified multiple times. Given after a
+                           path it only applies under that path:
+                           'src -e gen include -e vendor'. '*', '?' and
+                           '[a-z]' match within a name ('*_gen.go').
+                           Directories holding a '.licenseignore' file are
+                           always excluded.
+      --exclude-from <FILE>
+                           Also exclude the patterns listed in FILE, one per
+                           line ('#' starts a comment). They are added to the
+                           global excludes, even when given after a path.
+      --exclude-ext <EXT>  Skip files ending in .EXT, anywhere (e.g. min.js,
+                           generated.go). Can be specified multiple times.
+      --only-ext <EXT>     Only process files with extension EXT (e.g. rs),
+                           excludes still win. Can be specified multiple times.
+      --git-filter <WHICH> Only process files git tracks ('tracked'), or new
+                           files it doesn't ignore ('untracked'), among the
+                           paths given. 'all' (default) doesn't ask git.
+      --match <REGEX>      Only process files whose path (relative, '/'
+                           separated) matches REGEX somewhere; excludes still
+                           win. Can be specified multiple times (any matches).
+      --force-style <STYLE>
+                           Render every header in one style: 'hash',
+                           'c-like', 'double-slash' or 'dash'. Files whose
+                           language can't take that comment are skipped.
+  -j, --jobs <N>           Number of worker threads. (Default: CPU cores)
+      --threads-per-core <F>
+                           Instead of -j, run F worker threads per CPU core
+                           (e.g. 4 for slow network filesystems), at most 256.
+      --ordered            Print per-file results in the order files were
+                           found rather than finished, so two logs compare.
+      --parallel-walk <N>  Walk the directory tree with N threads instead of
+                           one (fast storage, many small directories).
+                           Ignored with -j 1.
+      --queue-cap <N>      Max paths queued ahead of the workers; the walk
+                           pauses when full. (Default: 64 per worker)
+      --blank-lines <N>    Blank lines between header and code (Default: 1).
+      --blank-lines <EXT=N>
+                           Same, for one extension only (e.g. py=2).
+      --blank-lines <STYLE=N>
+                           Same, for every file in one style: 'c-like',
+                           'hash', 'double-slash' or 'dash' (e.g. c-like=0
+                           for code right below the '*/').
+      --line-comment <EXT=MARKER>
+                           Comment files ending in .EXT with line comments
+                           starting MARKER (e.g. zig=//), over any built-in
+                           style. Can be specified multiple times.
+      --ext-style <EXT=STYLE>
+                           Comment files ending in .EXT in a built-in style
+                           instead of their own: 'hash', 'c-like',
+                           'double-slash' or 'dash' (e.g. sql=c-like).
+      --interpreter-style <NAME=STYLE>
+                           Same for scripts whose extension says nothing
+                           (bin/deploy), by the interpreter on their '#!'
+                           line (e.g. tclsh=hash). A NAME without version
+                           (python) covers python3 and python3.11 too.
+      --prepend-raw <EXT>  Files with extension EXT and no known comment style
+                           get the license text prepended verbatim instead of
+                           being skipped. Can be specified multiple times.
+      --strict-unsupported Fail the run if a file named on the command line
+                           has no known comment style (files found by walking
+                           a directory are still just skipped).
+      --summarize-unsupported
+                           Instead of a warning per unsupported file, list
+                           their extensions with counts once at the end.
+      --keep-at-top <PREFIX>
+                           Lines at the very top starting with PREFIX (e.g.
+                           '#region', '#pragma once') stay there, the header
+                           goes below them. Can be specified multiple times.
+      --keep-first <TEXT>  A leading comment line containing TEXT stays first,
+                           the header goes below it, on top of the defaults
+                           '@generated', 'eslint-disable', 'prettier-ignore'.
+                           Can be specified multiple times.
+      --header-above-echo-off
+                           Put the header of .bat/.cmd files above a leading
+                           '@echo off' instead of right below it.
+      --spdx-policy <POLICY>
+                           Files already tagged with another
+                           SPDX-License-Identifier: 'overwrite' (default) gives
+                           them the header anyway, 'skip' reports and leaves
+                           them alone.
+      --header-max-scan-lines <N>
+                           Look for an existing header in the first N lines
+                           only; a longer one goes unrecognized (Default: 100).
+      --ok-if-matches <REGEX>
+                           Leave a file as it is if those first lines match
+                           REGEX (e.g. 'Copyright \d+'), instead of comparing
+                           the header with the template. Can be repeated.
+      --no-guess           Fail on files whose old header can't be told apart
+                           from the code with certainty (unclosed block,
+                           leading comment without a license keyword, no
+                           blank line before the code), leaving them as is.
+      --strict             Fail the run if a path named on the command line
+                           does not exist, or a directory (entry) can't be
+                           read during the walk (otherwise only a warning).
+      --fix-blank-lines    Rewrite compliant files whose header is followed by
+                           a different number of blank lines than configured
+                           (by default any count of one or more is accepted).
+      --preserve-blank-run Keep extra blank lines between an old header and
+                           the code instead of collapsing them to the
+                           --blank-lines count (which stays the minimum).
+      --cache <PATH>       Remember compliant files (mtime, size, hash) in
+                           PATH and skip them next time while unchanged.
+                           Dropped when the template or settings change.
+      --keep-leading-comments
+                           Only replace a leading comment that mentions
+                           'Copyright', 'License' or 'SPDX'; any other comment
+                           is kept and the header goes above it.
+      --license-keyword <WORD>
+                           Same, with WORD instead of the default keywords.
+                           Can be specified multiple times.
+      --check              Read-only: report files whose header is missing or
+                           outdated and exit non-zero if there are any.
+      --enforce            Read-only policy pass: fail if any file lacks a
+                           current header or has an unknown type (files
+                           without an extension or shebang aside); both
+                           lists are printed at the end.
+      --dry-run            Like --check, but also print the header each file
+                           would get; exits non-zero if anything would change.
+      --dry-run-exit-zero  With --dry-run, exit 0 even if files would change
+                           (advisory runs); errors still fail the run.
+      --diff               With --dry-run, print a unified diff per file that
+                           would change instead, and nothing for the others.
+      --patch <FILE>       With --dry-run, also write every change as one
+                           unified diff to FILE, ready for 'git apply'.
+      --patch-root <DIR>   Make the paths in the --patch file relative to DIR
+                           (Default: the current directory).
+      --color <WHEN>       Color the diffs: 'auto' (default, on a terminal),
+                           'always' or 'never'.
+      --fix                Add or update headers. This is the default; the
+                           flag just makes it explicit (e.g. after --check).
+      --dedupe-headers     Collapse license blocks stacked at the top of a
+                           file (left by earlier runs) into one header.
+      --detect-wrong-style Warn about a license header in another language's
+                           comment style ('//' in a .py file) and replace it
+                           instead of stacking the right one on top.
+      --header-checksum    End every header with a 'lice-checksum:<hash>' line
+                           and warn about headers whose text no longer
+                           matches their own checksum (edited by hand).
+      --strip-trailing-whitespace
+                           Write headers without trailing whitespace, and
+                           strip it from the lines of an otherwise current
+                           header, leaving the rest of the file untouched.
+      --update-year-only   Only bump the copyright year in existing headers
+                           to the current year; leave everything else as is.
+      --normalize          Only rewrite headers whose text already matches
+                           the template but whose formatting (prefix spacing,
+                           trailing whitespace, blank lines) doesn't; other
+                           files are left alone.
+      --remove             Take the license header out again (a leading or,
+                           with --position bottom, trailing comment that
+                           mentions a license keyword) with the blank lines
+                           after it; a header-only file ends up empty.
+      --report-unlicensed  Read-only: list files that have no license header
+                           at all and exit non-zero if any are found.
+      --prefix <STR>       Use STR verbatim in front of every license line,
+                           instead of the style's own prefix (e.g. '//').
+      --first-line-prefix <STR>
+                           Use STR in front of the first license line only
+                           (e.g. ' ** ' for Doxygen), --prefix for the rest.
+      --no-prefix-space    Drop the trailing space of the style's prefix
+                           ('// x' becomes '//x').
+      --suffix <STR>       Append STR to the end of every license line
+                           (e.g. ' *' for box-style banners).
+      --style <STYLE>      'banner' boxes the header text: '=' rules above and
+                           below, '|' borders aligned on the longest line.
+                           'plain' (default) comments each line as it is.
+      --count-only         Read-only: print only the compliant/non-compliant/
+                           unsupported totals, no per-file output.
+      --format <FMT>       Output format: 'text' (default), 'json' or 'jsonl'.
+                           JSON emits one record per line, warnings included;
+                           'jsonl' streams one typed record per file and ends
+                           with a summary record.
+      --skip-symlinks      Do not follow symlinked files; their targets may
+                           live outside the tree and are left untouched.
+  -v, --verbose            Report extra detail (e.g. skipped symlinks).
+  -q, --quiet              No start banner and no closing throughput line.
+      --count-lines        At the end, tell how many header lines were
+                           written into how many files.
+      --count-by-language  At the end, break the results down by extension
+                           ('rs: 120 ok, 4 updated; py: 30 ok, 1 added').
+                           Not printed under -q.
+      --archive <ZIP>      License the source entries inside ZIP and write it
+                           back in place, instead of walking paths (builds
+                           with the 'zip' feature).
+      --out-dir <DIR>      Don't touch the sources: write each rewritten file
+                           to the same relative path under DIR instead.
+      --copy-unchanged     With --out-dir, also copy already-compliant files
+                           so DIR mirrors the whole tree.
+      --verify             Re-read every rewritten file and check it is now
+                           compliant; if not, always write the original
+                           content back (kept in memory, no backup file is
+                           made) and fail the run.
+      --verify-idempotent  After writing (and --post-cmd), check each file as
+                           --check would; list those that would still change
+                           at the end and fail the run if there are any.
+      --touch-only-changed-lines
+                           For large files, write just the changed head and
+                           copy the unchanged rest from the original (a
+                           server-side copy where the filesystem can).
+      --post-cmd <CMD>     Run CMD through the shell on every file that was
+                           rewritten, '{}' standing for its path (appended
+                           if absent), e.g. 'rustfmt {}'. Failures fail the run.
+      --fail-fast          Stop at the first file that can't be processed and
+                           exit non-zero with just that error.
+      --max-errors <N>     Report errors as usual, but stop and exit non-zero
+                           once more than N files have failed.
+      --position <POS>     Where the header goes: 'top' (default) or 'bottom'
+                           (appended after a blank line).
+      --header-position <POS>
+                           With --position top: 'after-package' puts the
+                           header below a leading 'package' line (Java, Go,
+                           Perl) instead of above it; 'top' is the default.
+      --final-newline <POLICY>
+                           How rewritten files end: 'preserve' (default) keeps
+                           the original's trailing newlines, 'ensure' adds one
+                           if missing, 'strip' removes them.
+      --no-gitattributes   Ignore 'eol=' in .gitattributes; inserted lines
+                           then follow each file's own line ending.
+      --from-file <PATH>   Also process the paths listed in PATH, one per
+                           line. Use '-' to read the list from stdin.
+      --from-file0 <PATH>  Same, but NUL-separated (git ls-files -z,
+                           find -print0). '--stdin0' reads it from stdin.
+      --stdin-filename <NAME>
+                           Filter: read one file from stdin and print it with
+                           its header, styled as a file called NAME (editors).
+      --stdin-style <STYLE>
+                           Same, in a named style instead: 'hash', 'c-like',
+                           'double-slash' or 'dash'; for unsaved buffers.
+      --explain <FILE>     Trace how FILE would be handled (style, preamble,
+                           existing header, template) and print the result,
+                           without writing anything.
+      --verify-license-file
+                           Check the template (trailing whitespace, tabs,
+                           mixed line endings, a '*/' inside, hard-coded
+                           year), show it in every style and exit.
+      --selftest           In memory, add the header to a sample file of
+                           every style (--line-comment and --ext-style ones
+                           too), run again and take it out; print PASS/FAIL
+                           per style, exit non-zero if any fails.
+      --dump-config        Print the effective settings as JSON, defaults
+                           resolved (e.g. the thread count), and exit.
+      --force              With 'init', overwrite an existing HEADER.txt.
+  -h, --help               Show this help message and exit.
+
+ENVIRONMENT:
+  LICE_JOBS                Worker threads when neither -j nor
+                           --threads-per-core is given.
+  LICE_QUEUE_CAP           Queue capacity when --queue-cap is not given.
+
+EXAMPLES:
+  # Apply license to the current directory
+  lice -f HEADER.txt .
+
+  # Apply to 'src' and 'include', excluding 'vendor' and 'build'
+  lice -f HEADER.txt -e vendor -e build src include
+
+  # Annual maintenance: '2023' -> '2023-<this year>', ranges are extended
+  lice -f HEADER.txt --update-year-only .
+
+  # CI: fail if any header is missing or outdated, without changing anything
+  lice -f HEADER.txt --check .
+
+  # Audit: print every file without a header, one path per line
+  lice -f HEADER.txt --report-unlicensed src
+
+  # Only the files git knows about, safe for any file name
+  git ls-files -z | lice -f HEADER.txt --stdin0"#;
+
+/// Helper: a positive count from environment variable `name`, if set
+fn env_count(name: &str) -> Result<Option<usize>, String> {
+    match std::env::var(name) {
+        Ok(val) => val
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .map(Some)
+            .ok_or_else(|| format!("Invalid {}: {} (expected a positive integer)", name, val)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => Err(format!("Invalid {}: not valid UTF-8", name)),
+    }
+}
+
+/// `--keep-leading-comments` without `--license-keyword`, lowercased
+const DEFAULT_LICENSE_KEYWORDS: [&str; 3] = ["copyright", "license", "spdx"];
+
+/// generator markers that have to stay the first comment, `--keep-first` adds more
+const DEFAULT_KEEP_FIRST: [&str; 3] = ["@generated", "eslint-disable", "prettier-ignore"];
+
+/// `--header-max-scan-lines` by default
+const DEFAULT_HEADER_MAX_SCAN_LINES: usize = 100;
+
+/// most worker threads `--threads-per-core` scales up to
+const MAX_SCALED_JOBS: usize = 256;
+
+/// what to do with each file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// add or replace the license header (default, or `--fix`)
+    Apply,
+    /// read-only: what `Apply` would change, reported as violations
+    Check,
+    /// read-only: `Check`, plus every file of unknown type is a violation
+    Enforce,
+    /// read-only: `Check` with a preview of every header that would be written
+    DryRun,
+    /// only bump the year inside an existing header
+    UpdateYear,
+    /// only re-emit headers whose text is current in the canonical format
+    Normalize,
+    /// take out the license header, leave the rest of the file
+    Remove,
+    /// read-only: list files without any header
+    ReportUnlicensed,
+    /// read-only: tally compliance and print the totals
+    CountOnly,
+}
+
+impl Mode {
+    /// the flag selecting this mode, `apply` for the default
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Mode::Apply => "apply",
+            Mode::Check => "check",
+            Mode::Enforce => "enforce",
+            Mode::DryRun => "dry-run",
+            Mode::UpdateYear => "update-year-only",
+            Mode::Normalize => "normalize",
+            Mode::Remove => "remove",
+            Mode::ReportUnlicensed => "report-unlicensed",
+            Mode::CountOnly => "count-only",
+        }
+    }
+
+    /// modes that may rewrite files
+    pub(crate) fn writes(self) -> bool {
+        matches!(
+            self,
+            Mode::Apply | Mode::UpdateYear | Mode::Normalize | Mode::Remove
+        )
+    }
+
+    /// modes whose stdout is machine-readable only (path list, totals)
+    pub(crate) fn is_terse(self) -> bool {
+        matches!(self, Mode::ReportUnlicensed | Mode::CountOnly)
+    }
+}
+
+/// how output is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Text,
+    Json,
+    /// one record per file as it finishes, then a closing summary record
+    JsonLines,
+}
+
+/// where the header goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Position {
+    /// prepend, after any shebang (default)
+    Top,
+    /// append after a blank line, for formats that want the notice last
+    Bottom,
+}
+
+/// `--git-filter`: which files git has to know about to be processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GitFilter {
+    /// every file, git or not (default)
+    All,
+    /// only files in the index (`git ls-files`)
+    Tracked,
+    /// only new files git doesn't ignore (`git ls-files --others --exclude-standard`)
+    Untracked,
+}
+
+/// `--spdx-policy`: what happens to a file already tagged with another license
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SpdxPolicy {
+    /// the header goes in regardless (default)
+    Overwrite,
+    /// reported and left as is, so third-party files aren't relicensed
+    Skip,
+}
+
+/// `--color`: when `--diff` output is colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColorWhen {
+    /// only when stdout is a terminal (default)
+    Auto,
+    Always,
+    Never,
+}
+
+/// how a rewritten file ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FinalNewline {
+    /// add a line break if there is none
+    Ensure,
+    /// as many trailing line breaks as the original had (default)
+    Preserve,
+    /// no trailing line break at all
+    Strip,
+}
+
+pub struct Config {
+    /// `-f` templates, joined in order with a blank line between
+    pub(crate) license_files: Vec<String>,
+    /// take the template from the existing header of this file instead
+    pub(crate) header_from: Option<PathBuf>,
+    /// `--license-id`: a bundled notice instead of a template file
+    pub(crate) license_id: Option<String>,
+    /// `--author`: fills `{author}` in the bundled notice
+    pub(crate) author: Option<String>,
+    /// `--header-var KEY=VALUE`: fills `{KEY}` in every template
+    pub(crate) header_vars: Vec<(String, String)>,
+    /// `--warn-unset-vars`: warn about `{name}` placeholders left in a template
+    pub(crate) warn_unset_vars: bool,
+    /// `--project-root`: per-extension templates in `.lice/HEADER.<ext>`
+    pub(crate) project_root: Option<PathBuf>,
+    /// `--dep5`: the REUSE `.reuse/dep5` file
+    pub(crate) dep5: Option<PathBuf>,
+    /// `--rule GLOB=>FILE`: path glob and template, first match wins
+    pub(crate) rules: Vec<(String, PathBuf)>,
+    pub(crate) excludes: Vec<String>,
+    /// `PATH -e PATTERN`: excludes given after a path, for that path only
+    pub(crate) scoped_excludes: Vec<(PathBuf, Vec<String>)>,
+    /// `--exclude-ext`, without the leading dot
+    pub(crate) exclude_exts: Vec<String>,
+    /// `--only-ext`, without the leading dot and lowercased; empty means all
+    pub(crate) only_exts: Vec<String>,
+    pub(crate) git_filter: GitFilter,
+    /// `--match`: a file is processed only if its path matches one of these
+    pub(crate) matches: Vec<Regex>,
+    pub(crate) targets: Vec<PathBuf>,
+    pub(crate) jobs: Option<usize>,
+    /// `--threads-per-core`: workers per available core, instead of `jobs`
+    pub(crate) threads_per_core: Option<f64>,
+    /// `--parallel-walk N`: walker threads sharing the directory walk
+    pub(crate) parallel_walk: Option<usize>,
+    /// how many lines from the top an existing header may reach down to
+    pub(crate) header_max_scan_lines: usize,
+    /// `--ok-if-matches`: a file whose top lines match one of these is compliant
+    pub(crate) ok_if_matches: Vec<Regex>,
+    /// `--ordered`: per-file output in discovery order, not completion order
+    pub(crate) ordered: bool,
+    pub(crate) mode: Mode,
+    /// replaces the per-style line prefix, borrowed by [`Config::customize`]
+    pub(crate) prefix: Option<String>,
+    /// replaces the prefix of the first license line only
+    pub(crate) first_line_prefix: Option<String>,
+    /// keep the trailing space of the style prefix (`// ` vs `//`)
+    pub(crate) prefix_space: bool,
+    /// per-line suffix appended after every license line
+    pub(crate) suffix: Option<String>,
+    /// `--style banner`: box the header text
+    pub(crate) banner: bool,
+    pub(crate) format: Format,
+    /// leave symlinked files alone instead of rewriting their target
+    pub(crate) skip_symlinks: bool,
+    pub(crate) verbose: bool,
+    /// `-h`, or no arguments at all: show the usage and stop
+    pub(crate) help: bool,
+    /// no banner, no throughput line
+    pub(crate) quiet: bool,
+    /// write results under this directory instead of in place
+    pub(crate) out_dir: Option<PathBuf>,
+    /// with `out_dir`, mirror compliant files as well
+    pub(crate) copy_unchanged: bool,
+    /// `--archive`: license the entries of this zip file instead of a tree
+    pub(crate) archive: Option<PathBuf>,
+    pub(crate) position: Position,
+    /// `--header-position after-package`: a leading `package` line stays
+    /// above the header
+    pub(crate) after_package: bool,
+    /// honor `eol=` from `.gitattributes` for inserted lines
+    pub(crate) gitattributes: bool,
+    /// capacity of the path queue feeding the workers
+    pub(crate) queue_cap: Option<usize>,
+    /// `--blank-lines N`: blank lines after the header for every style
+    pub(crate) blank_lines: Option<usize>,
+    /// `--blank-lines STYLE=N`: per-style override (`c-like=0`), wins over
+    /// the above
+    pub(crate) blank_lines_by_style: HashMap<&'static str, usize>,
+    /// `--blank-lines EXT=N`: per-extension override, wins over the above
+    pub(crate) blank_lines_by_ext: HashMap<String, usize>,
+    /// re-check every written file, restoring it if the check fails
+    pub(crate) verify: bool,
+    /// `--verify-idempotent`: list written files a `--check` would still flag
+    pub(crate) verify_idempotent: bool,
+    /// `--touch-only-changed-lines`: rewrite a large file as the new head plus
+    /// the original's unchanged tail, copied file to file
+    pub(crate) touch_only_changed_lines: bool,
+    /// stop at the first file that fails to process
+    pub(crate) fail_fast: bool,
+    /// `--max-errors N`: stop once more than N files failed
+    pub(crate) max_errors: Option<usize>,
+    /// `--prepend-raw EXT`: unsupported extensions that get the bare license text
+    pub(crate) prepend_raw: Vec<String>,
+    /// `--line-comment`: user line prefixes (marker and a space) by extension,
+    /// ahead of the built-ins
+    pub(crate) line_comments: HashMap<String, String>,
+    /// `--ext-style`: a named built-in style by extension, ahead of the built-ins
+    pub(crate) ext_styles: HashMap<String, LanguageProfile<'static>>,
+    /// `--interpreter-style`: a named built-in style by `#!` interpreter,
+    /// ahead of the built-in table
+    pub(crate) interpreter_styles: HashMap<String, LanguageProfile<'static>>,
+    /// explicitly listed files without a style are an error, not a warning
+    pub(crate) strict_unsupported: bool,
+    /// one grouped list of unsupported extensions at the end, no per-file warning
+    pub(crate) summarize_unsupported: bool,
+    /// `--keep-at-top`: leading lines kept above the header, like a shebang
+    pub(crate) keep_at_top: Vec<String>,
+    /// `--keep-first`: leading comments containing one of these stay first
+    pub(crate) keep_first: Vec<String>,
+    /// ambiguous old headers are an error instead of a best guess
+    pub(crate) no_guess: bool,
+    pub(crate) spdx_policy: SpdxPolicy,
+    /// `--header-above-echo-off`: the style's leading line (`@echo off`)
+    /// goes below the header instead of staying on top
+    pub(crate) header_above_leading_line: bool,
+    /// missing targets and unreadable directories are an error, not a warning
+    pub(crate) strict: bool,
+    /// a current header followed by the wrong blank-line count is rewritten
+    pub(crate) fix_blank_lines: bool,
+    /// blank lines beyond `blank_lines` after an old header are kept
+    pub(crate) preserve_blank_run: bool,
+    /// `--cache`: file recording compliant files between runs
+    pub(crate) cache: Option<PathBuf>,
+    /// only a leading comment with a license keyword is replaced
+    pub(crate) keep_leading_comments: bool,
+    /// `--license-keyword`, lowercased; empty means the defaults
+    pub(crate) license_keywords: Vec<String>,
+    /// print the resolved settings instead of running
+    pub(crate) dump_config: bool,
+    /// `lice init`: write a starter `HEADER.txt` for the languages found
+    pub(crate) init: bool,
+    /// `init --force`: overwrite an existing `HEADER.txt`
+    pub(crate) force: bool,
+    /// `--self-benchmark N`: time the engine on N generated files instead
+    pub(crate) self_benchmark: Option<usize>,
+    /// `--explain FILE`: trace one file instead of running
+    pub(crate) explain: Option<PathBuf>,
+    /// `--stdin-filename NAME`: filter stdin to stdout, styled as a file NAME
+    pub(crate) stdin_filename: Option<PathBuf>,
+    /// `--stdin-style STYLE`: filter stdin to stdout in this built-in style
+    pub(crate) stdin_style: Option<LanguageProfile<'static>>,
+    /// check the template and show it rendered instead of running
+    pub(crate) verify_license_file: bool,
+    /// `--selftest`: round-trip the header through every style instead of running
+    pub(crate) selftest: bool,
+    /// `--force-style`: one comment style for every file that can take it
+    pub(crate) force_style: Option<LanguageProfile<'static>>,
+    /// strip every license block stacked under the first one
+    pub(crate) dedupe_headers: bool,
+    /// `--detect-wrong-style`: report (and replace) a license header written
+    /// in another language's comment style
+    pub(crate) detect_wrong_style: bool,
+    /// `--header-checksum`: end every header with a `lice-checksum:` line
+    pub(crate) header_checksum: bool,
+    /// `--strip-trailing-whitespace`: headers carry none, old ones are cleaned
+    pub(crate) strip_trailing_whitespace: bool,
+    pub(crate) final_newline: FinalNewline,
+    /// `--post-cmd`: shell command run on every rewritten file, `{}` its path
+    pub(crate) post_cmd: Option<String>,
+    /// `--count-lines`: sum up the header lines written at the end
+    pub(crate) count_lines: bool,
+    /// `--count-by-language`: break the outcomes down by extension at the end
+    pub(crate) count_by_language: bool,
+    /// `--patch`: under `--dry-run`, collect every change in this diff file
+    pub(crate) patch: Option<PathBuf>,
+    /// `--patch-root`: patch paths are relative to it (default: the cwd)
+    pub(crate) patch_root: Option<PathBuf>,
+    /// `--diff`: under `--dry-run`, a unified diff instead of the header
+    pub(crate) diff: bool,
+    /// `--dry-run-exit-zero`: pending changes don't fail a `--dry-run`
+    pub(crate) dry_run_exit_zero: bool,
+    pub(crate) color: ColorWhen,
+}
+
+impl Config {
+    /// parse the process arguments
+    ///
+    /// `LICE_JOBS` and `LICE_QUEUE_CAP` fill in for `-j` and `--queue-cap`
+    /// when those aren't given
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::from_args(std::env::args().skip(1))?;
+        if config.jobs.is_none() && config.threads_per_core.is_none() {
+            config.jobs = env_count("LICE_JOBS")?;
+        }
+        if config.queue_cap.is_none() {
+            config.queue_cap = env_count("LICE_QUEUE_CAP")?;
+        }
+        Ok(config)
+    }
+
+    /// the settings of a bare run, before any flag is looked at
+    pub(crate) fn defaults() -> Self {
+        Config {
+            license_files: Vec::new(),
+            header_from: None,
+            license_id: None,
+            author: None,
+            header_vars: Vec::new(),
+            warn_unset_vars: false,
+            project_root: None,
+            rules: Vec::new(),
+            dep5: None,
+            excludes: Vec::new(),
+            scoped_excludes: Vec::new(),
+            exclude_exts: Vec::new(),
+            only_exts: Vec::new(),
+            git_filter: GitFilter::All,
+            matches: Vec::new(),
+            targets: Vec::new(),
+            jobs: None,
+            threads_per_core: None,
+            parallel_walk: None,
+            ordered: false,
+            header_max_scan_lines: DEFAULT_HEADER_MAX_SCAN_LINES,
+            ok_if_matches: Vec::new(),
+            mode: Mode::Apply,
+            prefix: None,
+            first_line_prefix: None,
+            prefix_space: true,
+            suffix: None,
+            banner: false,
+            format: Format::Text,
+            skip_symlinks: false,
+            verbose: false,
+            help: false,
+            quiet: false,
+            out_dir: None,
+            archive: None,
+            copy_unchanged: false,
+            position: Position::Top,
+            after_package: false,
+            gitattributes: true,
+            queue_cap: None,
+            blank_lines: None,
+            blank_lines_by_style: HashMap::new(),
+            blank_lines_by_ext: HashMap::new(),
+            verify: false,
+            verify_idempotent: false,
+            touch_only_changed_lines: false,
+            fail_fast: false,
+            max_errors: None,
+            prepend_raw: Vec::new(),
+            line_comments: HashMap::new(),
+            ext_styles: HashMap::new(),
+            interpreter_styles: HashMap::new(),
+            strict_unsupported: false,
+            summarize_unsupported: false,
+            keep_at_top: Vec::new(),
+            keep_first: DEFAULT_KEEP_FIRST.iter().map(|s| s.to_string()).collect(),
+            no_guess: false,
+            spdx_policy: SpdxPolicy::Overwrite,
+            strict: false,
+            header_above_leading_line: false,
+            fix_blank_lines: false,
+            preserve_blank_run: false,
+            cache: None,
+            keep_leading_comments: false,
+            license_keywords: Vec::new(),
+            dump_config: false,
+            init: false,
+            force: false,
+            self_benchmark: None,
+            explain: None,
+            stdin_filename: None,
+            stdin_style: None,
+            verify_license_file: false,
+            selftest: false,
+            force_style: None,
+            dedupe_headers: false,
+            detect_wrong_style: false,
+            header_checksum: false,
+            strip_trailing_whitespace: false,
+            final_newline: FinalNewline::Preserve,
+            post_cmd: None,
+            count_lines: false,
+            count_by_language: false,
+            patch: None,
+            patch_root: None,
+            diff: false,
+            dry_run_exit_zero: false,
+            color: ColorWhen::Auto,
+        }
+    }
+
+    /// parse an argument list (without the program name), same grammar as the CLI
+    ///
+    /// `-h` or an empty list parses to a config that only asks for the
+    /// usage, see [`Config::help_requested`]
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let raw_args: Vec<String> = args.into_iter().collect();
+
+        // no arguments at all means the same as `-h`
+        let no_args = raw_args.is_empty();
+
+        let mut args = raw_args.into_iter();
+        // stdin can feed either the template or the path list, not both
+        let mut paths_from_stdin = false;
+        // the last path named, which a following `-e` is scoped to
+        let mut scope: Option<PathBuf> = None;
+
+        let mut config = Self::defaults();
+        config.help = no_args;
+
+        // `lice init ...`: the subcommand comes first
+        if args.as_slice().first().is_some_and(|arg| arg == "init") {
+            args.next();
+            config.init = true;
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-f" | "--file" => {
+                    let val = args.next().ok_or("-f/--file requires an argument")?;
+                    config.license_files.push(val);
+                }
+                "--header-from" => {
+                    let val = args.next().ok_or("--header-from requires an argument")?;
+                    config.header_from = Some(PathBuf::from(val));
+                }
+                "--license-id" => {
+                    let val = args.next().ok_or("--license-id requires an argument")?;
+                    if licenses::template(&val).is_none() {
+                        return Err(format!(
+                            "Unknown license id: {} (supported: {})",
+                            val,
+                            licenses::ids()
+                        ));
+                    }
+                    config.license_id = Some(val);
+                }
+                "--author" => {
+                    let val = args.next().ok_or("--author requires an argument")?;
+                    config.author = Some(val);
+                }
+                "--header-var" => {
+                    let val = args.next().ok_or("--header-var requires an argument")?;
+                    let (key, value) = val
+                        .split_once('=')
+                        .filter(|(key, _)| is_placeholder_name(key))
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid value for --header-var: {} (expected KEY=VALUE, \
+                                 KEY of letters, digits, '_' or '-')",
+                                val
+                            )
+                        })?;
+                    // given twice, the last value wins
+                    config.header_vars.retain(|(known, _)| known != key);
+                    config
+                        .header_vars
+                        .push((key.to_string(), value.to_string()));
+                }
+                "--warn-unset-vars" => config.warn_unset_vars = true,
+                "--project-root" => {
+                    let val = args.next().ok_or("--project-root requires an argument")?;
+                    config.project_root = Some(PathBuf::from(val));
+                }
+                "--rule" => {
+                    let val = args.next().ok_or("--rule requires an argument")?;
+                    let (glob, file) = val
+                        .split_once("=>")
+                        .map(|(glob, file)| (glob.trim(), file.trim()))
+                        .filter(|(glob, file)| !glob.is_empty() && !file.is_empty())
+                        .ok_or_else(|| format!("Invalid --rule: {} (expected GLOB=>FILE)", val))?;
+                    config.rules.push((glob.to_string(), PathBuf::from(file)));
+                }
+                "--dep5" => {
+                    let val = args.next().ok_or("--dep5 requires an argument")?;
+                    config.dep5 = Some(PathBuf::from(val));
+                }
+                "-e" | "--exclude" => {
+                    let val = args.next().ok_or("-e/--exclude requires an argument")?;
+                    match &scope {
+                        Some(root) => match config.scoped_excludes.last_mut() {
+                            Some((last, excludes)) if last == root => excludes.push(val),
+                            _ => config.scoped_excludes.push((root.clone(), vec![val])),
+                        },
+                        None => config.excludes.push(val),
+                    }
+                }
+                "--exclude-from" => {
+                    let val = args.next().ok_or("--exclude-from requires an argument")?;
+                    config.excludes.extend(read_exclude_file(&val)?);
+                }
+                "--exclude-ext" => {
+                    let val = args.next().ok_or("--exclude-ext requires an argument")?;
+                    config
+                        .exclude_exts
+                        .push(val.trim_start_matches('.').to_string());
+                }
+                "--only-ext" => {
+                    let val = args.next().ok_or("--only-ext requires an argument")?;
+                    config
+                        .only_exts
+                        .push(val.trim_start_matches('.').to_lowercase());
+                }
+                "--git-filter" => {
+                    let val = args.next().ok_or("--git-filter requires an argument")?;
+                    config.git_filter = match val.as_str() {
+                        "all" => GitFilter::All,
+                        "tracked" => GitFilter::Tracked,
+                        "untracked" => GitFilter::Untracked,
+                        _ => {
+                            return Err(format!(
+                                "Unknown git filter: {} (expected tracked|untracked|all)",
+                                val
+                            ));
+                        }
+                    };
+                }
+                "--ok-if-matches" => {
+                    let val = args.next().ok_or("--ok-if-matches requires an argument")?;
+                    config.ok_if_matches.push(Regex::new(&val)?);
+                }
+                "--match" => {
+                    let val = args.next().ok_or("--match requires an argument")?;
+                    config.matches.push(Regex::new(&val)?);
+                }
+                "-h" | "--help" => {
+                    // like a bare `lice`, the rest of the line doesn't matter
+                    config.help = true;
+                    break;
+                }
+                "-j" | "--jobs" => {
+                    let val = args.next().ok_or("-j requires an argument")?;
+                    // zero workers would leave the bounded queue blocked forever
+                    let num = val
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or("Invalid number for -j")?;
+                    config.jobs = Some(num);
+                }
+                "--threads-per-core" => {
+                    let val = args
+                        .next()
+                        .ok_or("--threads-per-core requires an argument")?;
+                    let factor = val
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|f| f.is_finite() && *f > 0.0)
+                        .ok_or("Invalid factor for --threads-per-core")?;
+                    config.threads_per_core = Some(factor);
+                }
+                "--ordered" => config.ordered = true,
+                "--header-max-scan-lines" => {
+                    let val = args
+                        .next()
+                        .ok_or("--header-max-scan-lines requires an argument")?;
+                    config.header_max_scan_lines = val
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or("Invalid number for --header-max-scan-lines")?;
+                }
+                "--parallel-walk" => {
+                    let val = args.next().ok_or("--parallel-walk requires an argument")?;
+                    let num = val
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or("Invalid number for --parallel-walk")?;
+                    config.parallel_walk = Some(num);
+                }
+                "--queue-cap" => {
+                    let val = args.next().ok_or("--queue-cap requires an argument")?;
+                    let num = val
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or("Invalid number for --queue-cap")?;
+                    config.queue_cap = Some(num);
+                }
+                "--blank-lines" => {
+                    let val = args.next().ok_or("--blank-lines requires an argument")?;
+                    let invalid = || format!("Invalid value for --blank-lines: {}", val);
+                    match val.split_once('=') {
+                        Some((key, n)) => {
+                            let n = n.parse::<usize>().map_err(|_| invalid())?;
+                            match STYLE_NAMES.iter().find(|(name, _)| *name == key) {
+                                Some((name, _)) => {
+                                    config.blank_lines_by_style.insert(name, n);
+                                }
+                                None => {
+                                    let ext = key.trim_start_matches('.');
+                                    config.blank_lines_by_ext.insert(ext.to_string(), n);
+                                }
+                            }
+                        }
+                        None => config.blank_lines = Some(val.parse().map_err(|_| invalid())?),
+                    }
+                }
+                "--prepend-raw" => {
+                    let val = args.next().ok_or("--prepend-raw requires an argument")?;
+                    config
+                        .prepend_raw
+                        .push(val.trim_start_matches('.').to_string());
+                }
+                "--line-comment" => {
+                    let val = args.next().ok_or("--line-comment requires an argument")?;
+                    let (ext, marker) = val
+                        .split_once('=')
+                        .map(|(ext, marker)| (ext.trim_start_matches('.'), marker.trim()))
+                        .filter(|(ext, marker)| !ext.is_empty() && !marker.is_empty())
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid value for --line-comment: {} (expected EXT=MARKER)",
+                                val
+                            )
+                        })?;
+                    config
+                        .line_comments
+                        .insert(ext.to_string(), format!("{} ", marker));
+                }
+                "--ext-style" => {
+                    let val = args.next().ok_or("--ext-style requires an argument")?;
+                    let (ext, name) = val
+                        .split_once('=')
+                        .map(|(ext, name)| (ext.trim_start_matches('.'), name.trim()))
+                        .filter(|(ext, _)| !ext.is_empty())
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid value for --ext-style: {} (expected EXT=STYLE)",
+                                val
+                            )
+                        })?;
+                    config
+                        .ext_styles
+                        .insert(ext.to_string(), named_style(name)?);
+                }
+                "--interpreter-style" => {
+                    let val = args
+                        .next()
+                        .ok_or("--interpreter-style requires an argument")?;
+                    let (interpreter, name) = val
+                        .split_once('=')
+                        .map(|(interpreter, name)| (interpreter.trim(), name.trim()))
+                        .filter(|(interpreter, _)| !interpreter.is_empty())
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid value for --interpreter-style: {} (expected NAME=STYLE)",
+                                val
+                            )
+                        })?;
+                    config
+                        .interpreter_styles
+                        .insert(interpreter.to_string(), named_style(name)?);
+                }
+                "--fix" => config.mode = Mode::Apply,
+                "--check" => config.mode = Mode::Check,
+                "--enforce" => config.mode = Mode::Enforce,
+                "--dry-run" => config.mode = Mode::DryRun,
+                "--diff" => config.diff = true,
+                "--dry-run-exit-zero" => config.dry_run_exit_zero = true,
+                "--patch" => {
+                    let val = args.next().ok_or("--patch requires a file")?;
+                    config.patch = Some(PathBuf::from(val));
+                }
+                "--patch-root" => {
+                    let val = args.next().ok_or("--patch-root requires a directory")?;
+                    config.patch_root = Some(PathBuf::from(val));
+                }
+                "--color" => {
+                    let val = args.next().ok_or("--color requires an argument")?;
+                    config.color = match val.as_str() {
+                        "auto" => ColorWhen::Auto,
+                        "always" => ColorWhen::Always,
+                        "never" => ColorWhen::Never,
+                        _ => {
+                            return Err(format!(
+                                "Unknown color mode: {} (expected auto|always|never)",
+                                val
+                            ));
+                        }
+                    };
+                }
+                "--update-year-only" => config.mode = Mode::UpdateYear,
+                "--normalize" => config.mode = Mode::Normalize,
+                "--remove" => config.mode = Mode::Remove,
+                "--report-unlicensed" => config.mode = Mode::ReportUnlicensed,
+                "--count-only" => config.mode = Mode::CountOnly,
+                "--format" => {
+                    let val = args.next().ok_or("--format requires an argument")?;
+                    config.format = match val.as_str() {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        "jsonl" => Format::JsonLines,
+                        _ => {
+                            return Err(format!(
+                                "Unknown format: {} (expected text|json|jsonl)",
+                                val
+                            ));
+                        }
+                    };
+                }
+                "--prefix" => {
+                    let val = args.next().ok_or("--prefix requires an argument")?;
+                    config.prefix = Some(val);
+                }
+                "--first-line-prefix" => {
+                    let val = args
+                        .next()
+                        .ok_or("--first-line-prefix requires an argument")?;
+                    config.first_line_prefix = Some(val);
+                }
+                "--no-prefix-space" => config.prefix_space = false,
+                "--skip-symlinks" => config.skip_symlinks = true,
+                "-v" | "--verbose" => config.verbose = true,
+                "-q" | "--quiet" => config.quiet = true,
+                "--count-lines" => config.count_lines = true,
+                "--count-by-language" => config.count_by_language = true,
+                "--post-cmd" => {
+                    let val = args.next().ok_or("--post-cmd requires an argument")?;
+                    config.post_cmd = Some(val);
+                }
+                "--style" => {
+                    let val = args.next().ok_or("--style requires an argument")?;
+                    config.banner = match val.as_str() {
+                        "plain" => false,
+                        "banner" => true,
+                        _ => {
+                            return Err(format!(
+                                "Unknown header style: {} (expected plain|banner)",
+                                val
+                            ));
+                        }
+                    };
+                }
+                "--suffix" => {
+                    let val = args.next().ok_or("--suffix requires an argument")?;
+                    config.suffix = Some(val);
+                }
+                "--out-dir" => {
+                    let val = args.next().ok_or("--out-dir requires an argument")?;
+                    config.out_dir = Some(PathBuf::from(val));
+                }
+                "--copy-unchanged" => config.copy_unchanged = true,
+                "--archive" => {
+                    let val = args.next().ok_or("--archive requires a file")?;
+                    config.archive = Some(PathBuf::from(val));
+                }
+                "--verify" => config.verify = true,
+                "--verify-idempotent" => config.verify_idempotent = true,
+                "--touch-only-changed-lines" => config.touch_only_changed_lines = true,
+                "--fail-fast" => config.fail_fast = true,
+                "--max-errors" => {
+                    let val = args.next().ok_or("--max-errors requires an argument")?;
+                    let num = val
+                        .parse::<usize>()
+                        .map_err(|_| "Invalid number for --max-errors")?;
+                    config.max_errors = Some(num);
+                }
+                "--strict-unsupported" => config.strict_unsupported = true,
+                "--strict" => config.strict = true,
+                "--header-above-echo-off" => config.header_above_leading_line = true,
+                "--no-guess" => config.no_guess = true,
+                "--spdx-policy" => {
+                    let val = args.next().ok_or("--spdx-policy requires an argument")?;
+                    config.spdx_policy = match val.as_str() {
+                        "overwrite" => SpdxPolicy::Overwrite,
+                        "skip" => SpdxPolicy::Skip,
+                        _ => {
+                            return Err(format!(
+                                "Unknown SPDX policy: {} (expected overwrite|skip)",
+                                val
+                            ));
+                        }
+                    };
+                }
+                "--keep-at-top" => {
+                    let val = args.next().ok_or("--keep-at-top requires an argument")?;
+                    if val.trim().is_empty() {
+                        return Err("--keep-at-top requires a non-empty prefix".to_string());
+                    }
+                    config.keep_at_top.push(val);
+                }
+                "--keep-first" => {
+                    let val = args.next().ok_or("--keep-first requires an argument")?;
+                    if val.trim().is_empty() {
+                        return Err("--keep-first requires non-empty text".to_string());
+                    }
+                    config.keep_first.push(val);
+                }
+                "--summarize-unsupported" => config.summarize_unsupported = true,
+                "--dump-config" => config.dump_config = true,
+                "--force" => config.force = true,
+                "--self-benchmark" => {
+                    let val = args
+                        .next()
+                        .ok_or("--self-benchmark requires a number of files")?;
+                    let num: usize = val
+                        .parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or("--self-benchmark requires a positive number of files")?;
+                    config.self_benchmark = Some(num);
+                }
+                "--verify-license-file" => config.verify_license_file = true,
+                "--selftest" => config.selftest = true,
+                "--explain" => {
+                    let val = args.next().ok_or("--explain requires a file")?;
+                    config.explain = Some(PathBuf::from(val));
+                }
+                "--dedupe-headers" => config.dedupe_headers = true,
+                "--detect-wrong-style" => config.detect_wrong_style = true,
+                "--header-checksum" => config.header_checksum = true,
+                "--strip-trailing-whitespace" => config.strip_trailing_whitespace = true,
+                "--force-style" => {
+                    let val = args.next().ok_or("--force-style requires an argument")?;
+                    let (_, style) = STYLE_NAMES
+                        .iter()
+                        .find(|(name, _)| *name == val)
+                        .ok_or_else(|| {
+                            format!(
+                                "Unknown style: {} (expected hash|c-like|double-slash|dash)",
+                                val
+                            )
+                        })?;
+                    config.force_style = Some(*style);
+                }
+                "--fix-blank-lines" => config.fix_blank_lines = true,
+                "--preserve-blank-run" => config.preserve_blank_run = true,
+                "--keep-leading-comments" => config.keep_leading_comments = true,
+                "--license-keyword" => {
+                    let val = args
+                        .next()
+                        .ok_or("--license-keyword requires an argument")?;
+                    config.keep_leading_comments = true;
+                    config.license_keywords.push(val.to_lowercase());
+                }
+                "--cache" => {
+                    let val = args.next().ok_or("--cache requires an argument")?;
+                    config.cache = Some(PathBuf::from(val));
+                }
+                "--no-gitattributes" => config.gitattributes = false,
+                "--position" => {
+                    let val = args.next().ok_or("--position requires an argument")?;
+                    config.position = match val.as_str() {
+                        "top" => Position::Top,
+                        "bottom" => Position::Bottom,
+                        _ => {
+                            return Err(format!("Unknown position: {} (expected top|bottom)", val));
+                        }
+                    };
+                }
+                "--header-position" => {
+                    let val = args
+                        .next()
+                        .ok_or("--header-position requires an argument")?;
+                    config.after_package = match val.as_str() {
+                        "top" => false,
+                        "after-package" => true,
+                        _ => {
+                            return Err(format!(
+                                "Unknown header position: {} (expected top|after-package)",
+                                val
+                            ));
+                        }
+                    };
+                }
+                "--final-newline" => {
+                    let val = args.next().ok_or("--final-newline requires an argument")?;
+                    config.final_newline = match val.as_str() {
+                        "ensure" => FinalNewline::Ensure,
+                        "preserve" => FinalNewline::Preserve,
+                        "strip" => FinalNewline::Strip,
+                        _ => {
+                            return Err(format!(
+                                "Unknown final newline policy: {} (expected ensure|preserve|strip)",
+                                val
+                            ));
+                        }
+                    };
+                }
+                "--from-file" => {
+                    let val = args.next().ok_or("--from-file requires an argument")?;
+                    paths_from_stdin |= val == "-";
+                    config.targets.extend(read_path_list(&val, b'\n')?);
+                }
+                "--from-file0" => {
+                    let val = args.next().ok_or("--from-file0 requires an argument")?;
+                    paths_from_stdin |= val == "-";
+                    config.targets.extend(read_path_list(&val, b'\0')?);
+                }
+                "--stdin0" => {
+                    paths_from_stdin = true;
+                    config.targets.extend(read_path_list("-", b'\0')?);
+                }
+                "--stdin-filename" => {
+                    let val = args.next().ok_or("--stdin-filename requires a name")?;
+                    config.stdin_filename = Some(PathBuf::from(val));
+                }
+                "--stdin-style" => {
+                    let val = args.next().ok_or("--stdin-style requires an argument")?;
+                    config.stdin_style = Some(named_style(val.trim())?);
+                }
+                s if s.starts_with('-') => return Err(format!("Unknown option: {}", s)),
+                _ => {
+                    scope = Some(PathBuf::from(&arg));
+                    config.targets.push(PathBuf::from(arg));
+                }
+            }
+        }
+        if config.help {
+            return Ok(config);
+        }
+
+        if paths_from_stdin && config.license_files.iter().any(|f| f == "-") {
+            return Err("-f - conflicts with reading the path list from stdin".to_string());
+        }
+        if config.filters_stdin() && (paths_from_stdin || !config.targets.is_empty()) {
+            return Err(
+                "--stdin-filename/--stdin-style filter stdin to stdout, they take no paths"
+                    .to_string(),
+            );
+        }
+        config.validate()
+    }
+
+    /// `-h` was given, or no arguments at all: show [`USAGE_INFO`] and don't run
+    pub fn help_requested(&self) -> bool {
+        self.help
+    }
+
+    /// apply the CLI prefix/suffix overrides to a style
+    pub(crate) fn customize<'a>(&'a self, mut style: LanguageProfile<'a>) -> LanguageProfile<'a> {
+        let name = STYLE_NAMES
+            .iter()
+            .find(|(_, named)| *named == style)
+            .map(|(name, _)| *name);
+        // a first line like the rest follows `--prefix`
+        let same_first = style.first_line_prefix == style.prefix;
+        if let Some(prefix) = self.prefix.as_deref() {
+            style.prefix = prefix;
+            if same_first {
+                style.first_line_prefix = prefix;
+            }
+        }
+        if let Some(first) = self.first_line_prefix.as_deref() {
+            style.first_line_prefix = first;
+        }
+        if !self.prefix_space {
+            style.prefix = style.prefix.trim_end();
+            style.first_line_prefix = style.first_line_prefix.trim_end();
+        }
+        if let Some(suffix) = self.suffix.as_deref() {
+            style.suffix = suffix;
+        }
+        style.banner = self.banner;
+        if let Some(n) = self.blank_lines {
+            style.blank_lines = n;
+        }
+        if let Some(&n) = name.and_then(|name| self.blank_lines_by_style.get(name)) {
+            style.blank_lines = n;
+        }
+        style
+    }
+
+    /// built-in style for `ext` with every CLI override applied
+    ///
+    /// formatting and the idempotency check both go through here, so they agree;
+    /// `--prepend-raw` extensions get the raw style, which has no prefix to customize
+    pub(crate) fn style_for_ext(&self, ext: &str) -> Option<LanguageProfile<'_>> {
+        let native = self
+            .ext_styles
+            .get(ext)
+            .copied()
+            .or_else(|| {
+                self.line_comments
+                    .get(ext)
+                    .map(|prefix| line_comment_style(prefix))
+            })
+            .or_else(|| get_language_style(ext));
+        let mut style = match native {
+            Some(native) => self.customize(self.pick_style(ext, native)?),
+            None if self.prepend_raw.iter().any(|e| e == ext) => LanguageProfile {
+                blank_lines: self.blank_lines.unwrap_or(STYLE_RAW.blank_lines),
+                banner: self.banner,
+                ..STYLE_RAW
+            },
+            None => return None,
+        };
+        if let Some(&n) = self.blank_lines_by_ext.get(ext) {
+            style.blank_lines = n;
+        }
+        Some(style)
+    }
+
+    /// worker threads, `-j` or one per CPU core
+    pub(crate) fn resolved_jobs(&self) -> usize {
+        self.jobs.unwrap_or_else(|| {
+            let cores = thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4);
+            match self.threads_per_core {
+                Some(factor) => {
+                    ((cores as f64 * factor).round() as usize).clamp(1, MAX_SCALED_JOBS)
+                }
+                None => cores,
+            }
+        })
+    }
+
+    /// `--dump-config`: every setting as one JSON object, with the resolved
+    /// thread count and queue capacity the run would use
+    pub(crate) fn dump(&self, jobs: usize, queue_cap: usize) -> String {
+        let opt = |s: Option<String>| s.map_or("null".to_string(), |s| json::string(&s));
+        let path = |p: &PathBuf| json::string(&p.display().to_string());
+        let list = |items: Vec<String>| format!("[{}]", items.join(","));
+        let strings = |items: &[String]| list(items.iter().map(|s| json::string(s)).collect());
+
+        let named_styles = |by_key: &HashMap<String, LanguageProfile>| {
+            let mut sorted: Vec<_> = by_key.iter().collect();
+            sorted.sort_by(|a, b| a.0.cmp(b.0));
+            let entries: Vec<String> = sorted
+                .iter()
+                .filter_map(|(key, style)| {
+                    let (name, _) = STYLE_NAMES.iter().find(|(_, s)| s == *style)?;
+                    Some(format!("{}:{}", json::string(key), json::string(name)))
+                })
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        };
+
+        let mut line_comments: Vec<_> = self.line_comments.iter().collect();
+        line_comments.sort_by(|a, b| a.0.cmp(b.0));
+        let line_comments = line_comments
+            .iter()
+            .map(|(ext, prefix)| {
+                format!("{}:{}", json::string(ext), json::string(prefix.trim_end()))
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut by_style: Vec<_> = self.blank_lines_by_style.iter().collect();
+        by_style.sort();
+        let by_style = by_style
+            .iter()
+            .map(|(name, n)| format!("{}:{}", json::string(name), n))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut by_ext: Vec<_> = self.blank_lines_by_ext.iter().collect();
+        by_ext.sort();
+        let by_ext = by_ext
+            .iter()
+            .map(|(ext, n)| format!("{}:{}", json::string(ext), n))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let styles = BUILTIN_STYLES
+            .iter()
+            .map(|&style| {
+                let style = self.customize(style);
+                format!(
+                    "{{\"start\":{},\"prefix\":{},\"first_line_prefix\":{},\"end\":{},\"suffix\":{},\"banner\":{},\"blank_lines\":{}}}",
+                    json::string(style.start),
+                    json::string(style.prefix),
+                    json::string(style.first_line_prefix),
+                    json::string(style.end),
+                    json::string(style.suffix),
+                    style.banner,
+                    style.blank_lines
+                )
+            })
+            .collect();
+
+        let rules = list(
+            self.rules
+                .iter()
+                .map(|(glob, file)| {
+                    format!(
+                        "{{\"glob\":{},\"file\":{}}}",
+                        json::string(glob),
+                        json::string(&file.display().to_string())
+                    )
+                })
+                .collect(),
+        );
+
+        let fields = [
+            ("license_files", strings(&self.license_files)),
+            (
+                "header_from",
+                opt(self.header_from.as_ref().map(|p| p.display().to_string())),
+            ),
+            ("license_id", opt(self.license_id.clone())),
+            ("author", opt(self.author.clone())),
+            (
+                "header_vars",
+                format!(
+                    "{{{}}}",
+                    self.header_vars
+                        .iter()
+                        .map(|(key, value)| format!(
+                            "{}:{}",
+                            json::string(key),
+                            json::string(value)
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+            ),
+            ("warn_unset_vars", self.warn_unset_vars.to_string()),
+            (
+                "project_root",
+                opt(self.project_root.as_ref().map(|p| p.display().to_string())),
+            ),
+            ("rules", rules),
+            (
+                "dep5",
+                opt(self.dep5.as_ref().map(|p| p.display().to_string())),
+            ),
+            (
+                "explain",
+                opt(self.explain.as_ref().map(|p| p.display().to_string())),
+            ),
+            (
+                "stdin_filename",
+                opt(self
+                    .stdin_filename
+                    .as_ref()
+                    .map(|p| p.display().to_string())),
+            ),
+            (
+                "stdin_style",
+                opt(self.stdin_style.and_then(|style| {
+                    STYLE_NAMES
+                        .iter()
+                        .find(|(_, s)| *s == style)
+                        .map(|(name, _)| name.to_string())
+                })),
+            ),
+            ("verify_license_file", self.verify_license_file.to_string()),
+            ("selftest", self.selftest.to_string()),
+            ("init", self.init.to_string()),
+            (
+                "self_benchmark",
+                self.self_benchmark
+                    .map_or("null".to_string(), |n| n.to_string()),
+            ),
+            ("force", self.force.to_string()),
+            ("targets", list(self.targets.iter().map(path).collect())),
+            ("excludes", strings(&self.excludes)),
+            (
+                "scoped_excludes",
+                list(
+                    self.scoped_excludes
+                        .iter()
+                        .map(|(root, excludes)| {
+                            format!(
+                                "{{\"path\":{},\"excludes\":{}}}",
+                                json::string(&root.display().to_string()),
+                                strings(excludes)
+                            )
+                        })
+                        .collect(),
+                ),
+            ),
+            ("exclude_exts", strings(&self.exclude_exts)),
+            ("only_exts", strings(&self.only_exts)),
+            (
+                "git_filter",
+                json::string(match self.git_filter {
+                    GitFilter::All => "all",
+                    GitFilter::Tracked => "tracked",
+                    GitFilter::Untracked => "untracked",
+                }),
+            ),
+            (
+                "match",
+                list(
+                    self.matches
+                        .iter()
+                        .map(|r| json::string(r.as_str()))
+                        .collect(),
+                ),
+            ),
+            ("jobs", jobs.to_string()),
+            (
+                "threads_per_core",
+                self.threads_per_core
+                    .map_or("null".to_string(), |f| f.to_string()),
+            ),
+            ("ordered", self.ordered.to_string()),
+            (
+                "header_max_scan_lines",
+                self.header_max_scan_lines.to_string(),
+            ),
+            (
+                "ok_if_matches",
+                list(
+                    self.ok_if_matches
+                        .iter()
+                        .map(|re| json::string(re.as_str()))
+                        .collect(),
+                ),
+            ),
+            (
+                "parallel_walk",
+                self.parallel_walk
+                    .map_or("null".to_string(), |n| n.to_string()),
+            ),
+            ("queue_cap", queue_cap.to_string()),
+            ("mode", json::string(self.mode.as_str())),
+            (
+                "format",
+                json::string(match self.format {
+                    Format::Text => "text",
+                    Format::Json => "json",
+                    Format::JsonLines => "jsonl",
+                }),
+            ),
+            (
+                "position",
+                json::string(match self.position {
+                    Position::Top => "top",
+                    Position::Bottom => "bottom",
+                }),
+            ),
+            ("after_package", self.after_package.to_string()),
+            ("styles", list(styles)),
+            (
+                "force_style",
+                opt(self.force_style.and_then(|forced| {
+                    let name = STYLE_NAMES.iter().find(|(_, style)| *style == forced);
+                    name.map(|(name, _)| name.to_string())
+                })),
+            ),
+            ("blank_lines_by_style", format!("{{{}}}", by_style)),
+            ("blank_lines_by_ext", format!("{{{}}}", by_ext)),
+            ("prepend_raw", strings(&self.prepend_raw)),
+            ("line_comment", format!("{{{}}}", line_comments)),
+            ("ext_style", named_styles(&self.ext_styles)),
+            ("interpreter_style", named_styles(&self.interpreter_styles)),
+            (
+                "keep_leading_comments",
+                self.keep_leading_comments.to_string(),
+            ),
+            ("license_keywords", strings(&self.license_keywords)),
+            ("fix_blank_lines", self.fix_blank_lines.to_string()),
+            ("preserve_blank_run", self.preserve_blank_run.to_string()),
+            ("dedupe_headers", self.dedupe_headers.to_string()),
+            ("detect_wrong_style", self.detect_wrong_style.to_string()),
+            ("header_checksum", self.header_checksum.to_string()),
+            (
+                "strip_trailing_whitespace",
+                self.strip_trailing_whitespace.to_string(),
+            ),
+            (
+                "final_newline",
+                json::string(match self.final_newline {
+                    FinalNewline::Ensure => "ensure",
+                    FinalNewline::Preserve => "preserve",
+                    FinalNewline::Strip => "strip",
+                }),
+            ),
+            ("diff", self.diff.to_string()),
+            ("dry_run_exit_zero", self.dry_run_exit_zero.to_string()),
+            (
+                "patch",
+                opt(self.patch.as_ref().map(|p| p.display().to_string())),
+            ),
+            (
+                "patch_root",
+                opt(self.patch_root.as_ref().map(|p| p.display().to_string())),
+            ),
+            (
+                "color",
+                json::string(match self.color {
+                    ColorWhen::Auto => "auto",
+                    ColorWhen::Always => "always",
+                    ColorWhen::Never => "never",
+                }),
+            ),
+            ("gitattributes", self.gitattributes.to_string()),
+            ("skip_symlinks", self.skip_symlinks.to_string()),
+            ("strict_unsupported", self.strict_unsupported.to_string()),
+            ("keep_at_top", strings(&self.keep_at_top)),
+            ("keep_first", strings(&self.keep_first)),
+            ("strict", self.strict.to_string()),
+            (
+                "header_above_echo_off",
+                self.header_above_leading_line.to_string(),
+            ),
+            ("no_guess", self.no_guess.to_string()),
+            (
+                "spdx_policy",
+                json::string(match self.spdx_policy {
+                    SpdxPolicy::Overwrite => "overwrite",
+                    SpdxPolicy::Skip => "skip",
+                }),
+            ),
+            (
+                "summarize_unsupported",
+                self.summarize_unsupported.to_string(),
+            ),
+            (
+                "out_dir",
+                opt(self.out_dir.as_ref().map(|p| p.display().to_string())),
+            ),
+            ("copy_unchanged", self.copy_unchanged.to_string()),
+            (
+                "archive",
+                opt(self.archive.as_ref().map(|p| p.display().to_string())),
+            ),
+            (
+                "cache",
+                opt(self.cache.as_ref().map(|p| p.display().to_string())),
+            ),
+            ("verify", self.verify.to_string()),
+            ("verify_idempotent", self.verify_idempotent.to_string()),
+            (
+                "touch_only_changed_lines",
+                self.touch_only_changed_lines.to_string(),
+            ),
+            ("fail_fast", self.fail_fast.to_string()),
+            (
+                "max_errors",
+                self.max_errors
+                    .map_or("null".to_string(), |n| n.to_string()),
+            ),
+            ("verbose", self.verbose.to_string()),
+            ("quiet", self.quiet.to_string()),
+            ("count_lines", self.count_lines.to_string()),
+            ("count_by_language", self.count_by_language.to_string()),
+            ("post_cmd", opt(self.post_cmd.clone())),
+        ];
+
+        let body: Vec<String> = fields
+            .iter()
+            .map(|(key, value)| format!("  {}: {}", json::string(key), value))
+            .collect();
+        format!("{{\n{}\n}}", body.join(",\n"))
+    }
+
+    /// `--stdin-filename`/`--stdin-style`: stdin is licensed to stdout
+    pub(crate) fn filters_stdin(&self) -> bool {
+        self.stdin_filename.is_some() || self.stdin_style.is_some()
+    }
+
+    /// does a leading comment `text` look like a license, per `--license-keyword`?
+    pub(crate) fn is_license_text(&self, text: &str) -> bool {
+        !self.keep_leading_comments || self.mentions_license(text)
+    }
+
+    /// does `text` contain a `--license-keyword` (or a default one)?
+    pub(crate) fn mentions_license(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        match self.license_keywords.as_slice() {
+            [] => DEFAULT_LICENSE_KEYWORDS.iter().any(|k| text.contains(k)),
+            keywords => keywords.iter().any(|k| text.contains(k.as_str())),
+        }
+    }
+
+    /// style for an extensionless script from its first line, overrides applied
+    pub(crate) fn style_for_shebang(&self, first_line: &str) -> Option<LanguageProfile<'_>> {
+        let interpreter = shebang_interpreter(first_line)?;
+        // `--interpreter-style` by the exact name (`python3`), then without
+        // the version (`python`), before the built-in table
+        let unversioned = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        let native = self
+            .interpreter_styles
+            .get(interpreter)
+            .or_else(|| self.interpreter_styles.get(unversioned))
+            .copied()
+            .or_else(|| get_interpreter_style(interpreter))?;
+        Some(self.customize(self.pick_style("", native)?))
+    }
+
+    /// the `native` style, or `--force-style` if the language can take it
+    fn pick_style<'a>(
+        &self,
+        ext: &str,
+        native: LanguageProfile<'a>,
+    ) -> Option<LanguageProfile<'a>> {
+        match self.force_style {
+            Some(forced) if accepts_style(ext, native, forced) => Some(forced),
+            Some(_) => None,
+            None => Some(native),
+        }
+    }
+
+    fn validate(mut self) -> Result<Self, String> {
+        if self.init {
+            if !self.license_files.is_empty() || self.header_from.is_some() {
+                return Err("init writes the template, it takes no -f/--header-from".to_string());
+            }
+            if self.targets.is_empty() {
+                self.targets.push(PathBuf::from("."));
+            }
+        } else if self.force {
+            return Err("--force only applies to 'lice init'".to_string());
+        }
+        if self.self_benchmark.is_some() {
+            if !self.targets.is_empty() || self.init {
+                return Err(
+                    "--self-benchmark generates its own files, it takes no paths".to_string(),
+                );
+            }
+            if !self.license_files.is_empty()
+                || self.header_from.is_some()
+                || self.license_id.is_some()
+            {
+                return Err("--self-benchmark brings its own template".to_string());
+            }
+        }
+        match (
+            self.license_files.first(),
+            &self.header_from,
+            &self.license_id,
+        ) {
+            (None, None, None) if self.init || self.self_benchmark.is_some() => {}
+            (None, None, None) => return Err("Missing required argument: -f/--file".to_string()),
+            (Some(_), Some(_), _) => {
+                return Err("-f/--file conflicts with --header-from".to_string());
+            }
+            (Some(_), _, Some(_)) => {
+                return Err("-f/--file conflicts with --license-id".to_string());
+            }
+            (_, Some(_), Some(_)) => {
+                return Err("--header-from conflicts with --license-id".to_string());
+            }
+            _ => {}
+        }
+        match (&self.license_id, &self.author) {
+            (Some(_), None) => return Err("--license-id requires --author".to_string()),
+            (None, Some(_)) => return Err("--author requires --license-id".to_string()),
+            _ => {}
+        }
+        if self.fail_fast && self.max_errors.is_some() {
+            return Err("--max-errors conflicts with --fail-fast".to_string());
+        }
+        if self.license_files.iter().filter(|f| *f == "-").count() > 1 {
+            return Err("-f - can only be given once".to_string());
+        }
+        if self.count_by_language && self.mode.is_terse() {
+            return Err(
+                "--count-by-language conflicts with --count-only and --report-unlicensed"
+                    .to_string(),
+            );
+        }
+        if let Some(ext) = self
+            .ext_styles
+            .keys()
+            .find(|ext| self.line_comments.contains_key(*ext))
+        {
+            return Err(format!(
+                "--ext-style and --line-comment both set a style for .{}",
+                ext
+            ));
+        }
+        if self.jobs.is_some() && self.threads_per_core.is_some() {
+            return Err("-j/--jobs conflicts with --threads-per-core".to_string());
+        }
+        if self.mode == Mode::Normalize && self.position == Position::Bottom {
+            return Err("--normalize only supports --position top".to_string());
+        }
+        if self.after_package && self.position == Position::Bottom {
+            return Err("--header-position after-package requires --position top".to_string());
+        }
+        if self.strip_trailing_whitespace && self.position == Position::Bottom {
+            return Err("--strip-trailing-whitespace only supports --position top".to_string());
+        }
+        if self.filters_stdin() {
+            if self.license_files.iter().any(|f| f == "-") {
+                return Err("-f - conflicts with the stdin filter".to_string());
+            }
+            if self.mode != Mode::Apply || self.archive.is_some() || self.out_dir.is_some() {
+                return Err(
+                    "the stdin filter only adds headers, it takes no other mode, \
+                     --archive or --out-dir"
+                        .to_string(),
+                );
+            }
+        }
+        if self.verify_idempotent && self.mode != Mode::Apply {
+            return Err(
+                "--verify-idempotent checks what --fix writes, it takes no other mode".to_string(),
+            );
+        }
+        if self.dry_run_exit_zero && self.mode != Mode::DryRun {
+            return Err("--dry-run-exit-zero requires --dry-run".to_string());
+        }
+        if self.diff && self.mode != Mode::DryRun {
+            return Err("--diff requires --dry-run".to_string());
+        }
+        if self.patch.is_some() && self.mode != Mode::DryRun {
+            return Err("--patch requires --dry-run".to_string());
+        }
+        if self.patch_root.is_some() && self.patch.is_none() {
+            return Err("--patch-root requires --patch".to_string());
+        }
+        if self.fix_blank_lines && self.preserve_blank_run {
+            return Err("--fix-blank-lines conflicts with --preserve-blank-run".to_string());
+        }
+        if self.archive.is_some() {
+            if !self.targets.is_empty() {
+                return Err("--archive takes no other paths".to_string());
+            }
+            if !matches!(
+                self.mode,
+                Mode::Apply | Mode::Check | Mode::Enforce | Mode::DryRun
+            ) {
+                return Err("--archive only supports applying, --check and --dry-run".to_string());
+            }
+            if self.out_dir.is_some() {
+                return Err("--archive conflicts with --out-dir".to_string());
+            }
+        }
+        if self.targets.is_empty()
+            && self.explain.is_none()
+            && self.archive.is_none()
+            && !self.verify_license_file
+            && !self.selftest
+            && !self.filters_stdin()
+            && self.self_benchmark.is_none()
+        {
+            return Err("No target paths specified. Use '.' for current directory.".to_string());
+        }
+        self.targets = expand_targets(self.targets)?;
+        // a scope named by a glob covers every path it expanded to
+        self.scoped_excludes = self
+            .scoped_excludes
+            .into_iter()
+            .flat_map(|(root, excludes)| {
+                let roots = match root.to_str() {
+                    Some(pattern) if glob::has_magic(pattern) && !root.exists() => {
+                        glob::expand(pattern)
+                    }
+                    _ => vec![root],
+                };
+                roots.into_iter().map(move |root| (root, excludes.clone()))
+            })
+            .collect();
+        if self.copy_unchanged && self.out_dir.is_none() {
+            return Err("--copy-unchanged requires --out-dir".to_string());
+        }
+        Ok(self)
+    }
+}
+
+/// expand glob targets (`src/*.rs`) the shell left alone, e.g. on Windows
+///
+/// a target that exists as written is kept verbatim, even with `*` in its name
+fn expand_targets(targets: Vec<PathBuf>) -> Result<Vec<PathBuf>, String> {
+    let mut out = Vec::with_capacity(targets.len());
+    for target in targets {
+        match target.to_str() {
+            Some(pattern) if glob::has_magic(pattern) && !target.exists() => {
+                let matched = glob::expand(pattern);
+                if matched.is_empty() {
+                    return Err(format!("No files match pattern: {}", pattern));
+                }
+                out.extend(matched);
+            }
+            _ => out.push(target),
+        }
+    }
+    Ok(out)
+}
+
+/// `--exclude-from`: one `-e` pattern per line, `#` comments and blank
+/// lines skipped
+fn read_exclude_file(path: &str) -> Result<Vec<String>, String> {
+    let raw = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read exclude file {}: {}", path, e))?;
+    Ok(raw
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Helper: can `name` be a `{name}` placeholder: letters, digits, `_`, `-`
+pub(crate) fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Helper: the built-in style `--ext-style`/`--interpreter-style` name
+fn named_style(name: &str) -> Result<LanguageProfile<'static>, String> {
+    STYLE_NAMES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, style)| *style)
+        .ok_or_else(|| {
+            format!(
+                "Unknown style: {} (expected hash|c-like|double-slash|dash)",
+                name
+            )
+        })
+}
+
+/// read a `sep`-separated path list from a file, or from stdin for `-`
+///
+/// empty entries are dropped; with `\n` a trailing `\r` is stripped as well
+fn read_path_list(source: &str, sep: u8) -> Result<Vec<PathBuf>, String> {
+    let mut raw = Vec::new();
+    let read = if source == "-" {
+        io::stdin().read_to_end(&mut raw).map(|_| ())
+    } else {
+        fs::read(source).map(|bytes| raw = bytes)
+    };
+    read.map_err(|e| format!("Failed to read path list {}: {}", source, e))?;
+
+    Ok(raw
+        .split(|&b| b == sep)
+        .map(|entry| match sep {
+            b'\n' => entry.strip_suffix(b"\r").unwrap_or(entry),
+            _ => entry,
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| PathBuf::from(bytes_to_os_string(entry)))
+        .collect())
+}
+
+/// raw bytes to a path string; lossless on unix, where paths are arbitrary bytes
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes.to_vec())
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    OsString::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn help_is_returned_not_exited() {
+        assert!(Config::from_args(Vec::new()).unwrap().help_requested());
+        assert!(Config::from_args(args(&["-h"])).unwrap().help_requested());
+        // whatever follows `-h` is never looked at
+        let config = Config::from_args(args(&["--help", "--no-such-flag"])).unwrap();
+        assert!(config.help_requested());
+    }
+
+    #[test]
+    fn header_var_keys_are_placeholder_names() {
+        let parse = |var: &str| {
+            let list = [
+                "--license-id",
+                "MIT",
+                "--author",
+                "A",
+                "--header-var",
+                var,
+                ".",
+            ];
+            Config::from_args(args(&list)).map(|config| config.header_vars)
+        };
+        // only the first `=` splits, the value may hold more
+        let vars = parse("team=a=b").unwrap();
+        assert_eq!(vars, [("team".to_string(), "a=b".to_string())]);
+        assert!(parse("no value").is_err());
+        assert!(parse("two words=x").is_err());
+        assert!(parse("=x").is_err());
+    }
+
+    #[test]
+    fn help_is_not_requested_by_a_normal_run() {
+        let config = Config::from_args(args(&["-f", "HEADER.txt", "src"])).unwrap();
+        assert!(!config.help_requested());
+    }
+
+    #[test]
+    fn path_lists_add_to_the_targets() {
+        let dir = std::env::temp_dir().join(format!("lice-list-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let lines = dir.join("lines");
+        fs::write(&lines, "a.rs\r\nsub dir/b.py\n\n").unwrap();
+        let nul = dir.join("nul");
+        fs::write(&nul, b"c.rs\0odd\nname.py\0").unwrap();
+        let config = Config::from_args(args(&[
+            "-f",
+            "HEADER.txt",
+            "src",
+            "--from-file",
+            &lines.display().to_string(),
+            "--from-file0",
+            &nul.display().to_string(),
+        ]))
+        .unwrap();
+        let targets: Vec<PathBuf> = ["src", "a.rs", "sub dir/b.py", "c.rs", "odd\nname.py"]
+            .map(PathBuf::from)
+            .into();
+        assert_eq!(config.targets, targets);
+        // a list counts as targets on its own
+        let listed = Config::from_args(args(&[
+            "-f",
+            "x",
+            "--from-file",
+            &lines.display().to_string(),
+        ]));
+        assert!(listed.is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zero_workers_are_rejected() {
+        for flag in ["-j", "--queue-cap"] {
+            let err = Config::from_args(args(&["-f", "H.txt", flag, "0", "src"])).err();
+            assert_eq!(err, Some(format!("Invalid number for {flag}")));
+        }
+    }
+
+    #[test]
+    fn a_glob_target_that_matches_nothing_is_an_error() {
+        let pattern = "no-such-dir-for-lice/**/*.rs";
+        let err = Config::from_args(args(&["-f", "H.txt", pattern])).err();
+        assert_eq!(err, Some(format!("No files match pattern: {pattern}")));
+    }
+
+    #[test]
+    fn threads_per_core_scales_the_core_count_and_excludes_jobs() {
+        let cores = thread::available_parallelism().map_or(4, |n| n.get());
+        let jobs = |factor: &str| {
+            Config::from_args(args(&["-f", "H", "--threads-per-core", factor, "src"]))
+                .map(|config| config.resolved_jobs())
+        };
+        assert_eq!(jobs("2"), Ok((cores * 2).min(MAX_SCALED_JOBS)));
+        assert_eq!(jobs("0.01"), Ok(1));
+        assert_eq!(jobs("1000"), Ok(MAX_SCALED_JOBS));
+        assert!(jobs("0").is_err());
+        assert!(jobs("nan").is_err());
+        let both = args(&["-f", "H", "-j", "2", "--threads-per-core", "2", "src"]);
+        assert!(Config::from_args(both).is_err());
+    }
+
+    #[test]
+    fn license_id_must_be_known_and_name_an_author() {
+        let err = |list: &[&str]| Config::from_args(args(list)).err();
+        assert_eq!(
+            err(&["--license-id", "WTFPL", "."]).as_deref(),
+            Some(
+                "Unknown license id: WTFPL (supported: Apache-2.0, MIT, BSD-3-Clause, \
+                 GPL-3.0-or-later, GPL-3.0)"
+            )
+        );
+        assert_eq!(
+            err(&["--license-id", "MIT", "."]).as_deref(),
+            Some("--license-id requires --author")
+        );
+        assert_eq!(
+            err(&["-f", "H", "--license-id", "MIT", "--author", "A", "."]).as_deref(),
+            Some("-f/--file conflicts with --license-id")
+        );
+    }
+
+    #[test]
+    fn exclude_from_is_global_even_after_a_path() {
+        let dir = std::env::temp_dir().join(format!("lice-exclude-from-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let list = dir.join("ignore.txt");
+        fs::write(&list, "# generated\ngen\n").unwrap();
+        let config = Config::from_args(args(&[
+            "-f",
+            "H.txt",
+            "src",
+            "-e",
+            "vendor",
+            "--exclude-from",
+            list.to_str().unwrap(),
+        ]))
+        .unwrap();
+        assert_eq!(config.excludes, ["gen"]);
+        assert_eq!(
+            config.scoped_excludes,
+            [(PathBuf::from("src"), vec!["vendor".to_string()])]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stdin_filter_takes_no_paths_or_other_modes() {
+        let base = ["-f", "H.txt", "--stdin-style", "hash"];
+        let config = Config::from_args(args(&base)).unwrap();
+        assert!(config.filters_stdin());
+        for extra in [&["src"][..], &["--check"], &["-f", "-"]] {
+            let given = [&base[..], extra].concat();
+            assert!(Config::from_args(args(&given)).is_err(), "{given:?}");
+        }
+        assert!(Config::from_args(args(&["-f", "H.txt", "--stdin-style", "nope"])).is_err());
+    }
+}
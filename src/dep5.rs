@@ -0,0 +1,259 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! `--dep5`: per-path SPDX headers from a REUSE `.reuse/dep5` file
+//!
+//! the file is in Debian's machine-readable copyright format: paragraphs
+//! separated by blank lines, `Field: value` with indented continuation
+//! lines. of the `Files:` paragraphs only `Files`, `Copyright`, `License`
+//! and lice's own `X-Lice-Header: no` are read. as in Debian, the last
+//! paragraph whose patterns match a file wins; `*` matches across `/`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// one `Files:` paragraph
+#[derive(Debug)]
+struct Entry {
+    patterns: Vec<String>,
+    /// the SPDX header text, `None` for `X-Lice-Header: no`
+    template: Option<Arc<str>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Dep5 {
+    /// the directory holding `.reuse`, patterns are relative to it
+    root: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl Dep5 {
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        // `.reuse/dep5` -> the project root
+        let canonical = fs::canonicalize(path)?;
+        let root = canonical
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("/"))
+            .to_path_buf();
+
+        let mut entries = Vec::new();
+        for paragraph in paragraphs(&raw) {
+            let field = |name: &str| {
+                paragraph
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.as_str())
+            };
+            let Some(files) = field("Files") else {
+                continue; // the header paragraph
+            };
+            let no_header = field("X-Lice-Header").is_some_and(|v| v.trim() == "no");
+            let template = match (field("Copyright"), field("License")) {
+                _ if no_header => None,
+                (Some(copyright), Some(license)) => Some(spdx_template(copyright, license)),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{}: paragraph for '{}' needs Copyright and License",
+                            path.display(),
+                            files.trim()
+                        ),
+                    ));
+                }
+            };
+            entries.push(Entry {
+                patterns: files.split_whitespace().map(str::to_string).collect(),
+                template,
+            });
+        }
+        Ok(Dep5 { root, entries })
+    }
+
+    /// the paragraph covering `path`: `Some(None)` if it needs no header,
+    /// `None` if no paragraph names it
+    pub(crate) fn lookup(&self, path: &Path) -> Option<Option<Arc<str>>> {
+        let canonical = fs::canonicalize(path).ok()?;
+        let relative = canonical.strip_prefix(&self.root).ok()?;
+        let relative: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        let relative = relative.join("/");
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.patterns.iter().any(|p| wildcard(p, &relative)))
+            .map(|entry| entry.template.clone())
+    }
+}
+
+/// `Field: value` pairs per paragraph, continuation lines joined with `\n`
+fn paragraphs(raw: &str) -> Vec<Vec<(String, String)>> {
+    let mut out = Vec::new();
+    let mut current: Vec<(String, String)> = Vec::new();
+    for line in raw.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                out.push(std::mem::take(&mut current));
+            }
+        } else if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = current.last_mut() {
+                // a lone `.` stands for an empty line
+                let text = line.trim();
+                value.push('\n');
+                value.push_str(if text == "." { "" } else { text });
+            }
+        } else if !line.starts_with('#')
+            && let Some((key, value)) = line.split_once(':')
+        {
+            current.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+/// the header a file should carry for `Copyright` and `License`
+fn spdx_template(copyright: &str, license: &str) -> Arc<str> {
+    let mut out = String::new();
+    for holder in copyright.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        out.push_str(&format!("SPDX-FileCopyrightText: {}\n", holder));
+    }
+    // only the first line is the expression, the rest is license text
+    let expression = license.lines().next().unwrap_or_default().trim();
+    out.push_str(&format!("\nSPDX-License-Identifier: {}\n", expression));
+    Arc::from(out)
+}
+
+/// dep5 patterns: `*` is any run of characters, `/` included, `?` is one
+fn wildcard(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    // classic two-pointer match with backtracking to the last `*`
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        match p.get(pi) {
+            Some('*') => {
+                star = Some((pi, ti));
+                pi += 1;
+            }
+            Some(&c) if c == '?' || c == t[ti] => {
+                pi += 1;
+                ti += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    pi = sp + 1;
+                    ti = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards_span_directories() {
+        assert!(wildcard("*", "src/a.rs"));
+        assert!(wildcard("src/*.rs", "src/deep/a.rs"));
+        assert!(!wildcard("src/*.rs", "lib/a.rs"));
+        assert!(wildcard("a?.py", "ab.py"));
+        assert!(!wildcard("a?.py", "a.py"));
+        assert!(wildcard("docs/*", "docs/"));
+    }
+
+    #[test]
+    fn paragraphs_join_continuation_lines() {
+        let raw = "Format: https://example.org\n\n\
+                   Files: a b\n# a comment\nCopyright: 2024 Jane\n 2025 John\n\
+                   License: MIT\n .\n full text\n";
+        let parsed = paragraphs(raw);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(
+            parsed[1],
+            [
+                ("Files".to_string(), "a b".to_string()),
+                ("Copyright".to_string(), "2024 Jane\n2025 John".to_string()),
+                ("License".to_string(), "MIT\n\nfull text".to_string()),
+            ]
+        );
+        assert_eq!(
+            &*spdx_template(&parsed[1][1].1, &parsed[1][2].1),
+            "SPDX-FileCopyrightText: 2024 Jane\nSPDX-FileCopyrightText: 2025 John\n\n\
+             SPDX-License-Identifier: MIT\n"
+        );
+    }
+
+    #[test]
+    fn the_last_matching_paragraph_wins() {
+        let dir = std::env::temp_dir().join(format!("lice-dep5-{}", std::process::id()));
+        fs::create_dir_all(dir.join(".reuse")).unwrap();
+        fs::create_dir_all(dir.join("vendor")).unwrap();
+        for name in ["a.rs", "vendor/b.rs", "gen.rs"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let dep5 = dir.join(".reuse/dep5");
+        fs::write(
+            &dep5,
+            "Files: *\nCopyright: 2024 Jane\nLicense: MIT\n\n\
+             Files: vendor/*\nCopyright: 2020 Vendor\nLicense: Apache-2.0\n\n\
+             Files: gen.rs\nX-Lice-Header: no\n",
+        )
+        .unwrap();
+        let dep5 = Dep5::load(&dep5).unwrap();
+        let license = |name: &str| {
+            dep5.lookup(&dir.join(name))
+                .map(|t| t.map(|t| t.lines().last().unwrap().to_string()))
+        };
+        assert_eq!(
+            license("a.rs"),
+            Some(Some("SPDX-License-Identifier: MIT".to_string()))
+        );
+        assert_eq!(
+            license("vendor/b.rs"),
+            Some(Some("SPDX-License-Identifier: Apache-2.0".to_string()))
+        );
+        assert_eq!(license("gen.rs"), Some(None));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_paragraph_without_a_license_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("lice-dep5-bad-{}", std::process::id()));
+        fs::create_dir_all(dir.join(".reuse")).unwrap();
+        let dep5 = dir.join(".reuse/dep5");
+        fs::write(&dep5, "Files: *.rs\nCopyright: 2024 Jane\n").unwrap();
+        let err = Dep5::load(&dep5).unwrap_err().to_string();
+        assert!(
+            err.ends_with("paragraph for '*.rs' needs Copyright and License"),
+            "{err}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
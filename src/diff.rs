@@ -0,0 +1,232 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! `--diff`: unified diffs of what `--dry-run` would write, std only
+//!
+//! headers change near the top, so the common prefix and suffix are cut off
+//! first and only the middle goes through an LCS table; past
+//! `MAX_TABLE_CELLS` the middle is shown as removed, then added.
+
+/// lines of context around each change, as `diff -u`
+const CONTEXT: usize = 3;
+
+/// largest LCS table built, in cells (old lines x new lines)
+const MAX_TABLE_CELLS: usize = 4_000_000;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// same line, at (old, new)
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// `old` -> `new` as a unified diff labelled `name`; empty if they are equal
+pub(crate) fn unified(old: &str, new: &str, name: &str, color: bool) -> String {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = edit_script(&a, &b);
+    if ops.iter().all(|op| matches!(op, Op::Equal(..))) {
+        return String::new();
+    }
+
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+    let mut out = String::new();
+    out.push_str(&paint(BOLD, &format!("--- a/{}", name)));
+    out.push('\n');
+    out.push_str(&paint(BOLD, &format!("+++ b/{}", name)));
+    out.push('\n');
+
+    for hunk in hunks(&ops) {
+        let old_before = ops[..hunk.start].iter().filter(|op| has_old(op)).count();
+        let new_before = ops[..hunk.start].iter().filter(|op| has_new(op)).count();
+        let ops = &ops[hunk];
+        let old_len = ops.iter().filter(|op| has_old(op)).count();
+        let new_len = ops.iter().filter(|op| has_new(op)).count();
+        let range = format!(
+            "@@ -{} +{} @@",
+            span(old_before, old_len),
+            span(new_before, new_len)
+        );
+        out.push_str(&paint(CYAN, &range));
+        out.push('\n');
+
+        for op in ops {
+            let (mark, line, code) = match *op {
+                Op::Equal(i, _) => (' ', a[i], ""),
+                Op::Delete(i) => ('-', a[i], RED),
+                Op::Insert(j) => ('+', b[j], GREEN),
+            };
+            let text = line.trim_end_matches('\n').trim_end_matches('\r');
+            let row = format!("{}{}", mark, text);
+            out.push_str(&if code.is_empty() {
+                row
+            } else {
+                paint(code, &row)
+            });
+            out.push('\n');
+            if !line.ends_with('\n') {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// `start,len` as in a hunk header, from the lines before the hunk; one
+/// line is just `start`, and an empty side starts at the line before
+fn span(before: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", before),
+        1 => (before + 1).to_string(),
+        _ => format!("{},{}", before + 1, len),
+    }
+}
+
+fn has_old(op: &Op) -> bool {
+    !matches!(op, Op::Insert(_))
+}
+
+fn has_new(op: &Op) -> bool {
+    !matches!(op, Op::Delete(_))
+}
+
+/// line-level edit script turning `a` into `b`
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal(i, i)).collect();
+    if mid_a.len().saturating_mul(mid_b.len()) <= MAX_TABLE_CELLS {
+        ops.extend(lcs_script(mid_a, mid_b, prefix));
+    } else {
+        ops.extend((0..mid_a.len()).map(|i| Op::Delete(prefix + i)));
+        ops.extend((0..mid_b.len()).map(|j| Op::Insert(prefix + j)));
+    }
+    let (tail_a, tail_b) = (a.len() - suffix, b.len() - suffix);
+    ops.extend((0..suffix).map(|k| Op::Equal(tail_a + k, tail_b + k)));
+    ops
+}
+
+/// classic LCS table walk, indices shifted by `offset`; deletions come first
+fn lcs_script(a: &[&str], b: &[&str], offset: usize) -> Vec<Op> {
+    let width = b.len() + 1;
+    // table[i * width + j]: LCS length of a[i..] and b[j..]
+    let mut table = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i * width + j] = if a[i] == b[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(Op::Equal(offset + i, offset + j));
+            i += 1;
+            j += 1;
+        } else if i < a.len()
+            && (j == b.len() || table[(i + 1) * width + j] >= table[i * width + j + 1])
+        {
+            ops.push(Op::Delete(offset + i));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(offset + j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+/// op ranges of each hunk: the changes plus `CONTEXT` equal lines around
+/// them, merging changes closer than twice that
+fn hunks(ops: &[Op]) -> Vec<std::ops::Range<usize>> {
+    let mut out: Vec<std::ops::Range<usize>> = Vec::new();
+    for (k, op) in ops.iter().enumerate() {
+        if matches!(op, Op::Equal(..)) {
+            continue;
+        }
+        let start = k.saturating_sub(CONTEXT);
+        let end = (k + 1 + CONTEXT).min(ops.len());
+        match out.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => out.push(start..end),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_change_is_one_hunk_with_context() {
+        let old = "// Copyright 2020 Old\n\nfn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\n";
+        let new = "// Copyright 2024 New\n\nfn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\n";
+        assert_eq!(
+            unified(old, new, "src/a.rs", false),
+            "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,4 +1,4 @@\n\
+             -// Copyright 2020 Old\n+// Copyright 2024 New\n \n fn a() {}\n fn b() {}\n"
+        );
+    }
+
+    #[test]
+    fn equal_inputs_give_no_diff() {
+        assert_eq!(unified("x\n", "x\n", "a", true), "");
+    }
+
+    #[test]
+    fn inserted_header_and_missing_newline() {
+        assert_eq!(
+            unified("x", "# H\n\nx", "a.py", false),
+            "--- a/a.py\n+++ b/a.py\n@@ -1 +1,3 @@\n+# H\n+\n x\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified("x", "x\n", "a.py", false),
+            "--- a/a.py\n+++ b/a.py\n@@ -1 +1 @@\n-x\n\\ No newline at end of file\n+x\n"
+        );
+    }
+
+    #[test]
+    fn color_wraps_the_marked_lines() {
+        let out = unified("a\n", "b\n", "f", true);
+        assert!(out.contains(&format!("{}-a{}\n", RED, RESET)));
+        assert!(out.contains(&format!("{}+b{}\n", GREEN, RESET)));
+        assert!(out.contains(&format!("{}@@ -1 +1 @@{}\n", CYAN, RESET)));
+    }
+}
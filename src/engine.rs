@@ -0,0 +1,7127 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! the core engine: discovery, detection and rewriting
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, mpsc};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{env, fs, io, process};
+
+use crate::bench;
+use crate::cache::{self, Cache};
+use crate::config::{
+    ColorWhen, Config, FinalNewline, Format, GitFilter, Mode, Position, SpdxPolicy,
+    is_placeholder_name,
+};
+use crate::dep5::Dep5;
+use crate::diff;
+use crate::gitattributes::{Eol, GitAttributes};
+use crate::glob;
+use crate::json;
+use crate::licenses;
+use crate::style::{BUILTIN_STYLES, LanguageProfile, StyleKey, get_language_style};
+
+/// a discovered file: its place in discovery order, its path, and whether it
+/// was listed explicitly
+type Job = (u64, PathBuf, bool);
+
+/// what a worker sends back for each job
+struct Finished {
+    seq: u64,
+    path: PathBuf,
+    outcome: Option<Outcome>,
+    /// `--ordered`: the output held back while processing it
+    output: Vec<Held>,
+}
+
+/// a piece of held-back output, and whether it goes to stderr
+type Held = (bool, String);
+
+thread_local! {
+    /// `--ordered` and `--format jsonl`: output of the file this worker is
+    /// on, `None` when printing straight away
+    static HELD: RefCell<Option<Vec<Held>>> = const { RefCell::new(None) };
+    /// `--format jsonl`: the status line of the file this thread is on,
+    /// kept for its record; `None` outside a file
+    static NOTE: RefCell<Option<Option<String>>> = const { RefCell::new(None) };
+}
+
+/// run-wide counters, shared by every worker
+#[derive(Default)]
+struct Stats {
+    /// header already up to date
+    compliant: AtomicUsize,
+    /// header missing or outdated
+    non_compliant: AtomicUsize,
+    /// files with an extension no style is known for
+    unsupported: AtomicUsize,
+    /// files that fail a read-only policy check (e.g. `--report-unlicensed`)
+    violations: AtomicUsize,
+    /// `--dry-run`: files that would have been rewritten
+    would_change: AtomicUsize,
+    /// files handed to `process_file` past `--match`
+    scanned: AtomicUsize,
+    /// directories (or entries) the walk couldn't read
+    unreadable: AtomicUsize,
+    /// files that failed, for `--max-errors`
+    errors: AtomicUsize,
+    /// bytes of rewritten content, for the throughput line
+    bytes_written: AtomicU64,
+    /// `--count-lines`: files a header was written into, the comment lines
+    /// written in total, and the most in one file
+    header_files: AtomicUsize,
+    header_lines: AtomicUsize,
+    max_header_lines: AtomicUsize,
+}
+
+impl Stats {
+    fn bump(counter: &AtomicUsize) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(counter: &AtomicUsize) -> usize {
+        counter.load(Ordering::Relaxed)
+    }
+}
+
+/// formatted headers of one template
+type HeadersByStyle = HashMap<StyleKey, Arc<str>>;
+
+/// one extension per built-in style, for `--verify-license-file` and `--selftest`
+const STYLE_SAMPLES: [(&str, &str); 6] = [
+    ("c-like", "c"),
+    ("double-slash", "rs"),
+    ("hash", "py"),
+    ("dash", "lua"),
+    ("html", "md"),
+    ("batch", "bat"),
+];
+
+/// `--selftest`: the code of the sample file, nothing a style could
+/// take for a comment
+const SELFTEST_BODY: &str = "value = 1\n\nvalue = 2\n";
+
+/// longest `#!` line looked at for files without an extension
+const SHEBANG_SCAN_BYTES: u64 = 256;
+
+/// default in-flight paths per worker when `--queue-cap` is not given
+const QUEUE_SLOTS_PER_WORKER: usize = 64;
+
+/// what happened to a file; in the read-only modes, what would happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// header already up to date
+    Ok,
+    /// no header before, one was inserted
+    Added,
+    /// an existing header was replaced (or its year bumped)
+    Updated,
+    /// `--normalize`: a header with the right text was reformatted
+    Normalized,
+    /// `--strip-trailing-whitespace`: a current header lost its trailing
+    /// whitespace
+    Cleaned,
+    /// `--remove`: the license header was taken out
+    Removed,
+    /// left alone: unsupported type, malformed comment, nothing to update
+    Skipped,
+    /// reading, writing or verifying the file failed
+    Failed,
+}
+
+impl Outcome {
+    /// every outcome, in the order tallies list them
+    const ALL: [Outcome; 8] = [
+        Outcome::Ok,
+        Outcome::Added,
+        Outcome::Updated,
+        Outcome::Normalized,
+        Outcome::Cleaned,
+        Outcome::Removed,
+        Outcome::Skipped,
+        Outcome::Failed,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Outcome::Ok => "License OK",
+            Outcome::Added => "License added",
+            Outcome::Updated => "License updated",
+            Outcome::Normalized => "License normalized",
+            Outcome::Cleaned => "Header whitespace cleaned",
+            Outcome::Removed => "License removed",
+            Outcome::Skipped => "Skipped",
+            Outcome::Failed => "Failed",
+        }
+    }
+
+    /// `--check`: the change that would be made
+    fn pending_label(self) -> &'static str {
+        match self {
+            Outcome::Ok => "License OK",
+            Outcome::Added => "License missing",
+            Outcome::Updated => "License outdated",
+            Outcome::Cleaned => "Header has trailing whitespace",
+            other => other.label(),
+        }
+    }
+
+    /// `--count-by-language` and `--format jsonl`: one word for the outcome,
+    /// as `mode` would say it
+    fn tally_name(self, mode: Mode) -> &'static str {
+        let pending = matches!(mode, Mode::Check | Mode::Enforce | Mode::DryRun);
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Added if pending => "missing",
+            Outcome::Added => "added",
+            Outcome::Updated if pending => "outdated",
+            Outcome::Updated => "updated",
+            Outcome::Normalized => "normalized",
+            Outcome::Cleaned => "cleaned",
+            Outcome::Removed => "removed",
+            Outcome::Skipped => "skipped",
+            Outcome::Failed => "failed",
+        }
+    }
+
+    /// `--dry-run`: the change that would be made, preview follows
+    fn dry_run_label(self) -> &'static str {
+        match self {
+            Outcome::Added => "Would add license",
+            Outcome::Updated => "Would update license",
+            Outcome::Cleaned => "Would clean header whitespace",
+            other => other.pending_label(),
+        }
+    }
+}
+
+/// a [`Visitor`]'s answer to a proposed change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// go ahead: write it, or in the read-only modes report it
+    Proceed,
+    /// leave the file as it is; it is reported as [`Outcome::Skipped`]
+    Veto,
+}
+
+/// per-file hook for [`LiceEngine::run_with`]
+///
+/// called from the worker threads, so it must be `Send + Sync`; both methods
+/// default to doing nothing
+pub trait Visitor: Send + Sync {
+    /// `path` would become `proposed` (`Added`, `Updated`, `Normalized` or
+    /// `Cleaned`), decided and not yet written. in the read-only modes
+    /// (`--check`, `--dry-run`) nothing is written either way, but a vetoed
+    /// file is not counted as a violation
+    fn review(&self, path: &Path, outcome: Outcome, proposed: &str) -> Verdict {
+        let _ = (path, outcome, proposed);
+        Verdict::Proceed
+    }
+
+    /// `path` is done, with the outcome it gets in the [`Report`]
+    fn finished(&self, path: &Path, outcome: Outcome) {
+        let _ = (path, outcome);
+    }
+}
+
+/// what a whole run did, returned by [`LiceEngine::run`]
+#[derive(Debug, Default)]
+pub struct Report {
+    /// files with an outcome, i.e. `outcomes.len()`
+    pub total: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub normalized: usize,
+    pub cleaned: usize,
+    pub removed: usize,
+    pub ok: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    /// files failing a policy (`--check`, `--report-unlicensed`, `--verify`,
+    /// `--fail-fast`, ...); the CLI exits non-zero when this isn't 0
+    pub violations: usize,
+    /// directories (or entries) the walk couldn't read, so whatever is below
+    /// them has no outcome; violations too under `--strict`
+    pub unreadable: usize,
+    /// every file in the order it finished, which varies between runs with `-j`
+    pub outcomes: Vec<(PathBuf, Outcome)>,
+}
+
+impl Report {
+    fn new(outcomes: Vec<(PathBuf, Outcome)>, violations: usize) -> Self {
+        let mut report = Report {
+            total: outcomes.len(),
+            violations,
+            ..Report::default()
+        };
+        for (_, outcome) in &outcomes {
+            let counter = match outcome {
+                Outcome::Ok => &mut report.ok,
+                Outcome::Added => &mut report.added,
+                Outcome::Updated => &mut report.updated,
+                Outcome::Normalized => &mut report.normalized,
+                Outcome::Cleaned => &mut report.cleaned,
+                Outcome::Removed => &mut report.removed,
+                Outcome::Skipped => &mut report.skipped,
+                Outcome::Failed => &mut report.errors,
+            };
+            *counter += 1;
+        }
+        report.outcomes = outcomes;
+        report
+    }
+}
+
+/// severity of a per-file diagnostic
+#[derive(Debug, Clone, Copy)]
+enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// where in a file a diagnostic points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    /// 1-based
+    line: usize,
+    /// byte offset from the start of the file
+    offset: usize,
+}
+
+impl Location {
+    fn of(text: &[u8], offset: usize) -> Self {
+        let line = text[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+        Location { line, offset }
+    }
+}
+
+/// why a file could not be processed, and where in it when that is known
+#[derive(Debug)]
+struct FileError {
+    message: String,
+    at: Option<Location>,
+    /// the file is there but may not be read
+    denied: bool,
+}
+
+impl FileError {
+    fn new(message: impl Into<String>) -> Self {
+        FileError {
+            message: message.into(),
+            at: None,
+            denied: false,
+        }
+    }
+}
+
+impl From<io::Error> for FileError {
+    fn from(e: io::Error) -> Self {
+        FileError::new(e.to_string())
+    }
+}
+
+pub struct LiceEngine {
+    config: Config,
+    raw_license_text: String,
+    /// the template as read, before uncommenting and placeholders
+    template_source: String,
+    /// formatted header per (customized) style, so workers don't reformat per file
+    headers: HashMap<StyleKey, String>,
+    /// canonical `--out-dir`, kept out of the walk so results aren't re-processed
+    out_dir: Option<PathBuf>,
+    gitattributes: GitAttributes,
+    stats: Stats,
+    /// set to stop the walk and the workers early (e.g. `--fail-fast`)
+    cancelled: AtomicBool,
+    /// `--cache`, when the mode can make use of it
+    cache: Option<Cache>,
+    /// `--dep5`: SPDX headers by path, ahead of every other template
+    dep5: Option<Dep5>,
+    /// `--git-filter`: the files git lists, absolute with canonical directories
+    git_files: Option<HashSet<PathBuf>>,
+    /// `--enforce`: files no style is known for
+    unknown_types: Mutex<Vec<PathBuf>>,
+    /// `--summarize-unsupported`: files seen per unsupported extension
+    unsupported_exts: Mutex<HashMap<String, usize>>,
+    /// `--project-root`: template per extension, `None` when there is no file
+    project_templates: Mutex<HashMap<String, Option<Arc<str>>>>,
+    /// `--rule`: path glob and its template, in command-line order
+    rules: Vec<(String, Arc<str>)>,
+    /// headers formatted from a per-file template, by template and style
+    formatted: Mutex<HashMap<Arc<str>, HeadersByStyle>>,
+    /// `--color`, resolved against the terminal once
+    color: bool,
+    /// `--patch`: the canonical root and the diff of every changed file
+    patch_root: Option<PathBuf>,
+    patches: Mutex<Vec<(String, String)>>,
+    /// `run_with`: the embedder's hook
+    visitor: Option<Box<dyn Visitor>>,
+    /// `--verify-idempotent`: written files a `--check` would still flag
+    churned: Mutex<Vec<PathBuf>>,
+}
+
+impl LiceEngine {
+    /// read license file and preprocess header
+    ///
+    /// fails with `InvalidInput` for a config that asked for help, which
+    /// skipped validation, or has no template at all
+    pub fn new(config: Config) -> Result<Self, io::Error> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message);
+        if config.help_requested() {
+            return Err(invalid(
+                "-h asks for the usage text, there is nothing to run",
+            ));
+        }
+        // `-f` templates, each read (and uncommented) on its own
+        let mut parts = Vec::new();
+        for spec in &config.license_files {
+            parts.push(if spec == "-" {
+                io::read_to_string(io::stdin())?
+            } else if is_url(spec) {
+                Self::fetch_license(spec)?
+            } else {
+                Self::read_license_file(Path::new(spec))?
+            });
+        }
+        // reported once the engine exists, in the configured format
+        let mut sample_reformats = false;
+        let source = if let Some(id) = &config.license_id {
+            let author = config.author.as_deref().unwrap_or_default();
+            licenses::template(id)
+                .ok_or_else(|| invalid(&format!("unknown --license-id: {}", id)))?
+                .replace("{author}", author)
+        } else if config.init {
+            INIT_TEMPLATE.to_string()
+        } else if config.self_benchmark.is_some() {
+            bench::TEMPLATE.to_string()
+        } else if !parts.is_empty() {
+            join_templates(parts.clone())
+        } else if let Some(sample) = &config.header_from {
+            let (raw, reformats) = Self::template_from_sample(&config, sample)?;
+            sample_reformats = reformats;
+            raw
+        } else {
+            return Err(invalid(
+                "no license template: give -f, --header-from or --license-id",
+            ));
+        };
+        // a `-f` template may have been pasted with its comment markers
+        let raw = match config.license_id {
+            None if !parts.is_empty() => {
+                let bare: Vec<String> = parts.iter().map(|part| uncomment_template(part)).collect();
+                let markers_only = bare
+                    .iter()
+                    .zip(&parts)
+                    .zip(&config.license_files)
+                    .find(|((bare, part), _)| bare.trim().is_empty() && !part.trim().is_empty());
+                if let Some((_, spec)) = markers_only {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: the template is only comment markers, no text", spec),
+                    ));
+                }
+                join_templates(bare)
+            }
+            _ => source.clone(),
+        };
+        // placeholders are filled in whatever the template came from
+        let raw = expand_template(&raw, &config.header_vars);
+        let raw = if config.header_checksum {
+            with_checksum(&raw)
+        } else {
+            raw
+        };
+
+        // every style a file can end up with: the built-ins plus any
+        // per-extension variants (e.g. `--blank-lines py=2`, `--prepend-raw`)
+        let overridden = config
+            .blank_lines_by_ext
+            .keys()
+            .chain(&config.prepend_raw)
+            .chain(config.line_comments.keys())
+            .chain(config.ext_styles.keys());
+        let headers = BUILTIN_STYLES
+            .iter()
+            .map(|&style| config.customize(style))
+            .chain(overridden.filter_map(|ext| config.style_for_ext(ext)))
+            .map(|style| (style.into(), Self::make_header_for_style(&raw, style)))
+            .collect();
+
+        let rules = config
+            .rules
+            .iter()
+            .map(|(glob, file)| {
+                let raw = uncomment_template(&Self::read_license_file(file)?);
+                Ok((
+                    glob.clone(),
+                    Arc::from(expand_template(&raw, &config.header_vars)),
+                ))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        // a read-only tally or an in-place apply can trust a compliant entry;
+        // mirroring every file (`--copy-unchanged`) needs the contents anyway
+        let cache = match &config.cache {
+            Some(path)
+                if matches!(
+                    config.mode,
+                    Mode::Apply | Mode::Check | Mode::Enforce | Mode::DryRun | Mode::CountOnly
+                ) && !config.copy_unchanged =>
+            {
+                Some(Cache::load(
+                    path,
+                    Self::fingerprint(&config, &raw, &headers, &rules),
+                ))
+            }
+            _ => None,
+        };
+
+        let dep5 = config.dep5.as_deref().map(Dep5::load).transpose()?;
+        let git_files = match config.git_filter {
+            GitFilter::All => None,
+            filter => Some(git_file_set(filter)?),
+        };
+
+        let out_dir = match &config.out_dir {
+            // `--dump-config` runs nothing and the read-only modes write
+            // nothing, so neither creates anything either
+            Some(dir) if !config.dump_config && config.mode.writes() => {
+                fs::create_dir_all(dir)?;
+                Some(fs::canonicalize(dir)?)
+            }
+            _ => None,
+        };
+
+        let color = match config.color {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => io::stdout().is_terminal(),
+        };
+
+        let patch_root = match &config.patch {
+            Some(_) => Some(fs::canonicalize(
+                config.patch_root.as_deref().unwrap_or(Path::new(".")),
+            )?),
+            None => None,
+        };
+
+        let engine = Self {
+            config,
+            template_source: source,
+            raw_license_text: raw,
+            headers,
+            out_dir,
+            gitattributes: GitAttributes::default(),
+            stats: Stats::default(),
+            cancelled: AtomicBool::new(false),
+            cache,
+            dep5,
+            git_files,
+            unknown_types: Mutex::new(Vec::new()),
+            unsupported_exts: Mutex::new(HashMap::new()),
+            project_templates: Mutex::new(HashMap::new()),
+            rules,
+            formatted: Mutex::new(HashMap::new()),
+            color,
+            patch_root,
+            patches: Mutex::new(Vec::new()),
+            visitor: None,
+            churned: Mutex::new(Vec::new()),
+        };
+        if let Some(sample) = engine.config.header_from.as_deref()
+            && sample_reformats
+        {
+            engine.diag(
+                Level::Warn,
+                sample,
+                "--header-from: header does not round-trip exactly, \
+                 files carrying it verbatim will be reformatted",
+            );
+        }
+        if engine.config.warn_unset_vars {
+            let config = &engine.config;
+            let main = config
+                .license_files
+                .first()
+                .map(PathBuf::from)
+                .or_else(|| config.header_from.clone())
+                .unwrap_or_else(|| PathBuf::from("template"));
+            engine.warn_unset_vars(&main, &engine.raw_license_text);
+            for ((_, file), (_, template)) in config.rules.iter().zip(&engine.rules) {
+                engine.warn_unset_vars(Path::new(file), template);
+            }
+        }
+        Ok(engine)
+    }
+
+    /// `--warn-unset-vars`: one warning per `{name}` left in `template`
+    fn warn_unset_vars(&self, source: &Path, template: &str) {
+        for name in unset_placeholders(template) {
+            let message = format!("Placeholder {{{}}} has no --header-var, kept as is", name);
+            self.diag(Level::Warn, source, &message);
+        }
+    }
+
+    /// everything that decides whether a file is compliant, hashed for the cache
+    fn fingerprint(
+        config: &Config,
+        raw: &str,
+        headers: &HashMap<StyleKey, String>,
+        rules: &[(String, Arc<str>)],
+    ) -> u64 {
+        let mut formatted: Vec<&String> = headers.values().collect();
+        formatted.sort();
+        let key = format!(
+            "{:?}{:?}{:?}{:?}{:?}{:?}{:?}{}{}{:?}{:?}{:?}{:?}{}{}{}{:?}{:?}{}{}{:?}{}{}{}{:?}{:?}",
+            raw,
+            formatted,
+            // two extensions may share a header but not a style
+            config.line_comments.iter().collect::<BTreeMap<_, _>>(),
+            config.ext_styles.iter().collect::<BTreeMap<_, _>>(),
+            config.interpreter_styles.iter().collect::<BTreeMap<_, _>>(),
+            rules,
+            config.position,
+            config.preserve_blank_run,
+            config.gitattributes,
+            Self::project_template_files(config),
+            config
+                .dep5
+                .as_ref()
+                .and_then(|p| fs::read_to_string(p).ok()),
+            // what counts as an existing header
+            config.keep_at_top,
+            config.keep_first,
+            config.header_above_leading_line,
+            config.after_package,
+            config.keep_leading_comments,
+            config.license_keywords,
+            config.force_style,
+            config.header_max_scan_lines,
+            // what is still rewritten or refused though the header is current
+            config.fix_blank_lines,
+            config.spdx_policy,
+            config.strip_trailing_whitespace,
+            config.dedupe_headers,
+            config.no_guess,
+            // `.lice/HEADER.*` files above are hashed before substitution
+            &config.header_vars,
+            // a file may have been compliant by `--ok-if-matches` alone
+            config
+                .ok_if_matches
+                .iter()
+                .map(|re| re.as_str())
+                .collect::<Vec<_>>(),
+        );
+        cache::fnv1a(key.as_bytes())
+    }
+
+    /// every `.lice/HEADER.*` under `--project-root` with its contents, sorted
+    fn project_template_files(config: &Config) -> Vec<(PathBuf, String)> {
+        let Some(Ok(entries)) = config
+            .project_root
+            .as_ref()
+            .map(|root| fs::read_dir(root.join(".lice")))
+        else {
+            return Vec::new();
+        };
+        let mut files: Vec<(PathBuf, String)> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("HEADER."))
+            })
+            .filter_map(|path| fs::read_to_string(&path).ok().map(|text| (path, text)))
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// `--project-root`: the template for files ending in `.ext`, read on first use
+    fn project_template(&self, ext: &str) -> io::Result<Option<Arc<str>>> {
+        let Some(root) = &self.config.project_root else {
+            return Ok(None);
+        };
+        if let Some(known) = self.project_templates.lock().unwrap().get(ext) {
+            return Ok(known.clone());
+        }
+
+        let path = root.join(".lice").join(format!("HEADER.{}", ext));
+        let template = match fs::read_to_string(&path) {
+            Ok(raw) => {
+                let template = expand_template(&uncomment_template(&raw), &self.config.header_vars);
+                if self.config.warn_unset_vars {
+                    self.warn_unset_vars(&path, &template);
+                }
+                Some(Arc::from(template))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("template {}: {}", path.display(), e),
+                ));
+            }
+        };
+        // two workers may both read it the first time, either result is the same
+        self.project_templates
+            .lock()
+            .unwrap()
+            .insert(ext.to_string(), template.clone());
+        Ok(template)
+    }
+
+    /// `--rule`: the template of the first rule whose glob matches `path`
+    fn rule_template(&self, path: &Path) -> Option<Arc<str>> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        let path = match_path(path);
+        self.rules
+            .iter()
+            .find(|(glob, _)| glob::matches(glob, &path))
+            .map(|(_, template)| Arc::clone(template))
+    }
+
+    /// `-f`: read the template, with a readable error for the usual mistakes
+    fn read_license_file(path: &Path) -> io::Result<String> {
+        match fs::metadata(path) {
+            Ok(meta) if meta.is_dir() => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("license file is a directory: {}", path.display()),
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("license file not found: {}", path.display()),
+            )),
+            _ => fs::read_to_string(path),
+        }
+    }
+
+    /// `-f <URL>`: fetched once here, every file then uses the same text
+    #[cfg(feature = "http")]
+    fn fetch_license(url: &str) -> io::Result<String> {
+        crate::fetch::get(url)
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to fetch {}: {}", url, e)))
+    }
+
+    #[cfg(not(feature = "http"))]
+    fn fetch_license(url: &str) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{}: fetching the license file needs a build with `--features http`",
+                url
+            ),
+        ))
+    }
+
+    /// `--archive`: license the entries of a zip file, rewritten in place
+    #[cfg(feature = "zip")]
+    fn process_archive(&self, path: &Path) -> io::Result<Vec<(PathBuf, Outcome)>> {
+        let in_archive =
+            |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+        let bytes = fs::read(path).map_err(in_archive)?;
+        let mut archive = crate::zip::Archive::read(&bytes).map_err(in_archive)?;
+
+        let mut outcomes = Vec::new();
+        let mut changed = false;
+        for entry in archive.entries_mut() {
+            let shown = path.join(&entry.name);
+            let (outcome, note) = capture_note(|| self.license_entry(&shown, entry));
+            let outcome = outcome.map_err(in_archive)?;
+            self.record_file(&shown, outcome, note);
+            if let Some(outcome) = outcome {
+                changed |= matches!(outcome, Outcome::Added | Outcome::Updated);
+                outcomes.push((shown, outcome));
+            }
+        }
+
+        if changed && self.config.mode == Mode::Apply {
+            // through a sibling, so a failed write leaves the archive whole
+            let temp = path.with_extension("lice-tmp");
+            let bytes = archive.write().map_err(in_archive)?;
+            fs::write(&temp, &bytes).map_err(in_archive)?;
+            fs::rename(&temp, path).map_err(in_archive)?;
+            self.stats
+                .bytes_written
+                .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        }
+        Ok(outcomes)
+    }
+
+    #[cfg(not(feature = "zip"))]
+    fn process_archive(&self, path: &Path) -> io::Result<Vec<(PathBuf, Outcome)>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{}: processing archives needs a build with `--features zip`",
+                path.display()
+            ),
+        ))
+    }
+
+    /// one archive entry, `apply_license` without the filesystem; `None`
+    /// for directories and entries of unsupported types
+    #[cfg(feature = "zip")]
+    fn license_entry(
+        &self,
+        shown: &Path,
+        entry: &mut crate::zip::Entry,
+    ) -> io::Result<Option<Outcome>> {
+        let name = PathBuf::from(&entry.name);
+        let style = name
+            .extension()
+            .and_then(|s| s.to_str())
+            .and_then(|ext| self.style_for_ext(ext));
+        let Some(style) = style.filter(|_| !entry.is_dir()) else {
+            return Ok(None);
+        };
+        Stats::bump(&self.stats.scanned);
+
+        let Some(data) = entry.contents()? else {
+            self.diag(
+                Level::Warn,
+                shown,
+                "Skipping, encrypted or compressed with an unsupported method",
+            );
+            return Ok(Some(Outcome::Skipped));
+        };
+        let Ok(content) = String::from_utf8(data) else {
+            self.diag(Level::Warn, shown, "Skipping, not valid UTF-8");
+            return Ok(Some(Outcome::Skipped));
+        };
+
+        let template = self.rule_template(&name);
+        let header = self.header_with_eol(style, template.as_deref(), Eol::detect(&content));
+        if self.is_compliant(&content, &header, style) {
+            self.report(shown, Outcome::Ok);
+            return Ok(Some(Outcome::Ok));
+        }
+        let outcome = self.pending_outcome(&content, style);
+        let Some(new_content) = apply_license_with(&content, &header, style, &self.config) else {
+            self.diag(Level::Warn, shown, "Unclosed block comment, skipping");
+            return Ok(Some(Outcome::Skipped));
+        };
+        if self.vetoed(shown, outcome, &new_content) {
+            return Ok(Some(Outcome::Skipped));
+        }
+
+        match self.config.mode {
+            Mode::Check | Mode::Enforce => {
+                Stats::bump(&self.stats.violations);
+                self.diag(Level::Info, shown, outcome.pending_label());
+            }
+            Mode::DryRun => {
+                Stats::bump(&self.stats.would_change);
+                if self.config.diff {
+                    self.print_diff(shown, &content, &new_content);
+                } else {
+                    let _stdout = io::stdout().lock();
+                    self.diag(Level::Info, shown, outcome.dry_run_label());
+                    self.preview(&header);
+                }
+            }
+            _ => {
+                entry.replace(new_content.as_bytes())?;
+                self.count_header_lines(&header);
+                self.report(shown, outcome);
+            }
+        }
+        Ok(Some(outcome))
+    }
+
+    /// `--header-from`: recover the raw license text from a file's existing header,
+    /// and whether rendering it back changes that header
+    fn template_from_sample(config: &Config, sample: &Path) -> io::Result<(String, bool)> {
+        let invalid = |msg: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {:?}", msg, sample))
+        };
+
+        let ext = sample.extension().and_then(|s| s.to_str()).unwrap_or("");
+        let style = config
+            .style_for_ext(ext)
+            .ok_or_else(|| invalid("--header-from: unsupported file type"))?;
+
+        let content = fs::read_to_string(sample)?;
+        let (_, body) = split_preamble(&content, style, config);
+        let span = existing_header_span(body, style, config)
+            .ok_or_else(|| invalid("--header-from: no license header found"))?;
+        let commented = &body[span];
+        let raw = strip_comment(commented, style);
+
+        // the template must reproduce the sample exactly, or every file
+        // carrying the "same" header would be rewritten
+        let rendered = Self::make_header_for_style(&raw, style);
+        let reformats = rendered.trim_end() != commented.trim_end().replace("\r\n", "\n");
+        Ok((raw, reformats))
+    }
+
+    /// [`run`](Self::run), showing every file to `visitor`: each proposed
+    /// change before it is made, which it may veto, and each final outcome
+    pub fn run_with(mut self, visitor: impl Visitor + 'static) -> io::Result<Report> {
+        self.visitor = Some(Box::new(visitor));
+        self.run()
+    }
+
+    /// entry, returns what happened to every file
+    pub fn run(self) -> io::Result<Report> {
+        let started = Instant::now();
+        let num_threads = self.config.resolved_jobs();
+        // bounded: the walker blocks once workers fall this far behind
+        let queue_cap = self
+            .config
+            .queue_cap
+            .unwrap_or(num_threads * QUEUE_SLOTS_PER_WORKER);
+
+        if self.config.dump_config {
+            println!("{}", self.config.dump(num_threads, queue_cap));
+            return Ok(Report::default());
+        }
+        if self.config.init {
+            self.init()?;
+            return Ok(Report::default());
+        }
+        if let Some(files) = self.config.self_benchmark {
+            bench::run(files, num_threads)?;
+            return Ok(Report::default());
+        }
+        if let Some(path) = &self.config.explain {
+            self.explain(path)?;
+            return Ok(Report::default());
+        }
+        if self.config.verify_license_file {
+            let issues = self.verify_template();
+            return Ok(Report::new(Vec::new(), issues));
+        }
+        if self.config.selftest {
+            let failed = self.selftest();
+            return Ok(Report::new(Vec::new(), failed));
+        }
+        if self.config.filters_stdin() {
+            self.filter_stdin()?;
+            return Ok(Report::default());
+        }
+        if let Some(path) = &self.config.archive {
+            let outcomes = self.process_archive(path)?;
+            return Ok(self.finish(outcomes, started));
+        }
+
+        // ============================
+        // Mode A: Single-thread
+        // ============================
+        let banner = !self.config.mode.is_terse()
+            && self.config.format == Format::Text
+            && !self.config.quiet;
+
+        if num_threads == 1 {
+            if banner {
+                println!("Running in single-threaded mode.");
+            }
+            let mut outcomes = Vec::new();
+            let mut walk = self.candidates();
+            while let Some((path, explicit)) = walk.next_entry() {
+                if self.is_cancelled() {
+                    break;
+                }
+                if let (Some(outcome), _) = self.run_job(&path, explicit, false) {
+                    outcomes.push((path, outcome));
+                }
+            }
+            return Ok(self.finish(outcomes, started));
+        }
+
+        // ============================
+        // Mode B: Multi-thread
+        // ============================
+        let shared_engine = Arc::new(self);
+        let (tx, rx) = mpsc::sync_channel::<Job>(queue_cap);
+        let shared_rx = Arc::new(Mutex::new(rx));
+        // unbounded, so a worker never waits on the printer to report
+        let (results_tx, results_rx) = mpsc::channel::<Finished>();
+        let ordered = shared_engine.config.ordered;
+        // `--format jsonl` records go out through the printer as well
+        let hold = ordered || shared_engine.config.format == Format::JsonLines;
+        let printer = thread::spawn(move || collect_results(results_rx, ordered));
+
+        if banner {
+            println!("Starting {} worker threads...", num_threads);
+        }
+
+        let mut handles = Vec::with_capacity(num_threads);
+        for _ in 0..num_threads {
+            let thread_rx = Arc::clone(&shared_rx);
+            let thread_engine = Arc::clone(&shared_engine);
+            let results_tx = results_tx.clone();
+
+            handles.push(thread::spawn(move || {
+                loop {
+                    // get the job
+                    let (seq, path, explicit) = match thread_rx.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break, // exit
+                    };
+                    // once cancelled keep draining, so a walker blocked
+                    // on the bounded queue can still finish and hang up
+                    let (outcome, output) = thread_engine.run_job(&path, explicit, hold);
+                    // the printer outlives every worker
+                    let _ = results_tx.send(Finished {
+                        seq,
+                        path,
+                        outcome,
+                        output,
+                    });
+                }
+            }));
+        }
+
+        match shared_engine.config.parallel_walk {
+            Some(walkers) => Self::walk_in_parallel(&shared_engine, walkers, tx),
+            None => {
+                // main thread
+                let mut walk = shared_engine.candidates();
+                let mut seq = 0;
+                while let Some((path, explicit)) = walk.next_entry() {
+                    if shared_engine.is_cancelled() {
+                        break;
+                    }
+                    if let Err(e) = tx.send((seq, path, explicit)) {
+                        eprintln!("Failed to send task: {}", e);
+                    }
+                    seq += 1;
+                }
+                drop(tx);
+            }
+        }
+        drop(results_tx);
+
+        for h in handles {
+            h.join().unwrap();
+        }
+        let outcomes = printer.join().unwrap();
+        Ok(shared_engine.finish(outcomes, started))
+    }
+
+    /// `--parallel-walk`: `walkers` threads share the directory walk and feed
+    /// the workers' queue; returns once the whole tree has been walked
+    fn walk_in_parallel(engine: &Arc<Self>, walkers: usize, tx: mpsc::SyncSender<Job>) {
+        let queue = Arc::new(WalkQueue::new(engine.candidates().stack));
+        let sequence = Arc::new(AtomicU64::new(0));
+        let handles: Vec<_> = (0..walkers)
+            .map(|_| {
+                let engine = Arc::clone(engine);
+                let queue = Arc::clone(&queue);
+                let sequence = Arc::clone(&sequence);
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    while let Some((path, explicit)) = queue.pop() {
+                        // once cancelled, finish what's queued without walking further
+                        if engine.is_cancelled() {
+                            queue.finish(Vec::new());
+                            continue;
+                        }
+                        let mut children = Vec::new();
+                        let job = engine.visit(path, explicit, &mut children);
+                        queue.finish(children);
+                        if let Some((path, explicit)) = job
+                            && let Err(e) =
+                                tx.send((sequence.fetch_add(1, Ordering::Relaxed), path, explicit))
+                        {
+                            eprintln!("Failed to send task: {}", e);
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+        for h in handles {
+            h.join().unwrap();
+        }
+    }
+
+    /// print end-of-run output and assemble the report
+    fn finish(&self, outcomes: Vec<(PathBuf, Outcome)>, started: Instant) -> Report {
+        if self.config.mode == Mode::CountOnly {
+            let compliant = Stats::get(&self.stats.compliant);
+            let non_compliant = Stats::get(&self.stats.non_compliant);
+            let unsupported = Stats::get(&self.stats.unsupported);
+            match self.config.format {
+                Format::Text => {
+                    println!("compliant: {}", compliant);
+                    println!("non-compliant: {}", non_compliant);
+                    println!("unsupported: {}", unsupported);
+                }
+                Format::Json | Format::JsonLines => println!(
+                    "{{\"compliant\":{},\"non_compliant\":{},\"unsupported\":{}}}",
+                    compliant, non_compliant, unsupported
+                ),
+            }
+        }
+        if self.config.summarize_unsupported {
+            self.print_unsupported_summary();
+        }
+        if self.config.mode == Mode::Enforce {
+            self.print_enforce_report(&outcomes);
+        }
+        if self.config.verify_idempotent {
+            self.print_churned();
+        }
+        if self.config.mode == Mode::DryRun && self.config.format == Format::Text {
+            println!(
+                "{} of {} files would change",
+                Stats::get(&self.stats.would_change),
+                Stats::get(&self.stats.scanned)
+            );
+        }
+        if self.config.count_lines {
+            self.print_header_lines();
+        }
+        if self.config.count_by_language && !self.config.quiet {
+            self.print_language_breakdown(&outcomes);
+        }
+        // under `--format jsonl` the summary record carries the throughput
+        if !self.config.mode.is_terse()
+            && !self.config.quiet
+            && self.config.format != Format::JsonLines
+        {
+            self.print_throughput(started.elapsed().as_secs_f64());
+        }
+        if let Some(patch) = &self.config.patch
+            && let Err(e) = self.write_patch(patch)
+        {
+            let message = format!("Failed to write patch ({})", e);
+            self.diag(Level::Error, patch, &message);
+            Stats::bump(&self.stats.violations);
+        }
+        if let Some(cache) = &self.cache
+            && let Err(e) = cache.save()
+        {
+            let message = format!("Failed to write cache ({})", e);
+            self.diag(Level::Warn, cache.path(), &message);
+        }
+        let unreadable = Stats::get(&self.stats.unreadable);
+        if unreadable > 0 {
+            self.print_unreadable(unreadable);
+        }
+        let aborted = self
+            .config
+            .max_errors
+            .filter(|max| Stats::get(&self.stats.errors) > *max);
+        if let Some(max) = aborted {
+            self.print_aborted(max);
+        }
+        // `--dry-run` fails the run like `--check` when anything would change,
+        // unless it is only advisory
+        let pending = if self.config.dry_run_exit_zero {
+            0
+        } else {
+            Stats::get(&self.stats.would_change)
+        };
+        let violations =
+            Stats::get(&self.stats.violations) + pending + usize::from(aborted.is_some());
+        if self.config.format == Format::JsonLines {
+            self.print_summary(&outcomes, violations, started.elapsed().as_secs_f64());
+        }
+        Report {
+            unreadable,
+            ..Report::new(outcomes, violations)
+        }
+    }
+
+    /// the walk skipped part of the tree: say so once more at the end, on
+    /// stderr like the warnings (or errors, under `--strict`) it sums up
+    fn print_unreadable(&self, unreadable: usize) {
+        let (level, outcome) = if self.config.strict {
+            ("error", "failing the run")
+        } else {
+            ("warn", "--strict fails the run on this")
+        };
+        let message = format!(
+            "{} {} could not be read, files below were not processed ({})",
+            unreadable,
+            if unreadable == 1 {
+                "directory"
+            } else {
+                "directories"
+            },
+            outcome
+        );
+        match self.config.format {
+            Format::Text => eprintln!("[{}] {}", level.to_uppercase(), message),
+            Format::Json | Format::JsonLines => eprintln!(
+                "{{\"level\":{},\"message\":{},\"unreadable\":{}}}",
+                json::string(level),
+                json::string(&message),
+                unreadable
+            ),
+        }
+    }
+
+    /// `--max-errors`: the run was cut short, on stderr with the errors
+    fn print_aborted(&self, max: usize) {
+        let errors = Stats::get(&self.stats.errors);
+        let message = format!(
+            "Stopped after {} failed {} (more than --max-errors {}), the rest was not processed",
+            errors,
+            if errors == 1 { "file" } else { "files" },
+            max
+        );
+        match self.config.format {
+            Format::Text => eprintln!("[ERROR] {}", message),
+            Format::Json | Format::JsonLines => eprintln!(
+                "{{\"level\":\"error\",\"message\":{},\"max_errors\":{}}}",
+                json::string(&message),
+                max
+            ),
+        }
+    }
+
+    /// `Scanned N files, wrote X in Ys (Z files/s)`, or the same as JSON fields
+    fn print_throughput(&self, secs: f64) {
+        let scanned = Stats::get(&self.stats.scanned);
+        let written = self.stats.bytes_written.load(Ordering::Relaxed);
+        let rate = files_per_sec(scanned, secs);
+        match self.config.format {
+            Format::Text => println!(
+                "Scanned {} files, wrote {} in {:.2}s ({:.0} files/s)",
+                scanned,
+                human_bytes(written),
+                secs,
+                rate
+            ),
+            Format::Json | Format::JsonLines => println!(
+                "{{\"scanned\":{},\"bytes_written\":{},\"elapsed_secs\":{:.3},\"files_per_sec\":{:.1}}}",
+                scanned, written, secs, rate
+            ),
+        }
+    }
+
+    /// `--format jsonl`: the last record of a run, always printed (`-q`
+    /// included) so a reader knows the stream is complete
+    fn print_summary(&self, outcomes: &[(PathBuf, Outcome)], violations: usize, secs: f64) {
+        let mode = self.config.mode;
+        let tallies: Vec<String> = Outcome::ALL
+            .iter()
+            .map(|kind| (kind, outcomes.iter().filter(|(_, o)| o == kind).count()))
+            .filter(|(_, n)| *n > 0)
+            .map(|(kind, n)| format!("{}:{}", json::string(kind.tally_name(mode)), n))
+            .collect();
+        let scanned = Stats::get(&self.stats.scanned);
+        emit(
+            false,
+            format!(
+                "{{\"type\":\"summary\",\"mode\":{},\"files\":{},\"outcomes\":{{{}}},\"scanned\":{},\"violations\":{},\"unreadable\":{},\"bytes_written\":{},\"elapsed_secs\":{:.3},\"files_per_sec\":{:.1}}}\n",
+                json::string(mode.as_str()),
+                outcomes.len(),
+                tallies.join(","),
+                scanned,
+                violations,
+                Stats::get(&self.stats.unreadable),
+                self.stats.bytes_written.load(Ordering::Relaxed),
+                secs,
+                files_per_sec(scanned, secs)
+            ),
+        );
+    }
+
+    /// `--patch`: every collected diff, in path order
+    fn write_patch(&self, patch: &Path) -> io::Result<()> {
+        let mut patches = self.patches.lock().unwrap();
+        patches.sort();
+        let text: String = patches.iter().map(|(_, diff)| diff.as_str()).collect();
+        fs::write(patch, text)
+    }
+
+    /// `--count-lines`: `Inserted 8 header lines into 340 files (2720 lines)`
+    fn print_header_lines(&self) {
+        let files = Stats::get(&self.stats.header_files);
+        let lines = Stats::get(&self.stats.header_lines);
+        let max = Stats::get(&self.stats.max_header_lines);
+        match self.config.format {
+            // every file got the same header unless the total says otherwise
+            Format::Text if lines == max * files => println!(
+                "Inserted {} header lines into {} files ({} lines)",
+                max, files, lines
+            ),
+            Format::Text => println!(
+                "Inserted up to {} header lines into {} files ({} lines)",
+                max, files, lines
+            ),
+            Format::Json | Format::JsonLines => println!(
+                "{{\"header_files\":{},\"header_lines\":{},\"max_header_lines\":{}}}",
+                files, lines, max
+            ),
+        }
+    }
+
+    /// `--count-by-language`: outcomes per extension, the most files first
+    fn print_language_breakdown(&self, outcomes: &[(PathBuf, Outcome)]) {
+        let mut counts: HashMap<String, [usize; Outcome::ALL.len()]> = HashMap::new();
+        for (path, outcome) in outcomes {
+            let ext = path.extension().map_or_else(
+                || "(none)".to_string(),
+                |e| e.to_string_lossy().into_owned(),
+            );
+            let slot = Outcome::ALL
+                .iter()
+                .position(|o| o == outcome)
+                .unwrap_or_default();
+            counts.entry(ext).or_default()[slot] += 1;
+        }
+        let mut sorted: Vec<_> = counts.into_iter().collect();
+        sorted.sort_by(|a, b| {
+            let total = |n: &[usize]| n.iter().sum::<usize>();
+            total(&b.1).cmp(&total(&a.1)).then_with(|| a.0.cmp(&b.0))
+        });
+
+        let mode = self.config.mode;
+        let tallies = |n: &[usize], render: &dyn Fn(&str, usize) -> String| {
+            Outcome::ALL
+                .iter()
+                .zip(n)
+                .filter(|(_, n)| **n > 0)
+                .map(|(outcome, n)| render(outcome.tally_name(mode), *n))
+                .collect::<Vec<_>>()
+        };
+        match self.config.format {
+            Format::Text => {
+                let groups: Vec<String> = sorted
+                    .iter()
+                    .map(|(ext, n)| {
+                        let parts = tallies(n, &|name, n| format!("{} {}", n, name));
+                        format!("{}: {}", ext, parts.join(", "))
+                    })
+                    .collect();
+                println!("By language: {}", groups.join("; "));
+            }
+            Format::Json | Format::JsonLines => {
+                let groups: Vec<String> = sorted
+                    .iter()
+                    .map(|(ext, n)| {
+                        let parts = tallies(n, &|name, n| format!("{}:{}", json::string(name), n));
+                        format!("{}:{{{}}}", json::string(ext), parts.join(","))
+                    })
+                    .collect();
+                println!("{{\"by_language\":{{{}}}}}", groups.join(","));
+            }
+        }
+    }
+
+    /// `--enforce`: the two kinds of violation, each sorted by path
+    fn print_enforce_report(&self, outcomes: &[(PathBuf, Outcome)]) {
+        let mut missing: Vec<&PathBuf> = outcomes
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, Outcome::Added | Outcome::Updated))
+            .map(|(path, _)| path)
+            .collect();
+        missing.sort();
+        let mut unknown = self.unknown_types.lock().unwrap();
+        unknown.sort();
+
+        match self.config.format {
+            Format::Text => {
+                let sections = [
+                    ("Missing or outdated header", missing),
+                    ("Unknown file type", unknown.iter().collect()),
+                ];
+                for (title, paths) in sections {
+                    println!("{} ({}):", title, paths.len());
+                    for path in paths {
+                        println!("  {}", path.display());
+                    }
+                }
+            }
+            Format::Json | Format::JsonLines => {
+                let list = |paths: Vec<&PathBuf>| {
+                    let items: Vec<String> = paths
+                        .iter()
+                        .map(|p| json::string(&p.display().to_string()))
+                        .collect();
+                    format!("[{}]", items.join(","))
+                };
+                println!(
+                    "{{\"missing_header\":{},\"unknown_type\":{}}}",
+                    list(missing),
+                    list(unknown.iter().collect())
+                );
+            }
+        }
+    }
+
+    /// `--verify-idempotent`: the files the next run would change again
+    fn print_churned(&self) {
+        let mut churned = self.churned.lock().unwrap();
+        churned.sort();
+        match self.config.format {
+            Format::Text => {
+                println!("Would change again on the next run ({}):", churned.len());
+                for path in churned.iter() {
+                    println!("  {}", path.display());
+                }
+            }
+            Format::Json | Format::JsonLines => {
+                let items: Vec<String> = churned
+                    .iter()
+                    .map(|p| json::string(&p.display().to_string()))
+                    .collect();
+                println!("{{\"not_idempotent\":[{}]}}", items.join(","));
+            }
+        }
+    }
+
+    /// `--summarize-unsupported`: the most common extensions first, on stderr
+    /// like the warnings it replaces
+    fn print_unsupported_summary(&self) {
+        let counts = self.unsupported_exts.lock().unwrap();
+        if counts.is_empty() {
+            return;
+        }
+        let mut sorted: Vec<(&String, &usize)> = counts.iter().collect();
+        sorted.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        match self.config.format {
+            Format::Text => {
+                let groups: Vec<String> = sorted
+                    .iter()
+                    .map(|(ext, n)| {
+                        let files = if **n == 1 { "file" } else { "files" };
+                        format!(".{} ({} {})", ext, n, files)
+                    })
+                    .collect();
+                emit(
+                    true,
+                    format!("[WARN] Unsupported file types: {}\n", groups.join(", ")),
+                );
+            }
+            Format::Json | Format::JsonLines => {
+                let groups: Vec<String> = sorted
+                    .iter()
+                    .map(|(ext, n)| format!("{}:{}", json::string(ext), n))
+                    .collect();
+                emit(
+                    true,
+                    format!(
+                        "{{\"level\":\"warn\",\"message\":\"Unsupported file types\",\"unsupported\":{{{}}}}}\n",
+                        groups.join(",")
+                    ),
+                );
+            }
+        }
+    }
+
+    /// lazily walk the targets, yielding every file that passes the exclude rules
+    ///
+    /// this is the exact discovery `run` uses, for callers with their own scheduler
+    pub fn candidates(&self) -> Candidates<'_> {
+        Candidates {
+            engine: self,
+            stack: self
+                .config
+                .targets
+                .iter()
+                .map(|target| (target.clone(), true))
+                .collect(),
+        }
+    }
+
+    /// Iterative DFS
+    ///
+    /// `explicit`: `path` was named on the command line rather than found by the walk;
+    /// `None` for files silently passed over (no extension, no shebang)
+    fn process_file(&self, path: &Path, explicit: bool) -> Option<Outcome> {
+        // `--match` narrows down what the excludes let through
+        if !self.config.matches.is_empty() {
+            let path = match_path(path);
+            if !self.config.matches.iter().any(|re| re.is_match(&path)) {
+                return None;
+            }
+        }
+
+        // `--git-filter`: git has to list it
+        if let Some(files) = &self.git_files
+            && !git_key(path).is_some_and(|key| files.contains(&key))
+        {
+            return None;
+        }
+
+        // check ext
+        let ext = path.extension().and_then(|s| s.to_str());
+        if !self.config.only_exts.is_empty()
+            && !ext.is_some_and(|ext| {
+                let ext = ext.to_lowercase();
+                self.config.only_exts.contains(&ext)
+            })
+        {
+            return None;
+        }
+        Stats::bump(&self.stats.scanned);
+
+        let style = self.style_for_file(path, ext);
+
+        // `--dep5` names the SPDX header, or that none is needed
+        let covered = match (&self.dep5, style) {
+            (Some(dep5), Some(_)) => dep5.lookup(path),
+            _ => None,
+        };
+        if let Some(None) = covered {
+            if self.config.verbose {
+                self.diag(Level::Info, path, "Skipping, no header required by dep5");
+            }
+            return Some(Outcome::Skipped);
+        }
+
+        // `--project-root` may have a template for just this extension
+        let template = match ext
+            .filter(|_| style.is_some())
+            .map(|ext| self.project_template(ext))
+        {
+            Some(Ok(template)) => template,
+            Some(Err(e)) => {
+                self.fail(path, &FileError::from(e));
+                return Some(Outcome::Failed);
+            }
+            None => None,
+        };
+        let template = covered
+            .flatten()
+            .or_else(|| self.rule_template(path))
+            .or(template);
+
+        match style {
+            Some(style) => match self.apply_license(path, style, template.as_deref()) {
+                Ok(outcome) => Some(outcome),
+                Err(e) => {
+                    self.fail(path, &e);
+                    Some(Outcome::Failed)
+                }
+            },
+            None if explicit && self.config.strict_unsupported => {
+                // under `--fail-fast`, `fail` already counts the first one
+                if !self.config.fail_fast {
+                    Stats::bump(&self.stats.violations);
+                }
+                self.fail(
+                    path,
+                    &FileError::new("unsupported file type (listed explicitly)"),
+                );
+                Some(Outcome::Failed)
+            }
+            None if ext.is_none() => None, // no ext, ignore
+            None if self.config.force_style.is_some()
+                && ext.and_then(get_language_style).is_some() =>
+            {
+                if self.config.verbose {
+                    self.diag(Level::Info, path, "Skipping, --force-style does not fit");
+                }
+                Some(Outcome::Skipped)
+            }
+            None if self.config.mode == Mode::Enforce => {
+                Stats::bump(&self.stats.unsupported);
+                Stats::bump(&self.stats.violations);
+                self.diag(Level::Info, path, "Unknown file type");
+                self.unknown_types.lock().unwrap().push(path.to_path_buf());
+                Some(Outcome::Skipped)
+            }
+            None => {
+                Stats::bump(&self.stats.unsupported);
+                if self.config.summarize_unsupported {
+                    let ext = ext.unwrap_or_default().to_string();
+                    *self
+                        .unsupported_exts
+                        .lock()
+                        .unwrap()
+                        .entry(ext)
+                        .or_default() += 1;
+                } else if self.config.mode != Mode::CountOnly {
+                    self.diag(Level::Warn, path, "Ignoring unsupported file type")
+                }
+                Some(Outcome::Skipped)
+            }
+        }
+    }
+
+    /// `lice init`: write `HEADER.txt` here, then print what the walk found
+    /// and the commands to run next, as a commented shell script
+    fn init(&self) -> io::Result<()> {
+        let header = Path::new(INIT_HEADER_FILE);
+        if header.exists() && !self.config.force {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists, --force overwrites it", INIT_HEADER_FILE),
+            ));
+        }
+
+        let mut supported: HashMap<String, usize> = HashMap::new();
+        let mut unsupported: HashMap<String, usize> = HashMap::new();
+        for path in self.candidates() {
+            let (name, known) = match path.extension().and_then(|s| s.to_str()) {
+                Some(ext) => (
+                    format!(".{}", ext),
+                    self.style_for_file(&path, Some(ext)).is_some(),
+                ),
+                None => match self.style_for_file(&path, None) {
+                    Some(_) => ("(scripts)".to_string(), true),
+                    None => continue,
+                },
+            };
+            let counts = if known {
+                &mut supported
+            } else {
+                &mut unsupported
+            };
+            *counts.entry(name).or_default() += 1;
+        }
+        fs::write(header, &self.template_source)?;
+
+        let by_count = |counts: HashMap<String, usize>| {
+            let mut sorted: Vec<_> = counts.into_iter().collect();
+            sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            sorted
+        };
+        // the excludes given to `init` carry over to the suggested commands
+        let targets: Vec<String> = self
+            .config
+            .excludes
+            .iter()
+            .map(|e| format!("-e {}", shell_quote(e)))
+            .chain(self.config.targets.iter().map(|t| {
+                // and a path's own excludes stay right after it
+                let scoped = self
+                    .config
+                    .scoped_excludes
+                    .iter()
+                    .filter(|(root, _)| root == t)
+                    .flat_map(|(_, excludes)| excludes)
+                    .map(|e| format!(" -e {}", shell_quote(e)));
+                let mut arg = shell_quote(&t.display().to_string());
+                arg.extend(scoped);
+                arg
+            }))
+            .collect();
+        let targets = targets.join(" ");
+
+        println!("# written by `lice init`");
+        println!("#");
+        println!(
+            "# {} holds the header, '{{year}}' becomes the current year.",
+            INIT_HEADER_FILE
+        );
+        if self.config.license_id.is_none() {
+            println!(
+                "# edit the holder and license first (or rerun with --license-id and --author)."
+            );
+        }
+        println!("#");
+        println!("# files that get a header:");
+        for (name, n) in by_count(supported) {
+            println!("#   {:<12} {}", name, n);
+        }
+        let unsupported = by_count(unsupported);
+        if !unsupported.is_empty() {
+            println!("# files without a comment style, left alone unless you give them one");
+            println!("# (--line-comment EXT=MARKER, or --prepend-raw EXT):");
+            for (name, n) in unsupported {
+                println!("#   {:<12} {}", name, n);
+            }
+        }
+        println!("#");
+        println!("# preview, then apply:");
+        println!("lice -f {} --dry-run {}", INIT_HEADER_FILE, targets);
+        println!("lice -f {} {}", INIT_HEADER_FILE, targets);
+        println!("#");
+        println!("# in CI, fail when a header is missing or outdated:");
+        println!("# lice -f {} --check {}", INIT_HEADER_FILE, targets);
+        Ok(())
+    }
+
+    /// `--verify-license-file`: warn about template mistakes that would be
+    /// copied into every file, then show the header in each style; returns
+    /// the number of warnings
+    fn verify_template(&self) -> usize {
+        let source = &self.template_source;
+        let mut warnings = Vec::new();
+
+        let lines: Vec<&str> = source.split_inclusive('\n').collect();
+        let crlf = lines.iter().filter(|l| l.ends_with("\r\n")).count();
+        let lf = lines.iter().filter(|l| l.ends_with('\n')).count() - crlf;
+        if crlf > 0 && lf > 0 {
+            warnings.push(format!("mixed line endings: {} CRLF, {} LF", crlf, lf));
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let text = line.trim_end_matches(['\n', '\r']);
+            if text.ends_with([' ', '\t']) {
+                warnings.push(format!(
+                    "line {}: trailing whitespace (dropped in the header)",
+                    i + 1
+                ));
+            }
+            if text.contains('\t') {
+                warnings.push(format!("line {}: tab character", i + 1));
+            }
+            if !source.contains("{year}")
+                && let Some((_, year, _)) = find_year_token(text)
+            {
+                warnings.push(format!(
+                    "line {}: hard-coded year {}, '{{year}}' would always be the current one",
+                    i + 1,
+                    year
+                ));
+            }
+        }
+
+        // rendered as for a file of each extension, flags applied
+        let mut rendered = Vec::new();
+        let mut dangling = Vec::new();
+        for (name, ext) in STYLE_SAMPLES {
+            let Some(style) = self.style_for_ext(ext) else {
+                continue;
+            };
+            let end = style.end.trim();
+            if !end.is_empty() && self.raw_license_text.contains(end) {
+                warnings.push(format!(
+                    "the text contains '{}', which ends the comment early in {} files (.{})",
+                    end, name, ext
+                ));
+            }
+            let header = self.header_with_eol(style, None, Eol::Lf);
+            if header.lines().any(|l| l.ends_with([' ', '\t'])) {
+                dangling.push(format!("'{}'", style.prefix));
+            }
+            rendered.push((name, ext, header));
+        }
+        if !dangling.is_empty() {
+            warnings.push(format!(
+                "blank lines render with trailing whitespace ({}), see --strip-trailing-whitespace",
+                dangling.join(", ")
+            ));
+        }
+
+        for warning in &warnings {
+            println!("warning: {}", warning);
+        }
+        for (name, ext, header) in rendered {
+            println!("--- {} (.{}) ---", name, ext);
+            print!("{}", header);
+        }
+        println!("--- end ---");
+        match warnings.len() {
+            0 => println!("Template OK"),
+            n => println!("{} template warning(s)", n),
+        }
+        warnings.len()
+    }
+
+    /// `--selftest`: for every built-in style and every one set up by flags,
+    /// give a sample file the header in memory, then check that it is
+    /// compliant, that another run leaves it alone and that removing the
+    /// header gives the sample back; returns the number of styles that failed
+    fn selftest(&self) -> usize {
+        let mut exts: Vec<String> = STYLE_SAMPLES
+            .iter()
+            .map(|(_, ext)| ext.to_string())
+            .collect();
+        let mut custom: Vec<String> = self
+            .config
+            .line_comments
+            .keys()
+            .chain(self.config.ext_styles.keys())
+            .chain(&self.config.prepend_raw)
+            .filter(|ext| !exts.contains(ext))
+            .cloned()
+            .collect();
+        custom.sort();
+        custom.dedup();
+        exts.extend(custom);
+
+        let (mut tested, mut failed) = (0, 0);
+        for ext in &exts {
+            let Some(style) = self.style_for_ext(ext) else {
+                println!("SKIP .{}: --force-style does not fit", ext);
+                continue;
+            };
+            if style.start.is_empty() && style.prefix.trim().is_empty() {
+                println!(
+                    "SKIP .{}: --prepend-raw text has no marker to be found by",
+                    ext
+                );
+                continue;
+            }
+            tested += 1;
+            let marker = match style.start.trim() {
+                "" => style.prefix.trim().to_string(),
+                start => format!("{} {}", start, style.end.trim()),
+            };
+            match self.selftest_style(style) {
+                Ok(()) => println!("PASS .{} ({})", ext, marker),
+                Err(problem) => {
+                    failed += 1;
+                    println!("FAIL .{} ({}): {}", ext, marker, problem);
+                }
+            }
+        }
+        println!("{} of {} styles passed", tested - failed, tested);
+        failed
+    }
+
+    /// Helper: one `--selftest` round trip, or what went wrong in it
+    fn selftest_style(&self, style: LanguageProfile) -> Result<(), &'static str> {
+        let header = self.header_with_eol(style, None, Eol::Lf);
+        let applied = apply_license_with(SELFTEST_BODY, &header, style, &self.config)
+            .ok_or("the sample was taken for a malformed file")?;
+        if !self.is_compliant(&applied, &header, style) {
+            return Err("not compliant right after the header was added");
+        }
+        let again = apply_license_with(&applied, &header, style, &self.config);
+        if again.as_deref() != Some(applied.as_str()) {
+            return Err("a second run changes the file again");
+        }
+        // found the way an update finds an old header: at the top any
+        // leading comment is one, at the bottom only a license comment
+        let stripped = match self.config.position {
+            Position::Top => {
+                let (preamble, body) = split_preamble(&applied, style, &self.config);
+                existing_header_span(body, style, &self.config).map(|span| {
+                    let rest = skip_blank_lines(&body[span.end..], usize::MAX);
+                    format!("{}{}", preamble, rest)
+                })
+            }
+            Position::Bottom => remove_header(&applied, style, &self.config),
+        };
+        match stripped {
+            Some(stripped) if stripped == SELFTEST_BODY => Ok(()),
+            Some(_) => Err("removing the header does not give the sample back"),
+            None => Err("the header is not found again, it would be stacked on updates"),
+        }
+    }
+
+    /// `--stdin-filename`/`--stdin-style`: license the file on stdin and print
+    /// it to stdout; one whose header is current comes back as it was
+    fn filter_stdin(&self) -> io::Result<()> {
+        let content = io::read_to_string(io::stdin())?;
+        let name = self.config.stdin_filename.as_deref();
+        let style = match self.config.stdin_style {
+            Some(style) => Some(self.config.customize(style)),
+            // as `style_for_file`, with the shebang from the content
+            None => name
+                .and_then(|name| name.extension())
+                .and_then(|ext| ext.to_str())
+                .and_then(|ext| self.style_for_ext(ext))
+                .or_else(|| {
+                    let line = content.lines().next()?;
+                    self.config.style_for_shebang(line)
+                }),
+        };
+        let shown = name.map_or("stdin".to_string(), |name| name.display().to_string());
+        let style = style.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}: no comment style is known for it, name one with --stdin-style",
+                    shown
+                ),
+            )
+        })?;
+
+        let template = name.and_then(|name| self.rule_template(name));
+        let eol = self.eol_for(name.unwrap_or(Path::new("-")), &content);
+        let header = self.header_with_eol(style, template.as_deref(), eol);
+        let out = if self.is_compliant(&content, &header, style) {
+            content
+        } else {
+            apply_license_with(&content, &header, style, &self.config).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: unclosed block comment at the top, left as is", shown),
+                )
+            })?
+        };
+        io::stdout().write_all(out.as_bytes())
+    }
+
+    /// `--explain`: trace every decision for one file and print the result,
+    /// writing nothing
+    fn explain(&self, path: &Path) -> io::Result<()> {
+        println!("file: {}", path.display());
+        let ext = path.extension().and_then(|s| s.to_str());
+        let line = first_line(path);
+        let shebang = line.as_deref().filter(|l| l.starts_with("#!"));
+        println!(
+            "extension: {}, shebang: {}",
+            ext.unwrap_or("none"),
+            shebang.map_or("none", str::trim_end)
+        );
+        let style = self.style_for_file(path, ext);
+        let Some(style) = style else {
+            println!("style: unsupported, the file would be skipped");
+            return Ok(());
+        };
+        if style.start.is_empty() {
+            println!(
+                "style: line comments, prefix {:?}, first line {:?}, {} blank line(s) after",
+                style.prefix, style.first_line_prefix, style.blank_lines
+            );
+        } else {
+            println!(
+                "style: block comment {:?} ... {:?}, prefix {:?}, {} blank line(s) after",
+                style.start, style.end, style.prefix, style.blank_lines
+            );
+        }
+
+        let covered = self.dep5.as_ref().and_then(|dep5| dep5.lookup(path));
+        let template = match covered {
+            Some(None) => {
+                println!("template: none, dep5 says no header is required");
+                return Ok(());
+            }
+            Some(Some(template)) => {
+                println!("template: from --dep5");
+                Some(template)
+            }
+            None if let Some(template) = self.rule_template(path) => {
+                println!("template: from --rule");
+                Some(template)
+            }
+            None => match ext.map(|ext| self.project_template(ext)).transpose()? {
+                Some(Some(template)) => {
+                    println!("template: from --project-root");
+                    Some(template)
+                }
+                _ => {
+                    println!("template: -f");
+                    None
+                }
+            },
+        };
+
+        let content = read_text(path).map_err(|e| io::Error::other(e.message))?;
+        let eol = self.eol_for(path, &content);
+        let header = self.header_with_eol(style, template.as_deref(), eol);
+        println!(
+            "line ending: {}",
+            match eol {
+                Eol::Lf => "LF",
+                Eol::Crlf => "CRLF",
+            }
+        );
+
+        let (preamble, body) = split_preamble(&content, style, &self.config);
+        match preamble.lines().count() {
+            0 => println!("preamble: none"),
+            n => println!("preamble: {} line(s) kept above the header", n),
+        }
+
+        if self.config.position == Position::Bottom {
+            match trailing_header_span(&content, style, &self.config) {
+                Some(span) => println!(
+                    "position: bottom, the license comment ending the file (bytes {}..{}) is replaced",
+                    span.start, span.end
+                ),
+                None => println!("position: bottom, the header is appended"),
+            }
+        } else if style.start.is_empty() && style.prefix.trim().is_empty() {
+            println!("existing header: not detectable without a comment marker");
+        } else if !style.start.is_empty()
+            && body.trim_start().starts_with(style.start)
+            && existing_header_span(body, style, &self.config).is_none()
+        {
+            println!("existing header: block comment opened but never closed");
+            println!("result: malformed, the file would be skipped");
+            return Ok(());
+        } else {
+            match existing_header_span(body, style, &self.config) {
+                Some(span) => {
+                    let at = Location::of(content.as_bytes(), preamble.len() + span.start);
+                    let kind = if style.start.is_empty() {
+                        "line comments"
+                    } else {
+                        "block comment"
+                    };
+                    let verdict = if self.config.is_license_text(&body[span.clone()]) {
+                        "replaced by the new header"
+                    } else {
+                        "no license keyword, kept below the new header"
+                    };
+                    println!(
+                        "existing header: {} at line {}, {} bytes, {}",
+                        kind,
+                        at.line,
+                        span.len(),
+                        verdict
+                    );
+                }
+                None => println!("existing header: none"),
+            }
+            if stacked_headers(body, style, &self.config).is_some() {
+                println!(
+                    "stacked headers: yes, {}",
+                    if self.config.dedupe_headers {
+                        "collapsed by --dedupe-headers"
+                    } else {
+                        "kept (see --dedupe-headers)"
+                    }
+                );
+            }
+        }
+
+        let stacked = self.config.dedupe_headers
+            && self.config.position == Position::Top
+            && stacked_headers(body, style, &self.config).is_some();
+        if !stacked && self.is_compliant(&content, &header, style) {
+            println!("result: compliant, nothing to do");
+            return Ok(());
+        }
+        if let Some(message) = self.foreign_license(&content, template.as_deref()) {
+            println!("result: {}", message);
+            return Ok(());
+        }
+        match apply_license_with(&content, &header, style, &self.config) {
+            Some(new_content) => {
+                println!("result: {}", self.pending_outcome(&content, style).label());
+                println!("--- new content ---");
+                print!("{}", new_content);
+                if !new_content.ends_with('\n') {
+                    println!();
+                }
+                println!("--- end ---");
+            }
+            None => println!("result: malformed, the file would be skipped"),
+        }
+        Ok(())
+    }
+
+    /// a path from the command line that isn't there; fails the run under `--strict`
+    fn missing_target(&self, path: &Path) {
+        self.walk_error(path, "target not found");
+    }
+
+    /// something the walk couldn't look at (missing target, unreadable
+    /// directory or entry): a warning, or a failure under `--strict`
+    fn walk_error(&self, path: &Path, message: &str) {
+        if !self.config.strict {
+            self.diag(Level::Warn, path, message);
+            return;
+        }
+        // under `--fail-fast`, `fail` already counts the first one
+        if !self.config.fail_fast {
+            Stats::bump(&self.stats.violations);
+        }
+        self.fail(path, &FileError::new(message));
+    }
+
+    /// report a failed file; under `--fail-fast` the first one stops the run
+    fn fail(&self, path: &Path, error: &FileError) {
+        if !self.config.fail_fast {
+            self.diag_at(Level::Error, path, &error.message, error.at);
+            // `--max-errors`: workers stop taking files, the walk winds down
+            let errors = self.stats.errors.fetch_add(1, Ordering::SeqCst) + 1;
+            if self.config.max_errors.is_some_and(|max| errors > max) {
+                self.cancelled.store(true, Ordering::SeqCst);
+            }
+            return;
+        }
+        // workers still finishing their current file may fail too, only the
+        // first error is reported
+        if !self.cancelled.swap(true, Ordering::SeqCst) {
+            self.diag_at(Level::Error, path, &error.message, error.at);
+            Stats::bump(&self.stats.violations);
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// report something about `path`, as text or as a JSON record under `--format json`
+    ///
+    /// info goes to stdout, warnings and errors to stderr, in both formats
+    fn diag(&self, level: Level, path: &Path, message: &str) {
+        self.diag_at(level, path, message, None);
+    }
+
+    /// `diag`, pointing at a line of the file when `at` is given
+    fn diag_at(&self, level: Level, path: &Path, message: &str, at: Option<Location>) {
+        match self.config.format {
+            Format::Text => {
+                let message = match at {
+                    Some(at) => format!("{} (line {}, byte {})", message, at.line, at.offset),
+                    None => message.to_string(),
+                };
+                match level {
+                    Level::Info => emit(false, format!(" {}: {:?}\n", message, path)),
+                    Level::Warn => emit(true, format!("[WARN] {}: {:?}\n", message, path)),
+                    Level::Error => {
+                        emit(true, format!("Error processing {:?}: {}\n", path, message))
+                    }
+                }
+            }
+            Format::Json | Format::JsonLines => {
+                let jsonl = self.config.format == Format::JsonLines;
+                // the status line of a file goes into its record instead
+                if jsonl && matches!(level, Level::Info) && hold_note(message) {
+                    return;
+                }
+                let at = match at {
+                    Some(at) => format!(",\"line\":{},\"offset\":{}", at.line, at.offset),
+                    None => String::new(),
+                };
+                let record = format!(
+                    "{{{}\"level\":\"{}\",\"path\":{},\"message\":{}{}}}",
+                    if jsonl { "\"type\":\"diag\"," } else { "" },
+                    level.as_str(),
+                    json::string(&path.display().to_string()),
+                    json::string(message),
+                    at
+                );
+                let to_stderr = !matches!(level, Level::Info);
+                emit(to_stderr, format!("{}\n", record));
+            }
+        }
+    }
+
+    /// one file from the walk, the same with one thread or many: nothing once
+    /// the run is cancelled, else processed and counted in `stats`; with
+    /// `hold`, what it prints is returned instead, for the printer thread
+    fn run_job(&self, path: &Path, explicit: bool, hold: bool) -> (Option<Outcome>, Vec<Held>) {
+        if self.is_cancelled() {
+            return (None, Vec::new());
+        }
+        if hold {
+            HELD.with(|held| *held.borrow_mut() = Some(Vec::new()));
+        }
+        let outcome = self.process_recorded(path, explicit);
+        let output = HELD
+            .with(|held| held.borrow_mut().take())
+            .unwrap_or_default();
+        (outcome, output)
+    }
+
+    /// `process_file`, with its `--format jsonl` record
+    fn process_recorded(&self, path: &Path, explicit: bool) -> Option<Outcome> {
+        let (outcome, note) = capture_note(|| self.process_file(path, explicit));
+        self.record_file(path, outcome, note);
+        outcome
+    }
+
+    /// `--detect-wrong-style`: `content` without a license header opening it
+    /// in another built-in comment style, and the blank lines after it;
+    /// `None` when there is none
+    ///
+    /// the comment must mention a license keyword, and a line marker must be
+    /// followed by a space, so `#include` or `--flag` lines never qualify
+    fn wrong_style_removed(
+        &self,
+        path: &Path,
+        content: &str,
+        style: LanguageProfile,
+    ) -> Option<String> {
+        if !self.config.detect_wrong_style || self.config.position != Position::Top {
+            return None;
+        }
+        let (preamble, body) = split_preamble(content, style, &self.config);
+        let marker = |s: LanguageProfile| match s.start.trim() {
+            "" => s.prefix.trim().to_string(),
+            start => format!("{} {}", start, s.end.trim()),
+        };
+        let (span, foreign) = BUILTIN_STYLES
+            .iter()
+            .filter(|other| marker(**other) != marker(style))
+            .find_map(|&other| {
+                let span = existing_header_span(body, other, &self.config)?;
+                let comment = &body[span.clone()];
+                let spaced = !other.start.is_empty()
+                    || comment.lines().all(|line| {
+                        let line = line.trim();
+                        line == other.prefix.trim() || line.starts_with(other.prefix)
+                    });
+                let license = self.config.mentions_license(&strip_comment(comment, other));
+                (spaced && license).then_some((span, other))
+            })?;
+
+        self.diag(
+            Level::Warn,
+            path,
+            &format!(
+                "License header in the wrong comment style ('{}' instead of '{}')",
+                marker(foreign),
+                marker(style)
+            ),
+        );
+        let rest = skip_blank_lines(&body[span.end..], usize::MAX);
+        Some(format!("{}{}", preamble, rest))
+    }
+
+    /// `run_with`: may the change of `path` to `proposed` go ahead?
+    fn vetoed(&self, path: &Path, outcome: Outcome, proposed: &str) -> bool {
+        let vetoed = self
+            .visitor
+            .as_ref()
+            .is_some_and(|v| v.review(path, outcome, proposed) == Verdict::Veto);
+        if vetoed && self.config.verbose {
+            self.diag(Level::Info, path, "Skipping, vetoed by the visitor");
+        }
+        vetoed
+    }
+
+    /// `--format jsonl`: one `{"type":"file",...}` record for `path`, with
+    /// the status line it would have printed; and the visitor's `finished`
+    fn record_file(&self, path: &Path, outcome: Option<Outcome>, note: Option<String>) {
+        if let (Some(visitor), Some(outcome)) = (&self.visitor, outcome) {
+            visitor.finished(path, outcome);
+        }
+        if self.config.format != Format::JsonLines {
+            return;
+        }
+        let path = json::string(&path.display().to_string());
+        let record = match (outcome, note) {
+            (Some(outcome), note) => format!(
+                "{{\"type\":\"file\",\"path\":{},\"outcome\":{},\"message\":{}}}\n",
+                path,
+                json::string(outcome.tally_name(self.config.mode)),
+                json::string(note.as_deref().unwrap_or(outcome.label()))
+            ),
+            // filtered out, but it still said something
+            (None, Some(note)) => format!(
+                "{{\"type\":\"diag\",\"level\":\"info\",\"path\":{},\"message\":{}}}\n",
+                path,
+                json::string(&note)
+            ),
+            (None, None) => return,
+        };
+        emit(false, record);
+    }
+
+    /// built-in style for `ext` with the CLI overrides applied
+    fn style_for_ext(&self, ext: &str) -> Option<LanguageProfile<'_>> {
+        self.config.style_for_ext(ext)
+    }
+
+    /// style by extension; a script whose extension names no language
+    /// (`bin/deploy`, `run.cgi`) goes by the interpreter on its `#!` line
+    fn style_for_file(&self, path: &Path, ext: Option<&str>) -> Option<LanguageProfile<'_>> {
+        if let Some(ext) = ext {
+            let style = self.style_for_ext(ext);
+            // a known language that `--force-style` rules out stays out
+            if style.is_some() || get_language_style(ext).is_some() {
+                return style;
+            }
+        }
+        first_line(path).and_then(|line| self.config.style_for_shebang(&line))
+    }
+
+    /// formatted header for `style`, precomputed in `new` for every known style;
+    /// a per-file `template` (`--dep5`, `--rule`, `--project-root`) is
+    /// formatted on first use and kept
+    fn header_for(&self, style: LanguageProfile, template: Option<&str>) -> Cow<'_, str> {
+        if let Some(raw) = template {
+            let raw = &*self.checksummed(raw);
+            let mut formatted = self.formatted.lock().unwrap();
+            let by_style = match formatted.get_mut(raw) {
+                Some(by_style) => by_style,
+                None => formatted.entry(Arc::from(raw)).or_default(),
+            };
+            let header = by_style
+                .entry(style.into())
+                .or_insert_with(|| Arc::from(Self::make_header_for_style(raw, style)));
+            return Cow::Owned(header.to_string());
+        }
+        match self.headers.get(&StyleKey::from(style)) {
+            Some(header) => Cow::Borrowed(header),
+            None => Cow::Owned(Self::make_header_for_style(&self.raw_license_text, style)),
+        }
+    }
+
+    /// a `--rule`/`--project-root`/dep5 template with `--header-checksum`
+    /// applied, as the main template got it in `new`
+    fn checksummed<'a>(&self, raw: &'a str) -> Cow<'a, str> {
+        if self.config.header_checksum {
+            Cow::Owned(with_checksum(raw))
+        } else {
+            Cow::Borrowed(raw)
+        }
+    }
+
+    /// `--header-checksum`: does the existing header disagree with its own
+    /// `lice-checksum:` line?
+    fn header_edited(&self, content: &str, style: LanguageProfile) -> bool {
+        let (_, body) = split_preamble(content, style, &self.config);
+        let Some(span) = self.license_span(body, style) else {
+            return false;
+        };
+        let text = strip_comment(&body[span], style);
+        let text = text.trim_end();
+        let Some((above, last)) = text.rsplit_once('\n') else {
+            return false;
+        };
+        last.trim()
+            .strip_prefix(CHECKSUM_TAG)
+            .is_some_and(|sum| sum.trim() != header_checksum(above))
+    }
+
+    /// core business
+    fn apply_license(
+        &self,
+        path: &Path,
+        style: LanguageProfile,
+        template: Option<&str>,
+    ) -> Result<Outcome, FileError> {
+        // `--cache`: compliant last time and untouched since, no need to read it
+        if let Some(cache) = &self.cache
+            && let Ok(meta) = fs::metadata(path)
+            && cache.is_fresh(path, &meta)
+        {
+            match self.config.mode {
+                Mode::CountOnly => Stats::bump(&self.stats.compliant),
+                _ => self.report(path, Outcome::Ok),
+            }
+            return Ok(Outcome::Ok);
+        }
+
+        let content = read_text(path).inspect_err(|e| {
+            // a check can't vouch for a file it may not read; under
+            // `--fail-fast`, `fail` already counts the first one
+            if e.denied
+                && (self.config.strict || !self.config.mode.writes())
+                && !self.config.fail_fast
+            {
+                Stats::bump(&self.stats.violations);
+            }
+        })?;
+
+        match self.config.mode {
+            Mode::Apply | Mode::Check | Mode::Enforce | Mode::DryRun => {}
+            Mode::UpdateYear => return Ok(self.update_year(path, &content, style)?),
+            Mode::Remove => return Ok(self.remove(path, &content, style)?),
+            Mode::Normalize => {
+                let header = self.header_with_eol(style, template, self.eol_for(path, &content));
+                let raw = match template {
+                    Some(raw) => self.checksummed(raw),
+                    None => Cow::Borrowed(self.raw_license_text.as_str()),
+                };
+                return Ok(self.normalize(path, &content, style, &header, &raw)?);
+            }
+            Mode::ReportUnlicensed => return Ok(self.report_unlicensed(path, &content, style)),
+            Mode::CountOnly => {
+                let header = self.header_with_eol(style, template, self.eol_for(path, &content));
+                if self.is_compliant(&content, &header, style) {
+                    Stats::bump(&self.stats.compliant);
+                    self.remember(path, &content);
+                    return Ok(Outcome::Ok);
+                }
+                Stats::bump(&self.stats.non_compliant);
+                self.forget(path);
+                return Ok(self.pending_outcome(&content, style));
+            }
+        }
+
+        // generate header, in the line ending the file should end up with
+        let eol = self.eol_for(path, &content);
+        let header = self.header_with_eol(style, template, eol);
+
+        // a current header with an old one stacked below isn't done yet
+        let stacked = stacked_headers(
+            split_preamble(&content, style, &self.config).1,
+            style,
+            &self.config,
+        )
+        .is_some();
+        if stacked && !self.config.dedupe_headers {
+            self.diag(
+                Level::Warn,
+                path,
+                "Stacked license headers found, --dedupe-headers collapses them",
+            );
+        }
+
+        // check if exists
+        if !(stacked && self.config.dedupe_headers) && self.is_compliant(&content, &header, style) {
+            self.report(path, Outcome::Ok);
+            self.remember(path, &content);
+            if self.config.mode == Mode::Apply {
+                self.write_unchanged(path, &content)?;
+            }
+            return Ok(Outcome::Ok);
+        }
+        if self.config.header_checksum && self.header_edited(&content, style) {
+            self.diag(
+                Level::Warn,
+                path,
+                "Header was edited by hand (lice-checksum mismatch)",
+            );
+        }
+
+        if let Some(message) = self.foreign_license(&content, template) {
+            self.diag(Level::Warn, path, &message);
+            return Ok(Outcome::Skipped);
+        }
+
+        if self.config.no_guess
+            && let Some(error) = self.ambiguity(&content, style)
+        {
+            // under `--fail-fast`, `fail` already counts the first one
+            if !self.config.fail_fast {
+                Stats::bump(&self.stats.violations);
+            }
+            return Err(error);
+        }
+
+        // `--detect-wrong-style`: a license in a foreign comment style goes,
+        // instead of staying below the new header
+        let unstyled = self.wrong_style_removed(path, &content, style);
+        let source = unstyled.as_deref().unwrap_or(&content);
+
+        // only trailing whitespace away from current: clean just the header
+        let cleaned = match self.config.strip_trailing_whitespace {
+            true if unstyled.is_some() => None,
+            true if !(stacked && self.config.dedupe_headers) => {
+                clean_header(&content, style, &self.config)
+                    .filter(|cleaned| self.is_compliant(cleaned, &header, style))
+            }
+            _ => None,
+        };
+        let outcome = match cleaned {
+            Some(_) => Outcome::Cleaned,
+            None if unstyled.is_some() => Outcome::Updated,
+            None => self.pending_outcome(&content, style),
+        };
+
+        let Some(new_content) =
+            cleaned.or_else(|| apply_license_with(source, &header, style, &self.config))
+        else {
+            // malformed file
+            let (preamble, body) = split_preamble(&content, style, &self.config);
+            let start = preamble.len() + body.len() - body.trim_start().len();
+            self.diag_at(
+                Level::Warn,
+                path,
+                "Unclosed block comment, skipping",
+                Some(Location::of(content.as_bytes(), start)),
+            );
+            return Ok(Outcome::Skipped); // continue
+        };
+        if self.vetoed(path, outcome, &new_content) {
+            return Ok(Outcome::Skipped);
+        }
+
+        // detection is done, `--check` stops short of touching anything
+        if matches!(self.config.mode, Mode::Check | Mode::Enforce) {
+            Stats::bump(&self.stats.violations);
+            self.diag(Level::Info, path, outcome.pending_label());
+            return Ok(outcome);
+        }
+        if self.config.mode == Mode::DryRun {
+            Stats::bump(&self.stats.would_change);
+            if let Some(root) = &self.patch_root {
+                let name = patch_path(path, root);
+                let diff = diff::unified(&content, &new_content, &name, false);
+                self.patches.lock().unwrap().push((name, diff));
+            }
+            if self.config.diff {
+                self.print_diff(path, &content, &new_content);
+                return Ok(outcome);
+            }
+            // held across both, so another worker can't print in between
+            let _stdout = io::stdout().lock();
+            self.diag(Level::Info, path, outcome.dry_run_label());
+            self.preview(&header);
+            return Ok(outcome);
+        }
+
+        self.write_changed(path, &content, &new_content)?;
+        self.count_written(&new_content);
+        self.count_header_lines(&header);
+        if self.config.verify && !self.verify_written(path, &content, &header, style)? {
+            return Ok(Outcome::Failed);
+        }
+        self.report(path, outcome);
+        // with `--out-dir` the source itself is still unlicensed
+        match self.out_dir {
+            None => self.remember(path, &new_content),
+            Some(_) => self.forget(path),
+        }
+        self.post_cmd(path);
+        if self.config.verify_idempotent {
+            self.check_idempotent(path, style, template)?;
+        }
+        Ok(outcome)
+    }
+
+    /// `--verify-idempotent`: read back what was written (`--post-cmd` output
+    /// included) and decide as `--check` would; a file it would still flag
+    /// fails the run and is listed at the end
+    fn check_idempotent(
+        &self,
+        path: &Path,
+        style: LanguageProfile,
+        template: Option<&str>,
+    ) -> io::Result<()> {
+        let written = fs::read_to_string(self.output_path(path))?;
+        let header = self.header_with_eol(style, template, self.eol_for(path, &written));
+        let stacked = self.config.dedupe_headers
+            && stacked_headers(
+                split_preamble(&written, style, &self.config).1,
+                style,
+                &self.config,
+            )
+            .is_some();
+        if stacked || !self.is_compliant(&written, &header, style) {
+            Stats::bump(&self.stats.violations);
+            self.diag(
+                Level::Warn,
+                path,
+                "Not compliant as written, the next run would change it again",
+            );
+            self.churned.lock().unwrap().push(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// `--spdx-policy skip`: why `content` keeps its own license rather than
+    /// the header's, if it does
+    fn foreign_license(&self, content: &str, template: Option<&str>) -> Option<String> {
+        if self.config.spdx_policy != SpdxPolicy::Skip {
+            return None;
+        }
+        let existing = spdx_license_id(content)?;
+        let target = spdx_license_id(template.unwrap_or(&self.raw_license_text))
+            .or(self.config.license_id.as_deref());
+        match target {
+            Some(target) if target.eq_ignore_ascii_case(existing) => None,
+            Some(target) => Some(format!(
+                "Tagged SPDX-License-Identifier: {}, not {}, skipping",
+                existing, target
+            )),
+            None => Some(format!(
+                "Tagged SPDX-License-Identifier: {} and the header names no license, skipping",
+                existing
+            )),
+        }
+    }
+
+    /// `--no-guess`: why rewriting `content` would rest on a guess about
+    /// where its old header ends, if it would
+    fn ambiguity(&self, content: &str, style: LanguageProfile) -> Option<FileError> {
+        // appending at the bottom takes nothing away
+        if self.config.position != Position::Top {
+            return None;
+        }
+        let (preamble, body) = split_preamble(content, style, &self.config);
+        let lead = body.len() - body.trim_start().len();
+        let guess = |message: &str, offset: usize| FileError {
+            at: Some(Location::of(content.as_bytes(), preamble.len() + offset)),
+            ..FileError::new(message)
+        };
+
+        // 1. a block comment that never closes: the rest of the file may be
+        //    the old header, or code after a stray opener
+        if !style.start.is_empty()
+            && opens_block(&body[lead..], style)
+            && existing_header_span(body, style, &self.config).is_none()
+        {
+            return Some(guess("unclosed block comment at the top", lead));
+        }
+
+        let span = existing_header_span(body, style, &self.config)?;
+        let comment = &body[span.clone()];
+        if !self.config.mentions_license(comment) {
+            // with `--keep-leading-comments` it stays, which is no guess
+            if self.config.keep_leading_comments {
+                return None;
+            }
+            // 2. a leading comment that doesn't look like a license,
+            //    replacing it assumes it is an old header
+            return Some(guess(
+                "leading comment does not mention a license, replacing it would be a guess",
+                span.start,
+            ));
+        }
+        // 3. code right below the old header: the comment may well run on
+        //    into documentation of that code
+        let after = &body[span.end..];
+        if !after.is_empty() && !after.starts_with(['\n', '\r']) {
+            return Some(guess(
+                "no blank line between the leading comment and the code",
+                span.end,
+            ));
+        }
+        None
+    }
+
+    /// for a non-compliant file: an outdated header gets replaced in place,
+    /// otherwise one is added
+    fn pending_outcome(&self, content: &str, style: LanguageProfile) -> Outcome {
+        match self.config.position {
+            Position::Top
+                if self
+                    .license_span(split_preamble(content, style, &self.config).1, style)
+                    .is_some() =>
+            {
+                Outcome::Updated
+            }
+            Position::Bottom if trailing_header_span(content, style, &self.config).is_some() => {
+                Outcome::Updated
+            }
+            _ => Outcome::Added,
+        }
+    }
+
+    /// `--cache`: `path` is compliant as it is now on disk
+    fn remember(&self, path: &Path, content: &str) {
+        if let Some(cache) = &self.cache
+            && let Ok(meta) = fs::metadata(path)
+        {
+            cache.record(path, &meta, content);
+        }
+    }
+
+    fn forget(&self, path: &Path) {
+        if let Some(cache) = &self.cache {
+            cache.forget(path);
+        }
+    }
+
+    /// `--dry-run`: show the header that would be written, text output only
+    fn preview(&self, header: &str) {
+        if self.config.format != Format::Text {
+            return;
+        }
+        // one print, so workers can't interleave their previews
+        let preview: String = header
+            .trim_end()
+            .lines()
+            .map(|line| format!("   | {}\n", line))
+            .collect();
+        emit(false, preview);
+    }
+
+    /// `--diff`: what `--dry-run` would write, as one unified diff
+    fn print_diff(&self, path: &Path, old: &str, new: &str) {
+        if self.config.format != Format::Text {
+            return;
+        }
+        let name = path.display().to_string();
+        let name = name.strip_prefix("./").unwrap_or(&name);
+        // one print, so workers can't interleave their diffs
+        emit(false, diff::unified(old, new, name, self.color));
+    }
+
+    fn report(&self, path: &Path, outcome: Outcome) {
+        // `--diff` shows only what would change
+        if self.config.diff && outcome == Outcome::Ok {
+            return;
+        }
+        self.diag(Level::Info, path, outcome.label());
+    }
+
+    /// `--verify`: re-read what was written and check we'd now call it compliant
+    ///
+    /// on failure the original content is always put back, from memory (there
+    /// is no backup file), and the file is reported
+    fn verify_written(
+        &self,
+        path: &Path,
+        original: &str,
+        header: &str,
+        style: LanguageProfile,
+    ) -> io::Result<bool> {
+        let dest = self.output_path(path);
+        let written = fs::read_to_string(&dest)?;
+        if self.is_compliant(&written, header, style) {
+            return Ok(true);
+        }
+
+        fs::write(&dest, original)?;
+        Stats::bump(&self.stats.violations);
+        self.diag(
+            Level::Error,
+            path,
+            "verification failed: rewritten file is not recognized as licensed, original restored",
+        );
+        Ok(false)
+    }
+
+    /// where the result for `path` goes: itself, or its mirror under `--out-dir`
+    fn output_path(&self, path: &Path) -> PathBuf {
+        let Some(out_dir) = &self.out_dir else {
+            return path.to_path_buf();
+        };
+
+        // mirror by the normal components only: `./src/a.rs`, `/abs/src/a.rs`
+        // and `../src/a.rs` all land somewhere inside `out_dir`
+        let relative: PathBuf = path
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        out_dir.join(relative)
+    }
+
+    /// write the new content in place, or to the mirrored path under `--out-dir`
+    fn write_output(&self, path: &Path, content: &str) -> io::Result<()> {
+        // `--check` and `--dry-run` never create, rename or touch a file
+        if !self.config.mode.writes() {
+            return Ok(());
+        }
+        let dest = self.output_path(path);
+        if self.out_dir.is_some()
+            && let Some(parent) = dest.parent()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(dest, content)
+    }
+
+    /// `write_output` for a file whose text was `old`; with
+    /// `--touch-only-changed-lines` a long unchanged tail is copied from the
+    /// original instead of written out again
+    fn write_changed(&self, path: &Path, old: &str, new: &str) -> io::Result<()> {
+        let tail = common_suffix_len(old.as_bytes(), new.as_bytes());
+        if !self.config.touch_only_changed_lines
+            || !self.config.mode.writes()
+            || tail < STREAM_MIN_TAIL
+        {
+            return self.write_output(path, new);
+        }
+        let dest = match self.out_dir {
+            Some(_) => {
+                let dest = self.output_path(path);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                dest
+            }
+            // renaming onto a symlink would replace it, so go to its target
+            None => fs::canonicalize(path)?,
+        };
+        let mut name = dest.file_name().unwrap_or_default().to_os_string();
+        name.push(".lice-tmp");
+        let temp = dest.with_file_name(name);
+
+        let head = &new.as_bytes()[..new.len() - tail];
+        let result = (|| {
+            let mut source = fs::File::open(path)?;
+            source.seek(SeekFrom::Start((old.len() - tail) as u64))?;
+            let mut out = fs::File::create(&temp)?;
+            out.write_all(head)?;
+            io::copy(&mut source.take(tail as u64), &mut out)?;
+            if self.out_dir.is_none() {
+                out.set_permissions(fs::metadata(path)?.permissions())?;
+            }
+            fs::rename(&temp, &dest)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// `--post-cmd`: run the hook on a file just rewritten, in this worker;
+    /// a failing hook is reported and fails the run
+    fn post_cmd(&self, path: &Path) {
+        let Some(template) = &self.config.post_cmd else {
+            return;
+        };
+        let target = shell_quote(&self.output_path(path).display().to_string());
+        let command = if template.contains("{}") {
+            template.replace("{}", &target)
+        } else {
+            format!("{} {}", template, target)
+        };
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        // captured, so parallel hooks don't interleave their output
+        let message = match process::Command::new(shell)
+            .arg(flag)
+            .arg(&command)
+            .output()
+        {
+            Ok(output) if output.status.success() => return,
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                match stderr.trim() {
+                    "" => format!("--post-cmd failed ({})", output.status),
+                    detail => format!("--post-cmd failed ({}): {}", output.status, detail),
+                }
+            }
+            Err(e) => format!("--post-cmd could not run ({})", e),
+        };
+        Stats::bump(&self.stats.violations);
+        self.diag(Level::Error, path, &message);
+    }
+
+    fn count_written(&self, content: &str) {
+        self.stats
+            .bytes_written
+            .fetch_add(content.len() as u64, Ordering::Relaxed);
+    }
+
+    /// `--count-lines`: one more file got `header`
+    fn count_header_lines(&self, header: &str) {
+        let lines = header.trim_end().lines().count();
+        Stats::bump(&self.stats.header_files);
+        self.stats.header_lines.fetch_add(lines, Ordering::Relaxed);
+        self.stats
+            .max_header_lines
+            .fetch_max(lines, Ordering::Relaxed);
+    }
+
+    /// compliant files only reach `--out-dir` with `--copy-unchanged`
+    fn write_unchanged(&self, path: &Path, content: &str) -> io::Result<()> {
+        if self.out_dir.is_some() && self.config.copy_unchanged {
+            self.write_output(path, content)?;
+        }
+        Ok(())
+    }
+
+    /// line ending for lines we insert: `.gitattributes` `eol`, else the file's own
+    fn eol_for(&self, path: &Path, content: &str) -> Eol {
+        let pinned = if self.config.gitattributes {
+            self.gitattributes.eol_for(path)
+        } else {
+            None
+        };
+        pinned.unwrap_or_else(|| Eol::detect(content))
+    }
+
+    /// `header_for` converted to line ending `eol`
+    fn header_with_eol(
+        &self,
+        style: LanguageProfile,
+        template: Option<&str>,
+        eol: Eol,
+    ) -> Cow<'_, str> {
+        let mut header = self.header_for(style, template);
+        if self.config.strip_trailing_whitespace {
+            header = Cow::Owned(strip_line_ends(&header));
+        }
+        match eol {
+            Eol::Lf => header,
+            Eol::Crlf => Cow::Owned(header.replace('\n', "\r\n")),
+        }
+    }
+
+    /// is the current header already in place, at the configured position?
+    fn is_compliant(&self, content: &str, header: &str, style: LanguageProfile) -> bool {
+        if self.matches_ok_pattern(content) {
+            return true;
+        }
+        match self.config.position {
+            // extra blank lines were kept on purpose, they don't make it stale
+            Position::Top if self.config.preserve_blank_run => {
+                skip_blank_lines(split_preamble(content, style, &self.config).1, usize::MAX)
+                    .starts_with(header)
+            }
+            Position::Top => has_current_header(content, header, style, &self.config),
+            Position::Bottom => content.trim_end().ends_with(header.trim()),
+        }
+    }
+
+    /// `--ok-if-matches`: does the region a header would be in match any?
+    fn matches_ok_pattern(&self, content: &str) -> bool {
+        if self.config.ok_if_matches.is_empty() {
+            return false;
+        }
+        let lines = self.config.header_max_scan_lines;
+        let region = match self.config.position {
+            Position::Top => &content[..scan_len(content, lines)],
+            Position::Bottom => {
+                let start = content
+                    .trim_end()
+                    .rmatch_indices('\n')
+                    .nth(lines.saturating_sub(1))
+                    .map_or(0, |(i, _)| i + 1);
+                &content[start..]
+            }
+        };
+        self.config
+            .ok_if_matches
+            .iter()
+            .any(|re| re.is_match(region))
+    }
+
+    /// `existing_header_span`, unless the comment fails the `--license-keyword` test
+    fn license_span(&self, body: &str, style: LanguageProfile) -> Option<Range<usize>> {
+        existing_header_span(body, style, &self.config)
+            .filter(|span| self.config.is_license_text(&body[span.clone()]))
+    }
+
+    /// print the path if the file carries no header at all (an outdated one is fine)
+    fn report_unlicensed(&self, path: &Path, content: &str, style: LanguageProfile) -> Outcome {
+        let (_, body) = split_preamble(content, style, &self.config);
+
+        if self.license_span(body, style).is_some() {
+            return Outcome::Ok;
+        }
+        emit(false, format!("{}\n", path.display()));
+        Stats::bump(&self.stats.violations);
+        Outcome::Added
+    }
+
+    /// bump the copyright year inside the existing header, touching nothing else
+    fn update_year(
+        &self,
+        path: &Path,
+        content: &str,
+        style: LanguageProfile,
+    ) -> io::Result<Outcome> {
+        let (preamble, body) = split_preamble(content, style, &self.config);
+        let offset = preamble.len();
+
+        let span = match self.license_span(body, style) {
+            Some(span) => (span.start + offset)..(span.end + offset),
+            None => {
+                self.diag(Level::Warn, path, "No license header found");
+                return Ok(Outcome::Skipped);
+            }
+        };
+
+        let year = current_year();
+        let (token, replacement) = match find_year_token(&content[span.clone()]) {
+            Some((range, first, last)) => {
+                if last >= year {
+                    self.diag(Level::Info, path, "Year OK");
+                    self.write_unchanged(path, content)?;
+                    return Ok(Outcome::Ok);
+                }
+                let token = (range.start + span.start)..(range.end + span.start);
+                (token, format!("{}-{}", first, year))
+            }
+            None => {
+                self.diag(Level::Warn, path, "No copyright year found in header");
+                return Ok(Outcome::Skipped);
+            }
+        };
+
+        let mut new_content = String::with_capacity(content.len() + 5);
+        new_content.push_str(&content[..token.start]);
+        new_content.push_str(&replacement);
+        new_content.push_str(&content[token.end..]);
+        if self.vetoed(path, Outcome::Updated, &new_content) {
+            return Ok(Outcome::Skipped);
+        }
+
+        self.write_changed(path, content, &new_content)?;
+        self.count_written(&new_content);
+        self.diag(Level::Info, path, "Year updated");
+        self.post_cmd(path);
+        Ok(Outcome::Updated)
+    }
+
+    /// `--remove`: take out a header that mentions a license, touching nothing else
+    fn remove(&self, path: &Path, content: &str, style: LanguageProfile) -> io::Result<Outcome> {
+        let Some(new_content) = remove_header(content, style, &self.config) else {
+            self.diag(Level::Info, path, "No license header found");
+            self.write_unchanged(path, content)?;
+            return Ok(Outcome::Ok);
+        };
+
+        self.write_changed(path, content, &new_content)?;
+        self.count_written(&new_content);
+        self.report(path, Outcome::Removed);
+        self.post_cmd(path);
+        Ok(Outcome::Removed)
+    }
+
+    /// `--normalize`: re-emit a header whose text is `raw` in the canonical form
+    fn normalize(
+        &self,
+        path: &Path,
+        content: &str,
+        style: LanguageProfile,
+        header: &str,
+        raw: &str,
+    ) -> io::Result<Outcome> {
+        let (_, body) = split_preamble(content, style, &self.config);
+        let Some(span) = self.license_span(body, style) else {
+            self.diag(Level::Warn, path, "No license header found");
+            return Ok(Outcome::Skipped);
+        };
+        if !same_text(&strip_comment(&body[span], style), raw) {
+            if self.config.verbose {
+                self.diag(
+                    Level::Info,
+                    path,
+                    "Skipping, header text differs from the template",
+                );
+            }
+            return Ok(Outcome::Skipped);
+        }
+
+        // the canonical form is what a fresh apply would write
+        let new_content = apply_license_with(content, header, style, &self.config)
+            .unwrap_or_else(|| content.to_string());
+        if new_content == content {
+            self.report(path, Outcome::Ok);
+            self.write_unchanged(path, content)?;
+            return Ok(Outcome::Ok);
+        }
+        if self.vetoed(path, Outcome::Normalized, &new_content) {
+            return Ok(Outcome::Skipped);
+        }
+        self.write_changed(path, content, &new_content)?;
+        self.count_written(&new_content);
+        self.report(path, Outcome::Normalized);
+        self.remember(path, &new_content);
+        self.post_cmd(path);
+        Ok(Outcome::Normalized)
+    }
+
+    /// Helper: generate header from style and raw str
+    fn make_header_for_style(raw: &str, style: LanguageProfile) -> String {
+        let mut out = String::new();
+        if !style.start.is_empty() {
+            out.push_str(style.start);
+        }
+
+        for (i, line) in banner_lines(raw, style).iter().enumerate() {
+            out.push_str(if i == 0 {
+                style.first_line_prefix
+            } else {
+                style.prefix
+            });
+            out.push_str(line);
+            out.push_str(style.suffix);
+            out.push('\n');
+        }
+
+        out.push_str(style.end);
+        for _ in 0..style.blank_lines {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// one step of the walk: a file to process, or `None` after pushing a
+    /// directory's entries onto `stack` (or skipping `path` altogether)
+    fn visit(
+        &self,
+        path: PathBuf,
+        explicit: bool,
+        stack: &mut Vec<(PathBuf, bool)>,
+    ) -> Option<(PathBuf, bool)> {
+        let config = &self.config;
+        if self.is_excluded(&path) {
+            return None;
+        }
+
+        // a dangling symlink exists, it fails like any unreadable file
+        if explicit
+            && fs::symlink_metadata(&path).is_err_and(|e| e.kind() == io::ErrorKind::NotFound)
+        {
+            self.missing_target(&path);
+            return None;
+        }
+
+        if path.is_dir() {
+            if let Some(out_dir) = &self.out_dir
+                && fs::canonicalize(&path).is_ok_and(|p| &p == out_dir)
+            {
+                return None;
+            }
+            // a marker file opts the whole subtree out
+            if path.join(IGNORE_MARKER).exists() {
+                if config.verbose {
+                    self.diag(
+                        Level::Info,
+                        &path,
+                        "Skipping, directory has a .licenseignore",
+                    );
+                }
+                return None;
+            }
+            match fs::read_dir(&path) {
+                Ok(entries) => {
+                    for entry in entries {
+                        match entry {
+                            Ok(entry) => stack.push((entry.path(), false)),
+                            Err(e) => {
+                                Stats::bump(&self.stats.unreadable);
+                                let message = format!("Failed to read dir entry ({})", e);
+                                self.walk_error(&path, &message);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    Stats::bump(&self.stats.unreadable);
+                    let message = format!("Failed to read dir ({})", e);
+                    self.walk_error(&path, &message);
+                }
+            }
+            None
+        } else if self.has_excluded_ext(&path) {
+            None
+        } else if config.skip_symlinks && is_symlink(&path) {
+            if config.verbose {
+                self.diag(Level::Info, &path, "Skipping symlink");
+            }
+            None
+        } else {
+            Some((path, explicit))
+        }
+    }
+
+    /// Helper: if a path is excluded
+    fn is_excluded(&self, path: &Path) -> bool {
+        let components: Vec<_> = path.components().collect();
+        for (i, component) in components.iter().enumerate() {
+            match component.as_os_str().to_str() {
+                Some(s) => {
+                    // `build/` only names a directory: any component but the
+                    // last one is, the last one if it's a dir on disk
+                    let is_dir = || i + 1 < components.len() || path.is_dir();
+                    let scoped = self
+                        .config
+                        .scoped_excludes
+                        .iter()
+                        .filter(|(root, _)| path.starts_with(root))
+                        .flat_map(|(_, excludes)| excludes);
+                    for pattern in self.config.excludes.iter().chain(scoped) {
+                        match pattern.strip_suffix('/') {
+                            Some(dir) if name_matches(dir, s) && is_dir() => return true,
+                            Some(_) => {}
+                            None if name_matches(pattern, s) => return true,
+                            None => {}
+                        }
+                    }
+                }
+                None => {
+                    self.diag(Level::Warn, path, "Skipping non-UTF8 path");
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// `--exclude-ext`: does the file name end in one of the excluded extensions?
+    ///
+    /// compared on the whole name, so `min.js` catches `app.min.js` only
+    fn has_excluded_ext(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        self.config.exclude_exts.iter().any(|ext| {
+            name.strip_suffix(ext.as_str())
+                .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+        })
+    }
+}
+
+/// Helper: does exclude `pattern` name the path component `name`? a plain
+/// name must be equal, one with `*`, `?` or `[...]` is matched as a glob
+fn name_matches(pattern: &str, name: &str) -> bool {
+    if glob::has_magic(pattern) {
+        glob::matches(pattern, name)
+    } else {
+        pattern == name
+    }
+}
+
+/// Iterative DFS over the configured targets, see [`LiceEngine::candidates`]
+pub struct Candidates<'a> {
+    engine: &'a LiceEngine,
+    /// pending paths, each flagged whether it is a target given by the user
+    stack: Vec<(PathBuf, bool)>,
+}
+
+impl Candidates<'_> {
+    /// next file, and whether it was listed explicitly rather than discovered
+    fn next_entry(&mut self) -> Option<(PathBuf, bool)> {
+        while let Some((path, explicit)) = self.stack.pop() {
+            if let Some(job) = self.engine.visit(path, explicit, &mut self.stack) {
+                return Some(job);
+            }
+        }
+        None
+    }
+}
+
+/// `--parallel-walk`: the directory stack shared by the walker threads
+///
+/// the walk is over once the stack is empty and no walker is still reading
+/// a directory that may add to it
+struct WalkQueue {
+    /// pending paths, and how many walkers are busy with one
+    state: Mutex<(Vec<(PathBuf, bool)>, usize)>,
+    changed: Condvar,
+}
+
+impl WalkQueue {
+    fn new(stack: Vec<(PathBuf, bool)>) -> Self {
+        WalkQueue {
+            state: Mutex::new((stack, 0)),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// the next path to visit, waiting while busy walkers may still add
+    /// some; `None` once the walk is over. every `Some` must be `finish`ed
+    fn pop(&self) -> Option<(PathBuf, bool)> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(entry) = state.0.pop() {
+                state.1 += 1;
+                return Some(entry);
+            }
+            if state.1 == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// done with a popped path, which turned out to hold `children`
+    fn finish(&self, children: Vec<(PathBuf, bool)>) {
+        let mut state = self.state.lock().unwrap();
+        state.0.extend(children);
+        state.1 -= 1;
+        // wakes idle walkers for the new paths, or to see the walk is over
+        self.changed.notify_all();
+    }
+}
+
+impl Iterator for Candidates<'_> {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        self.next_entry().map(|(path, _)| path)
+    }
+}
+
+/// `content` with the license text `header` in place of its old one, in memory
+///
+/// `header` is the plain text, as in a `-f` file (`{year}` included); it is
+/// commented in `style` and written in the line ending `content` already
+/// uses. a shebang stays on top and the header goes below it; an outdated
+/// header is replaced, not stacked upon. with the defaults of a bare
+/// `lice -f` run, other options don't apply. a file opening a block comment
+/// it never closes comes back unchanged
+pub fn apply_license_to_content(content: &str, header: &str, style: LanguageProfile) -> String {
+    let config = Config::defaults();
+    let mut header = LiceEngine::make_header_for_style(&expand_template(header, &[]), style);
+    if Eol::detect(content) == Eol::Crlf {
+        header = header.replace('\n', "\r\n");
+    }
+    apply_license_with(content, &header, style, &config).unwrap_or_else(|| content.to_string())
+}
+
+/// Helper: `content` with `header` put in place of its old one, no IO involved
+///
+/// `header` is already formatted for `style` and in the line ending the file
+/// should end up with; line breaks inserted around it follow the header. a
+/// shebang stays on top. `None` if the file opens an unclosed block comment
+///
+/// the blank run after an old header is collapsed into the header's own
+/// `blank_lines`; with `--preserve-blank-run` only that many blank lines are
+/// consumed, so the gap becomes `max(run, blank_lines)`. a leading comment
+/// without any `--license-keyword` is kept below the new header. with
+/// `--dedupe-headers` license blocks stacked under the first one go as well.
+/// the file then ends as `--final-newline` says
+fn apply_license_with(
+    content: &str,
+    header: &str,
+    style: LanguageProfile,
+    config: &Config,
+) -> Option<String> {
+    let out = rewrite_content(content, header, style, config)?;
+    Some(fix_final_newline(
+        out,
+        content,
+        style,
+        config,
+        Eol::detect(header),
+    ))
+}
+
+/// Helper: `apply_license_with` before the `--final-newline` policy
+fn rewrite_content(
+    content: &str,
+    header: &str,
+    style: LanguageProfile,
+    config: &Config,
+) -> Option<String> {
+    let eol = Eol::detect(header);
+
+    if config.position == Position::Bottom {
+        // an outdated header at the end is replaced, not stacked upon
+        let kept = match trailing_header_span(content, style, config) {
+            Some(span) => content[..span.start].trim_end_matches(['\n', '\r']),
+            None => content,
+        };
+        return Some(append_header(kept, header, eol));
+    }
+
+    let deduped;
+    let content = match stacked_headers(split_preamble(content, style, config).1, style, config) {
+        Some(stacked) if config.dedupe_headers => {
+            let offset = split_preamble(content, style, config).0.len();
+            let mut kept = content.to_string();
+            kept.replace_range(stacked.start + offset..stacked.end + offset, "");
+            deduped = kept;
+            &deduped
+        }
+        _ => content,
+    };
+
+    if style.start.is_empty() {
+        // line comments
+        return Some(replace_line_comment_header(
+            content, header, style, eol, config,
+        ));
+    }
+
+    // block comments, below any shebang
+    let (preamble, body) = split_preamble(content, style, config);
+    let mut out = String::with_capacity(content.len() + header.len() + 2);
+    push_preamble(&mut out, preamble, eol, config);
+    out.push_str(header);
+
+    // the same span the compliance checks look at; opened but never closed
+    // means a malformed file
+    let old = if opens_block(body.trim_start(), style) {
+        Some(existing_header_span(body, style, config)?)
+    } else {
+        None
+    };
+    match old {
+        Some(span) if config.is_license_text(&body[span.clone()]) => {
+            let rest = &body[span.end..];
+            // whole blank lines only: the first code line keeps its indentation
+            let rest = if config.preserve_blank_run {
+                skip_blank_lines(rest, style.blank_lines)
+            } else {
+                skip_blank_lines(rest, usize::MAX)
+            };
+            out.push_str(rest);
+        }
+        // no header, or a comment that isn't one: keep it all below ours
+        _ => out.push_str(body),
+    }
+    Some(out)
+}
+
+/// Helper: byte range of license blocks stacked right under the first header
+/// in `body` (shebang already stripped), from the end of the first block
+///
+/// only blank lines may separate them, and each must mention a license
+/// keyword (`--license-keyword` or the defaults): that is what a rerun with
+/// a broken detection leaves behind
+fn stacked_headers(body: &str, style: LanguageProfile, config: &Config) -> Option<Range<usize>> {
+    let first = existing_header_span(body, style, config)?;
+    if !config.is_license_text(&body[first.clone()]) {
+        return None;
+    }
+
+    let mut end = first.end;
+    while let Some(span) = existing_header_span(&body[end..], style, config)
+        && config.mentions_license(&body[end..][span.clone()])
+    {
+        end += span.end;
+    }
+    if end == first.end {
+        None
+    } else {
+        Some(first.end..end)
+    }
+}
+
+/// Helper: handle line comment header replacement
+fn replace_line_comment_header(
+    content: &str,
+    header: &str,
+    style: LanguageProfile,
+    eol: Eol,
+    config: &Config,
+) -> String {
+    let (preamble, body) = split_preamble(content, style, config);
+    let preserve_blank_run = config.preserve_blank_run;
+    // byte offset of the first kept line; lines keep their own terminators
+    // so whatever follows the old header (CRLF included) is copied verbatim
+    let mut keep_start = 0;
+    let mut in_blank_run = false;
+    let mut blanks = 0;
+
+    // scan for "old", then the whole blank run after it: the new header
+    // brings its own `blank_lines` (with `preserve_blank_run`, only that many
+    // blanks are consumed and the rest of the run stays). without a marker
+    // (raw style) there is no telling an old header from the content, so
+    // nothing is consumed
+    let marker = style.prefix.trim();
+    // a leading comment that doesn't look like a license stays as it is
+    let replace = match existing_header_span(body, style, config) {
+        Some(span) => config.is_license_text(&body[span]),
+        // none, or one running on past `--header-max-scan-lines` that stays
+        None => !skip_blank_lines(body, usize::MAX)
+            .lines()
+            .next()
+            .is_some_and(|line| is_comment_line(line.trim(), style)),
+    };
+    while replace
+        && !marker.is_empty()
+        && let Some(line) = body[keep_start..].split_inclusive('\n').next()
+    {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            if preserve_blank_run && blanks == style.blank_lines {
+                break;
+            }
+            in_blank_run = true;
+            blanks += 1;
+            keep_start += line.len();
+        } else if !in_blank_run && is_comment_line(trimmed, style) {
+            keep_start += line.len();
+        } else if !in_blank_run && let Some(len) = embedded_doc_len(&body[keep_start..], style) {
+            keep_start += len;
+        } else {
+            // reach the code
+            break;
+        }
+    }
+
+    let body = &body[keep_start..];
+
+    let mut out = String::new();
+    push_preamble(&mut out, preamble, eol, config);
+    out.push_str(header);
+    out.push_str(body);
+    out
+}
+
+/// Helper: the preamble, ending its last line so the header starts on its
+/// own; under `--header-position after-package` a blank line follows a
+/// package line
+fn push_preamble(out: &mut String, preamble: &str, eol: Eol, config: &Config) {
+    out.push_str(preamble);
+    if !preamble.is_empty() && !preamble.ends_with('\n') {
+        out.push_str(eol.as_str());
+    }
+    if config.after_package && preamble.lines().next_back().is_some_and(is_package_line) {
+        out.push_str(eol.as_str());
+    }
+}
+
+/// Helper: apply the `--final-newline` policy to `out`, rewritten from `original`
+fn fix_final_newline(
+    mut out: String,
+    original: &str,
+    style: LanguageProfile,
+    config: &Config,
+    eol: Eol,
+) -> String {
+    let wanted = match config.final_newline {
+        FinalNewline::Strip => 0,
+        // at the bottom the file now ends in the header, with its own ending
+        FinalNewline::Preserve if config.position == Position::Bottom => return out,
+        // nothing but a shebang (or nothing at all), or nothing but the
+        // header once rewritten: no ending worth keeping, and the header's
+        // blank separator would dangle, so exactly one
+        _ if split_preamble(original, style, config).1.trim().is_empty() => 1,
+        _ if is_header_only(&out, style, config) => 1,
+        FinalNewline::Ensure if trailing_newlines(&out).0 > 0 => return out,
+        FinalNewline::Ensure => 1,
+        FinalNewline::Preserve => trailing_newlines(original).0,
+    };
+    let (count, len) = trailing_newlines(&out);
+    if count == wanted {
+        // already right: the body's own line breaks stay as they are
+        return out;
+    }
+    out.truncate(out.len() - len);
+    for _ in 0..wanted {
+        out.push_str(eol.as_str());
+    }
+    out
+}
+
+/// Helper: is `text` a comment block (after any preamble) and blank lines, nothing else?
+fn is_header_only(text: &str, style: LanguageProfile, config: &Config) -> bool {
+    let (_, body) = split_preamble(text, style, config);
+    existing_header_span(body, style, config).is_some_and(|span| body[span.end..].trim().is_empty())
+}
+
+/// Helper: number of line breaks (`\n` or `\r\n`) ending `text`, and their byte length
+fn trailing_newlines(text: &str) -> (usize, usize) {
+    let mut rest = text;
+    let mut count = 0;
+    while let Some(stripped) = rest.strip_suffix('\n') {
+        rest = stripped.strip_suffix('\r').unwrap_or(stripped);
+        count += 1;
+    }
+    (count, text.len() - rest.len())
+}
+
+/// Helper: `text` without up to `limit` leading blank lines
+fn skip_blank_lines(text: &str, limit: usize) -> &str {
+    let mut rest = text;
+    for _ in 0..limit {
+        match rest.split_inclusive('\n').next() {
+            Some(line) if line.trim().is_empty() => rest = &rest[line.len()..],
+            _ => break,
+        }
+    }
+    rest
+}
+
+/// Helper: split `content` into the preamble that must stay above the license
+/// and the body where the license goes
+///
+/// The preamble is the `#!` shebang line, kept verbatim (trailing
+/// whitespace, `env -S python3 -u` and all), followed by any lines starting
+/// with a `--keep-at-top` prefix (`#region`, `#pragma once`). A batch
+/// file's `@echo off` counts as a shebang unless `--header-above-echo-off`.
+/// For styles that take it (Markdown), a leading `---` YAML front-matter
+/// block closed by `---` or `...` comes first instead of the shebang. A preamble-only
+/// file without a trailing newline is entirely preamble; callers re-add the
+/// line break.
+/// The returned preamble length is the byte index where the header is inserted.
+fn split_preamble<'a>(
+    content: &'a str,
+    style: LanguageProfile,
+    config: &Config,
+) -> (&'a str, &'a str) {
+    let mut end = if style.front_matter {
+        front_matter_len(content)
+    } else {
+        0
+    };
+    let mut package_seen = false;
+    for line in content[end..].split_inclusive('\n') {
+        // the blank lines between the package line and the header go with it
+        if package_seen {
+            if !line.trim().is_empty() {
+                break;
+            }
+            end += line.len();
+            continue;
+        }
+        // Rust's `#![no_std]` opens with `#!` too, but is code
+        let shebang = end == 0 && line.starts_with("#!") && !line.starts_with("#![");
+        let leading = end == 0
+            && !style.leading_line.is_empty()
+            && !config.header_above_leading_line
+            && line
+                .trim_start()
+                .to_ascii_lowercase()
+                .starts_with(style.leading_line);
+        let kept = config
+            .keep_at_top
+            .iter()
+            .any(|prefix| line.trim_start().starts_with(prefix.as_str()));
+        let package = config.after_package && is_package_line(line);
+        if !shebang && !leading && !kept && !package && !is_keep_first(line, style, config) {
+            break;
+        }
+        end += line.len();
+        package_seen = package;
+    }
+    content.split_at(end)
+}
+
+/// Helper: a `package` declaration (Java, Go, Perl), for `--header-position
+/// after-package`
+fn is_package_line(line: &str) -> bool {
+    line.strip_prefix("package")
+        .is_some_and(|rest| rest.starts_with([' ', '\t']) && !rest.trim().is_empty())
+}
+
+/// `--self-benchmark`: drop per-file output while the runs are timed
+static SILENT: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_silent(silent: bool) {
+    SILENT.store(silent, Ordering::Relaxed);
+}
+
+/// Helper: print per-file output, or hold it back for the printer under
+/// `--ordered`; one print, so workers can't interleave within it
+fn emit(to_stderr: bool, text: String) {
+    if SILENT.load(Ordering::Relaxed) {
+        return;
+    }
+    HELD.with(|held| match held.borrow_mut().as_mut() {
+        Some(held) => held.push((to_stderr, text)),
+        None if to_stderr => eprint!("{}", text),
+        None => print!("{}", text),
+    });
+}
+
+/// Helper: run `f` for one file, keeping the status line it reports for its
+/// `--format jsonl` record
+fn capture_note<T>(f: impl FnOnce() -> T) -> (T, Option<String>) {
+    NOTE.with(|note| *note.borrow_mut() = Some(None));
+    let result = f();
+    let note = NOTE.with(|note| note.borrow_mut().take()).flatten();
+    (result, note)
+}
+
+/// Helper: keep `message` for the current file's record; false outside a file
+fn hold_note(message: &str) -> bool {
+    NOTE.with(|note| match note.borrow_mut().as_mut() {
+        Some(slot) => {
+            *slot = Some(message.to_string());
+            true
+        }
+        None => false,
+    })
+}
+
+/// Helper: scan rate for the closing line; a run too quick to time has none
+fn files_per_sec(scanned: usize, secs: f64) -> f64 {
+    if secs > 0.0 {
+        scanned as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+/// Helper: the printer, gathering the workers' outcomes; under `--ordered`
+/// it prints their held-back output in discovery order as it fills in
+fn collect_results(results: mpsc::Receiver<Finished>, ordered: bool) -> Vec<(PathBuf, Outcome)> {
+    let mut outcomes = Vec::new();
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    let release = |done: Finished, outcomes: &mut Vec<(PathBuf, Outcome)>| {
+        for (to_stderr, text) in done.output {
+            emit(to_stderr, text);
+        }
+        if let Some(outcome) = done.outcome {
+            outcomes.push((done.path, outcome));
+        }
+    };
+    for done in results {
+        if !ordered {
+            release(done, &mut outcomes);
+            continue;
+        }
+        pending.insert(done.seq, done);
+        while let Some(done) = pending.remove(&next) {
+            release(done, &mut outcomes);
+            next += 1;
+        }
+    }
+    // gaps only if a job got lost, keep the order of the rest
+    for (_, done) in pending {
+        release(done, &mut outcomes);
+    }
+    outcomes
+}
+
+/// a directory holding a file of this name is left out, with all below it
+const IGNORE_MARKER: &str = ".licenseignore";
+
+/// `lice init`: the template it writes, in the current directory
+const INIT_HEADER_FILE: &str = "HEADER.txt";
+
+/// `lice init` without `--license-id`: placeholders to edit
+const INIT_TEMPLATE: &str = "\
+Copyright {year} COPYRIGHT HOLDER
+
+SPDX-License-Identifier: LICENSE-ID
+";
+
+/// `--header-checksum`: the last header line, before the hash
+const CHECKSUM_TAG: &str = "lice-checksum:";
+
+/// Helper: the short hash `--header-checksum` stores for header `text`,
+/// blind to trailing whitespace
+fn header_checksum(text: &str) -> String {
+    let lines: Vec<&str> = text.trim_end().lines().map(str::trim_end).collect();
+    format!("{:08x}", cache::fnv1a(lines.join("\n").as_bytes()) as u32)
+}
+
+/// Helper: `raw` with its `lice-checksum:` line appended
+fn with_checksum(raw: &str) -> String {
+    let text = raw.trim_end();
+    format!("{}\n{}{}\n", text, CHECKSUM_TAG, header_checksum(text))
+}
+
+/// unchanged tail below which `--touch-only-changed-lines` writes the whole file
+const STREAM_MIN_TAIL: usize = 64 * 1024;
+
+/// Helper: how many trailing bytes `a` and `b` share
+fn common_suffix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+/// Helper: several `-f` templates as one, a blank line between; a single
+/// one is kept as it is
+fn join_templates(mut parts: Vec<String>) -> String {
+    if parts.len() == 1 {
+        return parts.remove(0);
+    }
+    let mut out = String::new();
+    for part in &parts {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(part.trim_end_matches(['\r', '\n']));
+        out.push('\n');
+    }
+    out
+}
+
+/// Helper: the files `git ls-files` lists for `filter`, keyed as `git_key`
+fn git_file_set(filter: GitFilter) -> io::Result<HashSet<PathBuf>> {
+    let root = run_git(&["rev-parse", "--show-toplevel"])?;
+    let root = fs::canonicalize(String::from_utf8_lossy(&root).trim())?;
+    let listed = match filter {
+        GitFilter::Untracked => vec!["--others", "--exclude-standard"],
+        _ => Vec::new(),
+    };
+    let root_arg = root.display().to_string();
+    let mut args = vec!["-C", root_arg.as_str(), "ls-files", "-z"];
+    args.extend(listed);
+    let out = run_git(&args)?;
+    Ok(out
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| root.join(String::from_utf8_lossy(name).as_ref()))
+        .collect())
+}
+
+/// Helper: stdout of `git args`, or an error saying why git couldn't answer
+fn run_git(args: &[&str]) -> io::Result<Vec<u8>> {
+    let output = process::Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("--git-filter needs git: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!(
+            "--git-filter: not inside a git repository ({})",
+            stderr.trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Helper: `path` as `git_file_set` keys it: the canonical directory plus
+/// the file name, so a symlink is found by its own name
+fn git_key(path: &Path) -> Option<PathBuf> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Some(fs::canonicalize(dir).ok()?.join(path.file_name()?))
+}
+
+/// Helper: is `line` a one-line comment carrying a `--keep-first` marker
+/// (`// @generated`, `/* eslint-disable */`)?
+fn is_keep_first(line: &str, style: LanguageProfile, config: &Config) -> bool {
+    let text = line.trim();
+    let block = |start: &str, end: &str| {
+        !start.is_empty() && text.starts_with(start) && text.ends_with(end)
+    };
+    let marker = style.prefix.trim();
+    // `//` and `/* */` both comment in the C family
+    let c_family = marker == "//" || style.start.trim() == "/*";
+    let comment = (style.start.is_empty() && !marker.is_empty() && text.starts_with(marker))
+        || block(style.start.trim(), style.end.trim())
+        || (c_family && (text.starts_with("//") || block("/*", "*/")));
+    comment
+        && config
+            .keep_first
+            .iter()
+            .any(|marker| text.contains(marker.as_str()))
+}
+
+/// Helper: byte length of the YAML front matter opening `content`, closing
+/// line included; 0 if there is none (or it never closes)
+fn front_matter_len(content: &str) -> usize {
+    let mut lines = content.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some("---") {
+        return 0;
+    }
+    let mut end = 4 + usize::from(content.starts_with("---\r\n"));
+    for line in lines {
+        end += line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            return end;
+        }
+    }
+    0
+}
+
+/// Helper: the first line of `path`, reading at most `SHEBANG_SCAN_BYTES`
+fn first_line(path: &Path) -> Option<String> {
+    let file = fs::File::open(path).ok()?;
+    let mut line = String::new();
+    io::BufReader::new(file.take(SHEBANG_SCAN_BYTES))
+        .read_line(&mut line)
+        .ok()?;
+    Some(line)
+}
+
+/// Helper: read `path` as UTF-8, pointing at the first invalid byte if it isn't
+fn read_text(path: &Path) -> Result<String, FileError> {
+    let bytes = fs::read(path).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => FileError {
+            denied: true,
+            ..FileError::new(format!("permission denied reading {}", path.display()))
+        },
+        _ => FileError::from(e),
+    })?;
+    String::from_utf8(bytes).map_err(|e| {
+        let offset = e.utf8_error().valid_up_to();
+        FileError {
+            at: Some(Location::of(e.as_bytes(), offset)),
+            ..FileError::new("file is not valid UTF-8")
+        }
+    })
+}
+
+/// Helper: `path` as `--match` sees it: `/`-separated, without `./`, and
+/// relative to the working directory when it lies below it
+fn match_path(path: &Path) -> String {
+    let cwd = if path.is_absolute() {
+        env::current_dir().ok()
+    } else {
+        None
+    };
+    let relative = cwd
+        .as_deref()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path);
+    let parts: Vec<_> = relative
+        .components()
+        .filter_map(|c| match c {
+            Component::CurDir => None,
+            Component::RootDir => Some("".into()),
+            c => Some(c.as_os_str().to_string_lossy()),
+        })
+        .collect();
+    parts.join("/")
+}
+
+/// Helper: `path` relative to the `--patch-root` `root`, `/` separated;
+/// as found if it lies outside
+fn patch_path(path: &Path, root: &Path) -> String {
+    match fs::canonicalize(path) {
+        Ok(full) if full.starts_with(root) => match_path(full.strip_prefix(root).unwrap()),
+        _ => match_path(path),
+    }
+}
+
+/// Helper: `path` as one word for `sh -c` (or `cmd /C`)
+fn shell_quote(path: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+/// Helper: `1536` as `1.5 KB`, binary units
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Helper: does `-f` name a URL rather than a file?
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Helper: is `path` itself a symlink (not following it)
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+/// Helper: does `content` already start with `header` (shebang aside)?
+///
+/// any number of blank lines may separate it from the code, as long as there
+/// is one when the header asks for any (older versions, other settings);
+/// with `--fix-blank-lines` the count must match the header's own exactly.
+/// a header-only file is fine too
+fn has_current_header(
+    content: &str,
+    header: &str,
+    style: LanguageProfile,
+    config: &Config,
+) -> bool {
+    let (_, body) = split_preamble(content, style, config);
+    let body = skip_blank_lines(body, usize::MAX);
+    if config.fix_blank_lines {
+        return match body.strip_prefix(header) {
+            Some(rest) => !rest.starts_with('\n') && !rest.starts_with("\r\n"),
+            None => body.trim_end() == header.trim_end(),
+        };
+    }
+
+    let text = header.trim_end_matches(['\n', '\r']);
+    let Some(rest) = body.strip_prefix(text) else {
+        return false;
+    };
+    // the header's last line has to end where ours does
+    let Some(rest) = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))
+    else {
+        return rest.is_empty();
+    };
+    let wants_blank = header.len() - text.len() > Eol::detect(header).as_str().len();
+    let code = skip_blank_lines(rest, usize::MAX);
+    !wants_blank || code.is_empty() || code.len() < rest.len()
+}
+
+/// Helper: `content` with `header` appended, separated by one blank line
+///
+/// the header's own trailing blank line is dropped, it would dangle at EOF
+fn append_header(content: &str, header: &str, eol: Eol) -> String {
+    let header = header.trim_end();
+    let eol = eol.as_str();
+    if content.trim().is_empty() {
+        return format!("{}{}", header, eol);
+    }
+
+    let mut out = String::with_capacity(content.len() + header.len() + 6);
+    out.push_str(content);
+    if !out.ends_with('\n') {
+        out.push_str(eol);
+    }
+    out.push_str(eol);
+    out.push_str(header);
+    out.push_str(eol);
+    out
+}
+
+/// Helper: `content` without its license header and the blank lines after
+/// it; `None` if there is none (a leading doc comment is no header)
+fn remove_header(content: &str, style: LanguageProfile, config: &Config) -> Option<String> {
+    match config.position {
+        Position::Bottom => {
+            let span = trailing_header_span(content, style, config)?;
+            let kept = content[..span.start].trim_end();
+            Some(if kept.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", kept, Eol::detect(content).as_str())
+            })
+        }
+        Position::Top => {
+            let (preamble, body) = split_preamble(content, style, config);
+            let span = existing_header_span(body, style, config)
+                .filter(|span| config.mentions_license(&body[span.clone()]))?;
+            Some(format!(
+                "{}{}",
+                preamble,
+                skip_blank_lines(&body[span.end..], usize::MAX)
+            ))
+        }
+    }
+}
+
+/// Helper: byte range of a license comment ending `content`, as
+/// `append_header` leaves it: after a blank line (or alone in the file), with
+/// only whitespace behind it
+///
+/// the bottom-position mirror of `existing_header_span`, looking at the last
+/// `--header-max-scan-lines` lines; a closing comment that doesn't mention a
+/// license (a vim modeline) is not taken for one
+fn trailing_header_span(
+    content: &str,
+    style: LanguageProfile,
+    config: &Config,
+) -> Option<Range<usize>> {
+    let end = content.trim_end().len();
+    let lines: Vec<&str> = content[..end].split_inclusive('\n').collect();
+    let first = lines.len().saturating_sub(config.header_max_scan_lines);
+    let offset_of = |i: usize| lines[..i].iter().map(|l| l.len()).sum::<usize>();
+
+    let open = if !style.start.is_empty() {
+        if !content[..end].ends_with(style.end.trim()) {
+            return None;
+        }
+        // the line the last comment opens on
+        (first..lines.len())
+            .rev()
+            .find(|&i| lines[i].trim_start().starts_with(style.start.trim_end()))?
+    } else {
+        let marker = style.prefix.trim();
+        if marker.is_empty() {
+            return None;
+        }
+        let run = lines[first..]
+            .iter()
+            .rev()
+            .take_while(|l| is_comment_line(l.trim(), style))
+            .count();
+        // cut short by the window, or no comment at the end at all
+        if run == 0 || (first > 0 && run == lines.len() - first) {
+            return None;
+        }
+        lines.len() - run
+    };
+    if open > 0 && !lines[open - 1].trim().is_empty() {
+        return None;
+    }
+    let span = offset_of(open)..content.len();
+    config
+        .mentions_license(&content[span.clone()])
+        .then_some(span)
+}
+
+/// Helper: byte range of the leading comment block in `body` (shebang already stripped)
+///
+/// line styles: consecutive lines starting with the comment marker
+/// block styles: from `start` up to the terminator closing that same
+/// comment (`*/` however it is spaced, found after the opening `/*`), plus
+/// the rest of its line if blank; the file may end right after it
+///
+/// only the first `--header-max-scan-lines` lines are looked at, a comment
+/// running on past them is no header
+fn existing_header_span(
+    body: &str,
+    style: LanguageProfile,
+    config: &Config,
+) -> Option<Range<usize>> {
+    let window = &body[..scan_len(body, config.header_max_scan_lines)];
+    let lead = window.len() - window.trim_start().len();
+
+    if !style.start.is_empty() {
+        if !opens_block(&body[lead..], style) {
+            return None;
+        }
+        let open = lead + style.start.trim_end().len();
+        let terminator = style.end.trim();
+        let mut close = open + window.get(open..)?.find(terminator)? + terminator.len();
+        // only whitespace left on the line: it goes with the comment
+        let line = body[close..]
+            .split_inclusive('\n')
+            .next()
+            .unwrap_or_default();
+        if line.trim().is_empty() {
+            close += line.len();
+        }
+        return Some(lead..close);
+    }
+
+    let marker = style.prefix.trim();
+    if marker.is_empty() {
+        return None;
+    }
+    let mut end = lead;
+    while let Some(line) = window[end..].split_inclusive('\n').next() {
+        if is_comment_line(line.trim(), style) {
+            end += line.len();
+        } else if let Some(len) = embedded_doc_len(&window[end..], style) {
+            end += len;
+        } else {
+            break;
+        }
+    }
+    // cut short by the window, not by the code
+    if end == window.len()
+        && let Some(next) = body[end..].split_inclusive('\n').next()
+        && is_comment_line(next.trim(), style)
+    {
+        return None;
+    }
+
+    if end == lead { None } else { Some(lead..end) }
+}
+
+/// Helper: does `text` start with the block opener of `style`?
+///
+/// the opener's own line break matches `\n` and `\r\n` alike, so a CRLF
+/// file's `/*\r\n` is the same header as `/*\n`
+fn opens_block(text: &str, style: LanguageProfile) -> bool {
+    let marker = style.start.trim_end();
+    if marker.is_empty() || marker.len() == style.start.len() {
+        return !style.start.is_empty() && text.starts_with(style.start);
+    }
+    text.strip_prefix(marker)
+        .is_some_and(|rest| rest.starts_with('\n') || rest.starts_with("\r\n"))
+}
+
+/// Helper: byte length of the first `lines` lines of `text`, terminators
+/// included, without splitting the rest
+fn scan_len(text: &str, lines: usize) -> usize {
+    match lines.checked_sub(1) {
+        Some(n) => text
+            .match_indices('\n')
+            .nth(n)
+            .map_or(text.len(), |(i, _)| i + 1),
+        None => 0,
+    }
+}
+
+/// Helper: does a trimmed line start with the comment marker of a line style,
+/// or with the marker of its first line?
+///
+/// `//!` and `///` doc comments (Rust module and item docs, Doxygen) belong
+/// to the code, so they are never part of a header; `////` rules still are
+fn is_comment_line(trimmed: &str, style: LanguageProfile) -> bool {
+    let marker = style.prefix.trim();
+    if marker == "//"
+        && (trimmed.starts_with("//!")
+            || (trimmed.starts_with("///") && !trimmed.starts_with("////")))
+    {
+        return false;
+    }
+    let first = style.first_line_prefix.trim();
+    trimmed.starts_with(marker) || (!first.is_empty() && trimmed.starts_with(first))
+}
+
+/// Helper: byte length of an embedded document opening `text`, up to and
+/// including its closing line
+///
+/// hash-style only: Ruby `=begin` .. `=end` and Perl POD (`=pod`, `=head1`, ..)
+/// .. `=cut`; the directives must start in column 0. an unterminated block is
+/// not treated as a comment
+fn embedded_doc_len(text: &str, style: LanguageProfile) -> Option<usize> {
+    if style.prefix.trim() != "#" || !style.start.is_empty() {
+        return None;
+    }
+    let first = text.split_inclusive('\n').next()?;
+    let directive = first.trim_end();
+    let close = match directive.strip_prefix('=') {
+        Some(d) if d == "begin" || d.starts_with("begin ") => "=end",
+        Some(d) if d.starts_with(|c: char| c.is_ascii_alphabetic()) => "=cut",
+        _ => return None,
+    };
+
+    let mut len = first.len();
+    for line in text[first.len()..].split_inclusive('\n') {
+        len += line.len();
+        let line = line.trim_end();
+        if line == close || line.starts_with(&format!("{} ", close)) {
+            return Some(len);
+        }
+    }
+    None
+}
+
+/// Helper: the license lines to comment, trimmed; boxed for `--style banner`
+fn banner_lines(raw: &str, style: LanguageProfile) -> Vec<String> {
+    let lines: Vec<&str> = raw.lines().map(str::trim_end).collect();
+    if !style.banner {
+        return lines.into_iter().map(str::to_string).collect();
+    }
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let rule = "=".repeat(width + 4);
+    let mut out = vec![rule.clone()];
+    out.extend(lines.iter().map(|line| {
+        let pad = width - line.chars().count();
+        format!("| {}{} |", line, " ".repeat(pad))
+    }));
+    out.push(rule);
+    out
+}
+
+/// Helper: the inverse of `make_header_for_style`, for a header span
+///
+/// drops the block `start`/`end` lines and each line's prefix and suffix;
+/// blank comment lines (prefix without the trailing space) become empty lines
+fn strip_comment(commented: &str, style: LanguageProfile) -> String {
+    let start = style.start.trim();
+    let end = style.end.trim();
+    let prefix = style.prefix;
+    let bare_prefix = prefix.trim_end();
+
+    let mut lines: Vec<&str> = commented.lines().map(str::trim_end).collect();
+    if !start.is_empty() && lines.first().is_some_and(|l| l.trim() == start) {
+        lines.remove(0);
+    }
+    if !end.is_empty() && lines.last().is_some_and(|l| l.trim() == end) {
+        lines.pop();
+    }
+
+    let mut out = String::new();
+    for (i, line) in lines.into_iter().enumerate() {
+        let first = Some(style.first_line_prefix).filter(|_| i == 0);
+        let text = first
+            .and_then(|p| {
+                line.strip_prefix(p)
+                    .or_else(|| line.strip_prefix(p.trim_end()))
+            })
+            .or_else(|| line.strip_prefix(prefix))
+            .or_else(|| line.strip_prefix(bare_prefix))
+            .unwrap_or(line);
+        let text = match style.suffix.trim_end() {
+            "" => text,
+            suffix => text.strip_suffix(suffix).unwrap_or(text),
+        };
+        let text = text.trim_end();
+        if style.banner {
+            // rule lines go, the borders come off the rest
+            if !text.is_empty() && text.chars().all(|c| c == '=') {
+                continue;
+            }
+            let inner = text.strip_prefix('|').and_then(|t| t.strip_suffix('|'));
+            if let Some(inner) = inner {
+                out.push_str(inner.strip_prefix(' ').unwrap_or(inner).trim_end());
+                out.push('\n');
+                continue;
+            }
+        }
+        out.push_str(text);
+        out.push('\n');
+    }
+    out
+}
+
+/// how far into a file `spdx_license_id` looks
+const SPDX_SCAN_LINES: usize = 20;
+
+/// Helper: the `SPDX-License-Identifier` expression in the first lines of
+/// `text`, without the comment markers around it
+pub(crate) fn spdx_license_id(text: &str) -> Option<&str> {
+    const TAG: &str = "SPDX-License-Identifier:";
+    text.lines().take(SPDX_SCAN_LINES).find_map(|line| {
+        let (_, rest) = line.split_once(TAG)?;
+        let id = rest
+            .trim()
+            .trim_end_matches("*/")
+            .trim_end_matches("-->")
+            .trim_end();
+        (!id.is_empty()).then_some(id)
+    })
+}
+
+/// Helper: `text` with the spaces and tabs at the end of each line removed,
+/// line endings kept
+fn strip_line_ends(text: &str) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let body = line.trim_end_matches(['\n', '\r']);
+            let eol = &line[body.len()..];
+            format!("{}{}", body.trim_end_matches([' ', '\t']), eol)
+        })
+        .collect()
+}
+
+/// Helper: `content` with trailing whitespace stripped inside its leading
+/// comment only; `None` if there is none to strip
+fn clean_header(content: &str, style: LanguageProfile, config: &Config) -> Option<String> {
+    let (preamble, body) = split_preamble(content, style, config);
+    let span = existing_header_span(body, style, config)?;
+    let cleaned = strip_line_ends(&body[span.clone()]);
+    if cleaned.len() == span.len() {
+        return None;
+    }
+    let start = preamble.len() + span.start;
+    let end = preamble.len() + span.end;
+    Some(format!(
+        "{}{}{}",
+        &content[..start],
+        cleaned,
+        &content[end..]
+    ))
+}
+
+/// Helper: do two license texts say the same, ignoring whitespace around
+/// each line and blank lines before and after?
+fn same_text(a: &str, b: &str) -> bool {
+    let lines = |text: &str| -> Vec<String> {
+        let lines: Vec<&str> = text.lines().map(str::trim).collect();
+        let start = lines
+            .iter()
+            .position(|l| !l.is_empty())
+            .unwrap_or(lines.len());
+        let end = lines
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(start, |i| i + 1);
+        lines[start..end].iter().map(|l| l.to_string()).collect()
+    };
+    lines(a) == lines(b)
+}
+
+/// Helper: locate the first copyright year (`2023`) or year range (`2020-2024`)
+///
+/// returns the byte range of the whole token together with its first and last year
+fn find_year_token(text: &str) -> Option<(Range<usize>, u32, u32)> {
+    let bytes = text.as_bytes();
+    let year_at = |i: usize| -> Option<u32> {
+        let digits = bytes.get(i..i + 4)?;
+        if !digits.iter().all(u8::is_ascii_digit) || !(digits[0] == b'1' || digits[0] == b'2') {
+            return None;
+        }
+        // must not be part of a longer number
+        if i > 0 && bytes[i - 1].is_ascii_digit() {
+            return None;
+        }
+        if bytes.get(i + 4).is_some_and(u8::is_ascii_digit) {
+            return None;
+        }
+        text[i..i + 4].parse().ok()
+    };
+
+    for start in 0..bytes.len() {
+        let Some(first) = year_at(start) else {
+            continue;
+        };
+
+        // optional range: "2020-2024", "2020 - 2024"
+        let rest = &text[start + 4..];
+        let after_dash = rest.trim_start();
+        if let Some(stripped) = after_dash.strip_prefix('-') {
+            let gap = stripped.len() - stripped.trim_start().len();
+            let second_at = start + 4 + (rest.len() - stripped.len()) + gap;
+            if let Some(last) = year_at(second_at) {
+                return Some((start..second_at + 4, first, last));
+            }
+        }
+        return Some((start..start + 4, first, first));
+    }
+    None
+}
+
+/// Helper: the bare text of a template pasted with its comment markers still on
+///
+/// if every line carries one built-in style's marker (or the whole text is one
+/// block comment) the markers are stripped, so each file gets its own style
+/// instead of `// # ...`; any other template is returned unchanged
+///
+/// a block may open as `/**` or `/*****`, start its text on the opening line
+/// (`/* Copyright ...`) and close on the last line of text (`... */`)
+fn uncomment_template(raw: &str) -> String {
+    let lines: Vec<&str> = raw
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if lines.is_empty() {
+        return raw.to_string();
+    }
+
+    let commented_in = |style: &LanguageProfile| {
+        if style.start.is_empty() {
+            let marker = style.prefix.trim();
+            lines.iter().all(|l| l.starts_with(marker))
+        } else {
+            lines[0].starts_with(style.start.trim())
+                && lines[lines.len() - 1].ends_with(style.end.trim())
+                && (lines.len() >= 2 || lines[0].len() >= style.start.len() + style.end.len())
+        }
+    };
+
+    match BUILTIN_STYLES.iter().find(|style| commented_in(style)) {
+        Some(&style) if !style.start.is_empty() => uncomment_block(raw, style),
+        Some(&style) => strip_comment(raw, style),
+        None => raw.to_string(),
+    }
+}
+
+/// Helper: the text of a block comment template; the markers go however
+/// they are drawn, lines without a ` * ` lose only their common indent
+fn uncomment_block(raw: &str, style: LanguageProfile) -> String {
+    let start = style.start.trim();
+    let end = style.end.trim();
+    let marker = Some(style.prefix.trim()).filter(|m| !m.is_empty());
+    // `/**`, `/*****`, `*****/`: the markers' own characters, repeated
+    let fill = |c: char| start.ends_with(c) || end.starts_with(c);
+    let lines: Vec<&str> = raw
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|l| l.is_empty())
+        .collect();
+    let last = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .unwrap_or_default();
+    let starred = |l: &str| marker.is_some_and(|m| l.trim_start().starts_with(m));
+    let indent = lines
+        .get(1..last)
+        .unwrap_or_default()
+        .iter()
+        .filter(|l| !l.is_empty() && !starred(l))
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    for (i, &line) in lines[..=last].iter().enumerate() {
+        let mut text = line;
+        if i == 0 {
+            text = text.trim_start()[start.len()..].trim_start_matches(fill);
+        }
+        if i == last {
+            text = text
+                .strip_suffix(end)
+                .unwrap_or(text)
+                .trim_end_matches(fill);
+        }
+        let text = match marker {
+            _ if i == 0 => text.trim_start(),
+            Some(m) if starred(text) => {
+                let rest = &text.trim_start()[m.len()..];
+                rest.strip_prefix(' ').unwrap_or(rest)
+            }
+            _ => text.get(indent..).unwrap_or(text.trim_start()),
+        }
+        .trim_end();
+        // the opening and closing lines hold text only past their markers
+        if text.is_empty() && (i == 0 || i == last) {
+            continue;
+        }
+        out.push_str(text);
+        out.push('\n');
+    }
+    out
+}
+
+/// Helper: a template as headers are built from it, placeholders (`{year}`,
+/// `--header-var`) filled in and line ends made `\n`
+///
+/// a template saved with `\r\n` (or old Mac `\r`) must not carry its carriage
+/// returns into LF files; each file's own ending is put back on writing.
+/// `vars` go first, so `--header-var year=2020` pins the year
+fn expand_template(raw: &str, vars: &[(String, String)]) -> String {
+    let mut text = raw.replace("\r\n", "\n").replace('\r', "\n");
+    for (key, value) in vars {
+        text = text.replace(&format!("{{{}}}", key), value);
+    }
+    text.replace("{year}", &current_year().to_string())
+}
+
+/// Helper: the `{name}` placeholders still in `text`, each once, in order
+fn unset_placeholders(text: &str) -> Vec<&str> {
+    let mut names: Vec<&str> = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        rest = &rest[open + 1..];
+        if let Some(close) = rest.find('}')
+            && is_placeholder_name(&rest[..close])
+            && !names.contains(&&rest[..close])
+        {
+            names.push(&rest[..close]);
+        }
+    }
+    names
+}
+
+/// Helper: current calendar year (UTC) without pulling in a date crate
+fn current_year() -> u32 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86_400) as i64;
+
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    year as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::{STYLE_C_LIKE, STYLE_DOUBLE_SLASH, STYLE_HASH};
+    use std::process;
+
+    /// a fresh, empty directory under the system temp dir
+    fn scratch() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("lice-test-{}-{n}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// an engine with `template` as the license file and `extra` flags
+    fn engine_with(template: &str, extra: &[&str]) -> LiceEngine {
+        let dir = scratch();
+        let path = dir.join("HEADER.txt");
+        fs::write(&path, template).unwrap();
+        let mut args = vec!["-f".to_string(), path.display().to_string()];
+        args.extend(extra.iter().map(|arg| arg.to_string()));
+        args.push(dir.display().to_string());
+        let engine = LiceEngine::new(Config::from_args(args).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        engine
+    }
+
+    /// an engine with a one-line template
+    fn engine(extra: &[&str]) -> LiceEngine {
+        engine_with("Copyright 2024 New\n", extra)
+    }
+
+    /// apply `engine` to a scratch file `name` holding `content`, return the result
+    fn licensed(engine: &LiceEngine, name: &str, content: &str) -> String {
+        let dir = scratch();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        let ext = name.rsplit('.').next().unwrap();
+        engine
+            .apply_license(&path, engine.style_for_ext(ext).unwrap(), None)
+            .unwrap();
+        let result = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[test]
+    fn year_token_is_a_year_or_a_range() {
+        assert_eq!(
+            find_year_token("Copyright 2021 Jane"),
+            Some((10..14, 2021, 2021))
+        );
+        assert_eq!(
+            find_year_token("(c) 2019-2022 Jane"),
+            Some((4..13, 2019, 2022))
+        );
+        assert_eq!(
+            find_year_token("(c) 2019 - 2022"),
+            Some((4..15, 2019, 2022))
+        );
+        // longer numbers and non-years are not years
+        assert_eq!(
+            find_year_token("build 120233 v3000, 2020"),
+            Some((20..24, 2020, 2020))
+        );
+        assert_eq!(find_year_token("no year here"), None);
+    }
+
+    #[test]
+    fn update_year_only_extends_the_year_and_nothing_else() {
+        let dir = scratch();
+        let engine = engine(&["--update-year-only"]);
+        let year = current_year();
+
+        let stale = dir.join("stale.rs");
+        fs::write(&stale, "// Copyright 2020 Jane\n\nfn main() {} // 2020\n").unwrap();
+        engine
+            .apply_license(&stale, STYLE_DOUBLE_SLASH, None)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&stale).unwrap(),
+            format!("// Copyright 2020-{year} Jane\n\nfn main() {{}} // 2020\n")
+        );
+
+        // current headers and files without one are left as they are
+        let current = format!("#!/bin/sh\n# (c) 2001-{year}\necho 2001\n");
+        let fresh = dir.join("fresh.sh");
+        fs::write(&fresh, &current).unwrap();
+        engine.apply_license(&fresh, STYLE_HASH, None).unwrap();
+        assert_eq!(fs::read_to_string(&fresh).unwrap(), current);
+        let bare = dir.join("bare.rs");
+        fs::write(&bare, "fn main() {}\n").unwrap();
+        engine
+            .apply_license(&bare, STYLE_DOUBLE_SLASH, None)
+            .unwrap();
+        assert_eq!(fs::read_to_string(&bare).unwrap(), "fn main() {}\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn report_unlicensed_counts_only_files_without_any_header() {
+        let dir = scratch();
+        let engine = engine(&["--report-unlicensed"]);
+        let files = [
+            ("bare.py", "x = 1\n"),
+            ("script.sh", "#!/bin/sh\necho hi\n"),
+            ("outdated.py", "# Copyright 1999 Old\n\nx = 1\n"),
+            ("shebang.sh", "#!/bin/sh\n# Copyright 2024 New\necho hi\n"),
+        ];
+        for (name, content) in files {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            engine.apply_license(&path, STYLE_HASH, None).unwrap();
+            // read-only: nothing is touched
+            assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        }
+        assert_eq!(Stats::get(&engine.stats.violations), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shebang_stays_above_the_header() {
+        let dir = scratch();
+        let engine = engine(&[]);
+        let apply = |name: &str, content: &str| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            engine.apply_license(&path, STYLE_HASH, None).unwrap();
+            fs::read_to_string(&path).unwrap()
+        };
+        assert_eq!(
+            apply("a.py", "#!/usr/bin/env python3\nprint(1)\n"),
+            "#!/usr/bin/env python3\n# Copyright 2024 New\n\nprint(1)\n"
+        );
+        // without one the header simply goes first
+        assert_eq!(
+            apply("b.py", "print(1)\n"),
+            "# Copyright 2024 New\n\nprint(1)\n"
+        );
+        // and an old header under the shebang is replaced in place
+        assert_eq!(
+            apply("c.sh", "#!/bin/sh -e \n# Copyright 2020 Old\n\necho hi\n"),
+            "#!/bin/sh -e \n# Copyright 2024 New\n\necho hi\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn preamble_is_the_shebang_line_only() {
+        let config = Config::defaults();
+        let split = |content| split_preamble(content, STYLE_HASH, &config);
+        assert_eq!(split("#!/bin/sh\necho\n"), ("#!/bin/sh\n", "echo\n"));
+        assert_eq!(split("echo\n#!/bin/sh\n"), ("", "echo\n#!/bin/sh\n"));
+        assert_eq!(split("#!/bin/sh"), ("#!/bin/sh", ""));
+    }
+
+    #[test]
+    fn prefix_overrides_are_used_for_writing_and_checking() {
+        let dir = scratch();
+        let path = dir.join("a.rs");
+        let engine = engine(&["--prefix", ";; "]);
+        let style = engine.style_for_ext("rs").unwrap();
+        fs::write(&path, "fn main() {}\n").unwrap();
+        engine.apply_license(&path, style, None).unwrap();
+        let licensed = ";; Copyright 2024 New\n\nfn main() {}\n";
+        assert_eq!(fs::read_to_string(&path).unwrap(), licensed);
+        // a second run recognizes its own header
+        engine.apply_license(&path, style, None).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), licensed);
+
+        let engine = self::engine(&["--no-prefix-space"]);
+        let style = engine.style_for_ext("py").unwrap();
+        let path = dir.join("a.py");
+        fs::write(&path, "x = 1\n").unwrap();
+        engine.apply_license(&path, style, None).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "#Copyright 2024 New\n\nx = 1\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn suffix_closes_every_license_line() {
+        let dir = scratch();
+        let path = dir.join("a.rs");
+        let engine = engine_with(
+            "Copyright 2024 New\nAll rights reserved\n",
+            &["--suffix", " //"],
+        );
+        let style = engine.style_for_ext("rs").unwrap();
+        fs::write(&path, "fn main() {}\n").unwrap();
+        engine.apply_license(&path, style, None).unwrap();
+        let licensed = "// Copyright 2024 New //\n// All rights reserved //\n\nfn main() {}\n";
+        assert_eq!(fs::read_to_string(&path).unwrap(), licensed);
+        engine.apply_license(&path, style, None).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), licensed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn count_only_tallies_without_writing() {
+        let dir = scratch();
+        let engine = engine(&["--count-only"]);
+        let files = [
+            ("current.py", "# Copyright 2024 New\n\nx = 1\n"),
+            ("outdated.py", "# Copyright 1999 Old\n\nx = 1\n"),
+            ("bare.rs", "fn main() {}\n"),
+            ("notes.txt", "hello\n"),
+        ];
+        for (name, content) in files {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            engine.process_file(&path, false);
+            assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        }
+        let stats = &engine.stats;
+        assert_eq!(
+            [&stats.compliant, &stats.non_compliant, &stats.unsupported].map(Stats::get),
+            [1, 2, 1]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn headers_are_formatted_once_per_customized_style() {
+        let engine = engine(&[]);
+        assert_eq!(engine.headers.len(), BUILTIN_STYLES.len());
+
+        let engine = self::engine(&["--prefix", ";; "]);
+        // the plain line styles are now all alike; the block styles, and the
+        // batch style with its `@echo off`, are still styles of their own
+        assert_eq!(engine.headers.len(), 4);
+        let style = engine.style_for_ext("py").unwrap();
+        assert!(matches!(
+            engine.header_for(style, None),
+            Cow::Borrowed(";; Copyright 2024 New\n\n")
+        ));
+        // a style nobody asked for in `new` is still formatted, just not kept
+        assert!(matches!(engine.header_for(STYLE_HASH, None), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn kept_body_keeps_its_line_endings() {
+        let dir = scratch();
+        let engine = engine(&[]);
+        let apply = |name: &str, content: &str, style| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            engine.apply_license(&path, style, None).unwrap();
+            fs::read_to_string(&path).unwrap()
+        };
+        assert_eq!(
+            apply(
+                "a.py",
+                "# Copyright 2020 Old\r\n\r\nx = 1\r\ny = 2\r\n",
+                STYLE_HASH
+            ),
+            "# Copyright 2024 New\r\n\r\nx = 1\r\ny = 2\r\n"
+        );
+        // a CRLF block header is found and replaced, not stacked
+        assert_eq!(
+            apply(
+                "a.c",
+                "/*\r\n * Copyright 2020 Old\r\n */\r\n\r\nint x;\r\n",
+                STYLE_C_LIKE
+            ),
+            "/*\r\n * Copyright 2024 New\r\n */\r\n\r\nint x;\r\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skip_symlinks_leaves_linked_files_out_of_the_walk() {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("src/real.py"), "x = 1\n").unwrap();
+        std::os::unix::fs::symlink(dir.join("src/real.py"), dir.join("src/link.py")).unwrap();
+        let walk = |extra: &[&str]| {
+            let mut args = vec![
+                "-f".to_string(),
+                dir.join("HEADER.txt").display().to_string(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            args.push(dir.join("src").display().to_string());
+            let engine = LiceEngine::new(Config::from_args(args).unwrap()).unwrap();
+            let mut seen: Vec<PathBuf> = engine.candidates().collect();
+            seen.sort();
+            seen
+        };
+        let src = dir.join("src");
+        assert_eq!(walk(&[]), [src.join("link.py"), src.join("real.py")]);
+        assert_eq!(walk(&["--skip-symlinks"]), [src.join("real.py")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn candidates_are_the_files_left_after_excludes() {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        for name in ["src/a.rs", "src/vendor/b.rs", "src/notes.txt"] {
+            fs::create_dir_all(dir.join(name).parent().unwrap()).unwrap();
+            fs::write(dir.join(name), "x\n").unwrap();
+        }
+        let args = [
+            "-f".to_string(),
+            dir.join("HEADER.txt").display().to_string(),
+            "-e".to_string(),
+            "vendor".to_string(),
+            dir.join("src").display().to_string(),
+        ];
+        let engine = LiceEngine::new(Config::from_args(args).unwrap()).unwrap();
+        let mut found: Vec<PathBuf> = engine.candidates().collect();
+        found.sort();
+        // unsupported files are still candidates, `run` reports them
+        assert_eq!(found, [dir.join("src/a.rs"), dir.join("src/notes.txt")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_unrunnable_config_is_an_error_not_a_panic() {
+        let help = Config::from_args(["-h".to_string()]).unwrap();
+        match LiceEngine::new(help) {
+            Ok(_) => panic!("built an engine for -h"),
+            Err(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+                assert!(e.to_string().contains("usage"), "{e}");
+            }
+        }
+
+        // built by hand, around the checks `from_args` makes
+        let mut unknown = Config::defaults();
+        unknown.license_id = Some("WTFPL".to_string());
+        for (config, message) in [
+            (Config::defaults(), "no license template"),
+            (unknown, "unknown --license-id: WTFPL"),
+        ] {
+            match LiceEngine::new(config) {
+                Ok(_) => panic!("built an engine without a template"),
+                Err(e) => {
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+                    assert!(e.to_string().starts_with(message), "{e}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn out_dir_mirrors_results_and_leaves_the_sources() {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::write(dir.join("src/sub/a.py"), "x = 1\n").unwrap();
+        fs::write(dir.join("src/b.py"), "# Copyright 2024 New\n\nx = 2\n").unwrap();
+        let out = dir.join("src/out");
+        let run = |extra: &[&str]| {
+            let mut args = vec![
+                "-f".to_string(),
+                dir.join("HEADER.txt").display().to_string(),
+            ];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            args.extend(["-j", "1"].map(String::from));
+            args.push(dir.join("src").display().to_string());
+            LiceEngine::new(Config::from_args(args).unwrap())
+                .unwrap()
+                .run()
+                .unwrap();
+        };
+        run(&["--out-dir", &out.display().to_string()]);
+        assert_eq!(
+            fs::read_to_string(dir.join("src/sub/a.py")).unwrap(),
+            "x = 1\n"
+        );
+        let mirrored: PathBuf = dir
+            .join("src/sub/a.py")
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        let written = fs::canonicalize(&out).unwrap().join(&mirrored);
+        assert_eq!(
+            fs::read_to_string(&written).unwrap(),
+            "# Copyright 2024 New\n\nx = 1\n"
+        );
+        // the compliant file isn't mirrored, and the output dir isn't walked
+        let b: PathBuf = dir
+            .join("src/b.py")
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        assert!(!fs::canonicalize(&out).unwrap().join(&b).exists());
+
+        run(&["--out-dir", &out.display().to_string(), "--copy-unchanged"]);
+        let copied = fs::canonicalize(&out).unwrap().join(&b);
+        assert_eq!(
+            fs::read_to_string(copied).unwrap(),
+            "# Copyright 2024 New\n\nx = 2\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bottom_position_appends_after_a_blank_line_once() {
+        let dir = scratch();
+        let engine = engine(&["--position", "bottom"]);
+        let path = dir.join("a.py");
+        fs::write(&path, "x = 1").unwrap();
+        engine.apply_license(&path, STYLE_HASH, None).unwrap();
+        let licensed = "x = 1\n\n# Copyright 2024 New\n";
+        assert_eq!(fs::read_to_string(&path).unwrap(), licensed);
+        // already at the bottom: left alone
+        engine.apply_license(&path, STYLE_HASH, None).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), licensed);
+        assert_eq!(append_header("", "# A\n\n", Eol::Lf), "# A\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn inserted_lines_follow_a_pinned_eol() {
+        let dir = scratch();
+        fs::create_dir(dir.join(".git")).unwrap();
+        fs::write(dir.join(".gitattributes"), "*.py eol=crlf\n").unwrap();
+        let path = dir.join("a.py");
+        let apply = |extra: &[&str]| {
+            fs::write(&path, "x = 1\n").unwrap();
+            engine(extra)
+                .apply_license(&path, STYLE_HASH, None)
+                .unwrap();
+            fs::read_to_string(&path).unwrap()
+        };
+        assert_eq!(apply(&[]), "# Copyright 2024 New\r\n\r\nx = 1\n");
+        assert_eq!(
+            apply(&["--no-gitattributes"]),
+            "# Copyright 2024 New\n\nx = 1\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_small_queue_still_drains_every_file() {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        for i in 0..20 {
+            fs::write(dir.join(format!("src/f{i}.py")), "x = 1\n").unwrap();
+        }
+        for jobs in ["1", "3"] {
+            let args = [
+                "-f",
+                &dir.join("HEADER.txt").display().to_string(),
+                "--report-unlicensed",
+                "--queue-cap",
+                "1",
+                "-j",
+                jobs,
+                &dir.join("src").display().to_string(),
+            ]
+            .map(String::from);
+            let engine = LiceEngine::new(Config::from_args(args).unwrap()).unwrap();
+            assert_eq!(engine.run().unwrap().violations, 20, "-j {jobs}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn blank_lines_are_set_globally_and_per_extension() {
+        let dir = scratch();
+        let engine = engine(&[
+            "--blank-lines",
+            "2",
+            "--blank-lines",
+            "py=0",
+            "--fix-blank-lines",
+        ]);
+        let apply = |name: &str, content: &str| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            let ext = name.rsplit('.').next().unwrap();
+            engine
+                .apply_license(&path, engine.style_for_ext(ext).unwrap(), None)
+                .unwrap();
+            fs::read_to_string(&path).unwrap()
+        };
+        assert_eq!(apply("a.py", "x = 1\n"), "# Copyright 2024 New\nx = 1\n");
+        // under --fix-blank-lines a current header with the old count is reformatted
+        assert_eq!(
+            apply("a.rs", "// Copyright 2024 New\n\nfn main() {}\n"),
+            "// Copyright 2024 New\n\n\nfn main() {}\n"
+        );
+        assert_eq!(
+            apply("a.c", "int x;\n"),
+            "/*\n * Copyright 2024 New\n */\n\n\nint x;\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_verification_restores_the_original() {
+        let dir = scratch();
+        let engine = engine(&["--verify"]);
+        let header = engine.header_with_eol(STYLE_HASH, None, Eol::Lf);
+        let path = dir.join("a.py");
+
+        // a rewrite that lost the header on the way
+        fs::write(&path, "x = 2\n").unwrap();
+        assert!(
+            !engine
+                .verify_written(&path, "x = 1\n", &header, STYLE_HASH)
+                .unwrap()
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "x = 1\n");
+        assert_eq!(Stats::get(&engine.stats.violations), 1);
+
+        // a good one is left as written
+        let licensed = format!("{}x = 1\n", header);
+        fs::write(&path, &licensed).unwrap();
+        assert!(
+            engine
+                .verify_written(&path, "x = 1\n", &header, STYLE_HASH)
+                .unwrap()
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), licensed);
+        assert_eq!(Stats::get(&engine.stats.violations), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn header_from_reuses_a_sample_header_as_the_template() {
+        let dir = scratch();
+        let sample = dir.join("sample.rs");
+        fs::write(
+            &sample,
+            "// Copyright 2020 Sample\n// All rights reserved\n\nfn main() {}\n",
+        )
+        .unwrap();
+        let config = Config::from_args([
+            "--header-from".to_string(),
+            sample.display().to_string(),
+            dir.display().to_string(),
+        ])
+        .unwrap();
+        let engine = LiceEngine::new(config).unwrap();
+        assert_eq!(
+            engine.raw_license_text,
+            "Copyright 2020 Sample\nAll rights reserved\n"
+        );
+
+        // the header round-trips, so other styles get the same text
+        let path = dir.join("a.py");
+        fs::write(&path, "x = 1\n").unwrap();
+        engine.apply_license(&path, STYLE_HASH, None).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Copyright 2020 Sample\n# All rights reserved\n\nx = 1\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fail_fast_reports_the_first_error_and_cancels() {
+        let dir = scratch();
+        for name in ["a.py", "b.py"] {
+            fs::write(dir.join(name), b"x = \xff\n").unwrap();
+        }
+
+        let engine = self::engine(&[]);
+        engine.process_file(&dir.join("a.py"), false);
+        engine.process_file(&dir.join("b.py"), false);
+        assert!(!engine.is_cancelled());
+        assert_eq!(Stats::get(&engine.stats.violations), 0);
+
+        let engine = self::engine(&["--fail-fast"]);
+        engine.process_file(&dir.join("a.py"), false);
+        assert!(engine.is_cancelled());
+        engine.process_file(&dir.join("b.py"), false);
+        assert_eq!(Stats::get(&engine.stats.violations), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ruby_and_pod_blocks_count_as_the_old_header() {
+        let engine = engine(&[]);
+        assert_eq!(
+            licensed(
+                &engine,
+                "a.rb",
+                "=begin\nCopyright 2020 Old\n=end\n\nputs 1\n"
+            ),
+            "# Copyright 2024 New\n\nputs 1\n"
+        );
+        assert_eq!(
+            licensed(
+                &engine,
+                "a.pl",
+                "=pod\n\nCopyright 2020 Old\n\n=cut\n\nprint 1;\n"
+            ),
+            "# Copyright 2024 New\n\nprint 1;\n"
+        );
+        // never closed: code, kept below the new header
+        assert_eq!(
+            licensed(&engine, "a.rb", "=begin\nputs 1\n"),
+            "# Copyright 2024 New\n\n=begin\nputs 1\n"
+        );
+    }
+
+    #[test]
+    fn prepend_raw_puts_the_bare_text_on_top_once() {
+        let engine = engine(&["--prepend-raw", ".txt"]);
+        assert!(self::engine(&[]).style_for_ext("txt").is_none());
+        let once = licensed(&engine, "notes.txt", "notes\n");
+        assert_eq!(once, "Copyright 2024 New\n\nnotes\n");
+        assert_eq!(licensed(&engine, "notes.txt", &once), once);
+    }
+
+    #[test]
+    fn strict_unsupported_fails_only_listed_files() {
+        let dir = scratch();
+        let notes = dir.join("notes.xyz");
+        fs::write(&notes, "x\n").unwrap();
+
+        let engine = self::engine(&["--strict-unsupported"]);
+        engine.process_file(&notes, false);
+        assert_eq!(Stats::get(&engine.stats.violations), 0);
+        assert_eq!(Stats::get(&engine.stats.unsupported), 1);
+        engine.process_file(&notes, true);
+        assert_eq!(Stats::get(&engine.stats.violations), 1);
+
+        // without the flag a listed file is only skipped too
+        let engine = self::engine(&[]);
+        engine.process_file(&notes, true);
+        assert_eq!(Stats::get(&engine.stats.violations), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pure_apply_keeps_the_shebang_on_top() {
+        let style = LanguageProfile::for_extension("py").unwrap();
+        assert_eq!(
+            apply_license_to_content(
+                "#!/usr/bin/env python3\nprint(1)\n",
+                "Copyright 2024 A",
+                style
+            ),
+            "#!/usr/bin/env python3\n# Copyright 2024 A\n\nprint(1)\n"
+        );
+    }
+
+    #[test]
+    fn pure_apply_keeps_crlf_and_replaces_an_outdated_header_once() {
+        let style = LanguageProfile::for_extension("c").unwrap();
+        assert_eq!(
+            apply_license_to_content("int x;\r\n", "Copyright 2024 A", style),
+            "/*\r\n * Copyright 2024 A\r\n */\r\n\r\nint x;\r\n"
+        );
+
+        let style = LanguageProfile::for_extension("rs").unwrap();
+        let old = "// Copyright 2020 A\n\nfn main() {}\n";
+        let new = apply_license_to_content(old, "Copyright 2024 A", style);
+        assert_eq!(new, "// Copyright 2024 A\n\nfn main() {}\n");
+        assert_eq!(
+            apply_license_to_content(&new, "Copyright 2024 A", style),
+            new
+        );
+    }
+
+    #[test]
+    fn year_placeholder_is_filled_in() {
+        let year = current_year();
+        assert_eq!(
+            expand_template("Copyright {year} A\n(c) 2001-{year}\n", &[]),
+            format!("Copyright {year} A\n(c) 2001-{year}\n")
+        );
+        assert_eq!(expand_template("no placeholder", &[]), "no placeholder");
+    }
+
+    #[test]
+    fn unusable_license_file_gets_a_plain_message() {
+        let dir = scratch();
+        let start = |template: &PathBuf| {
+            let args = vec![
+                "-f".to_string(),
+                template.display().to_string(),
+                dir.display().to_string(),
+            ];
+            match LiceEngine::new(Config::from_args(args).unwrap()) {
+                Ok(_) => panic!("{} was accepted", template.display()),
+                Err(e) => e.to_string(),
+            }
+        };
+        assert_eq!(
+            start(&dir),
+            format!("license file is a directory: {}", dir.display())
+        );
+        let missing = dir.join("NOPE.txt");
+        assert_eq!(
+            start(&missing),
+            format!("license file not found: {}", missing.display())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn preserve_blank_run_keeps_the_gap_below_an_old_header() {
+        let old = "// Copyright 2020 Old\n\n\n\nfn main() {}\n";
+        assert_eq!(
+            licensed(&engine(&[]), "a.rs", old),
+            "// Copyright 2024 New\n\nfn main() {}\n"
+        );
+        let engine = engine(&["--preserve-blank-run"]);
+        let kept = licensed(&engine, "a.rs", old);
+        assert_eq!(kept, "// Copyright 2024 New\n\n\n\nfn main() {}\n");
+        // the kept gap doesn't make it stale next time
+        let header = engine.header_with_eol(STYLE_DOUBLE_SLASH, None, Eol::Lf);
+        assert!(engine.is_compliant(&kept, &header, STYLE_DOUBLE_SLASH));
+        // a block header's gap the same way
+        assert_eq!(
+            licensed(
+                &engine,
+                "a.c",
+                "/*\n * Copyright 2020 Old\n */\n\n\nint x;\n"
+            ),
+            "/*\n * Copyright 2024 New\n */\n\n\nint x;\n"
+        );
+    }
+
+    /// a scratch dir with the template in `HEADER.txt` and `files` under `src/`
+    fn tree(files: &[(&str, &str)]) -> PathBuf {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        for (name, content) in files {
+            let path = dir.join("src").join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    /// `-f HEADER.txt extra... src` for a `tree`
+    fn tree_args(dir: &Path, extra: &[&str]) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            dir.join("HEADER.txt").display().to_string(),
+        ];
+        args.extend(extra.iter().map(|arg| arg.to_string()));
+        args.push(dir.join("src").display().to_string());
+        args
+    }
+
+    /// the files a run over `tree` would look at, relative to `src/`, sorted
+    fn candidates_in(dir: &Path, extra: &[&str]) -> Vec<String> {
+        let engine = LiceEngine::new(Config::from_args(tree_args(dir, extra)).unwrap()).unwrap();
+        let src = dir.join("src");
+        let mut found: Vec<String> = engine
+            .candidates()
+            .map(|path| path.strip_prefix(&src).unwrap().display().to_string())
+            .collect();
+        found.sort();
+        found
+    }
+
+    /// run single-threaded over a `tree`
+    fn run_in(dir: &Path, extra: &[&str]) -> Report {
+        let args = tree_args(dir, &[&["-j", "1"], extra].concat());
+        LiceEngine::new(Config::from_args(args).unwrap())
+            .unwrap()
+            .run()
+            .unwrap()
+    }
+
+    /// make `path` unreadable; `false` if it still can be read (running as
+    /// root), and the caller should skip the test
+    #[cfg(unix)]
+    fn lock_out(path: &Path) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o000)).unwrap();
+        let readable = if path.is_dir() {
+            fs::read_dir(path).is_ok()
+        } else {
+            fs::read(path).is_ok()
+        };
+        if readable {
+            eprintln!("skipped: {} is readable anyway (root?)", path.display());
+        }
+        !readable
+    }
+
+    /// undo `lock_out`, so the scratch dir can be removed
+    #[cfg(unix)]
+    fn unlock(path: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// the files a run over `tree` gave an outcome, relative to `src/`, sorted
+    fn processed_in(dir: &Path, extra: &[&str]) -> Vec<String> {
+        let src = dir.join("src");
+        let mut found: Vec<String> = run_in(dir, extra)
+            .outcomes
+            .iter()
+            .map(|(path, _)| path.strip_prefix(&src).unwrap().display().to_string())
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn cache_trusts_an_unchanged_compliant_file() {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        let licensed = "# Copyright 2024 New\n\nx = 1\n";
+        let file = dir.join("src/a.py");
+        fs::write(&file, licensed).unwrap();
+        let cache = dir.join("cc").display().to_string();
+        run_in(&dir, &["--cache", &cache]);
+        let saved = fs::read_to_string(&cache).unwrap();
+        assert!(saved.ends_with("a.py\n"), "{saved}");
+
+        // same size and mtime: trusted without being read
+        let mtime = fs::metadata(&file).unwrap().modified().unwrap();
+        let stale = licensed.replace("New", "Old");
+        fs::write(&file, &stale).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        assert_eq!(run_in(&dir, &["--cache", &cache]).ok, 1);
+        assert_eq!(fs::read_to_string(&file).unwrap(), stale);
+        assert_eq!(run_in(&dir, &[]).updated, 1);
+        assert_eq!(fs::read_to_string(&file).unwrap(), licensed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compliance_settings_are_part_of_the_cache_fingerprint() {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("src/a.py"), "x = 1\n").unwrap();
+        let cache = dir.join("cc").display().to_string();
+        let fingerprint = |flags: &[&str]| {
+            let _ = fs::remove_file(&cache);
+            run_in(
+                &dir,
+                &[&["--count-only", "--cache", &cache], flags].concat(),
+            );
+            let saved = fs::read_to_string(&cache).unwrap();
+            saved.lines().nth(1).unwrap().to_string()
+        };
+        let plain = fingerprint(&[]);
+        assert_eq!(fingerprint(&[]), plain);
+        let rule = format!("**/vendor/** => {}", dir.join("HEADER.txt").display());
+        for flags in [
+            &["--position", "bottom"][..],
+            &["--preserve-blank-run"],
+            &["--no-gitattributes"],
+            &["--blank-lines", "2"],
+            &["--blank-lines", "c-like=0"],
+            &["--prefix", "## "],
+            &["--first-line-prefix", "#! "],
+            &["--style", "banner"],
+            &["--header-checksum"],
+            &["--ok-if-matches", "Copyright"],
+            &["--line-comment", "py=;;"],
+            &["--line-comment", "sql=//"],
+            &["--ext-style", "sql=c-like"],
+            &["--interpreter-style", "python=double-slash"],
+            &["--header-var", "team=Core"],
+            &["--keep-at-top", "#region"],
+            &["--keep-first", "@codegen"],
+            &["--keep-leading-comments"],
+            &["--license-keyword", "copyright"],
+            &["--force-style", "hash"],
+            &["--header-max-scan-lines", "3"],
+            &["--dedupe-headers"],
+            &["--fix-blank-lines"],
+            &["--no-guess"],
+            &["--rule", &rule],
+            &["--header-above-echo-off"],
+            &["--header-position", "after-package"],
+            &["--spdx-policy", "skip"],
+            &["--strip-trailing-whitespace"],
+        ] {
+            assert_ne!(fingerprint(flags), plain, "{flags:?}");
+        }
+        fs::write(dir.join("HEADER.txt"), "Copyright 2025 New\n").unwrap();
+        assert_ne!(fingerprint(&[]), plain);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn plain_json_is_never_touched() {
+        let engine = engine(&[]);
+        assert_eq!(
+            licensed(&engine, "tsconfig.jsonc", "{}\n"),
+            "// Copyright 2024 New\n\n{}\n"
+        );
+        let dir = scratch();
+        let path = dir.join("package.json");
+        fs::write(&path, "{}\n").unwrap();
+        engine.process_file(&path, false);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keep_leading_comments_spares_a_comment_without_a_keyword() {
+        let note = "// fast path for x86\nfn main() {}\n";
+        assert_eq!(
+            licensed(&engine(&[]), "a.rs", note),
+            "// Copyright 2024 New\n\nfn main() {}\n"
+        );
+        let engine = engine(&["--keep-leading-comments"]);
+        assert_eq!(
+            licensed(&engine, "a.rs", note),
+            "// Copyright 2024 New\n\n// fast path for x86\nfn main() {}\n"
+        );
+        // an old license is still replaced, line or block
+        assert_eq!(
+            licensed(&engine, "a.rs", "// Copyright 2020 Old\n\nfn main() {}\n"),
+            "// Copyright 2024 New\n\nfn main() {}\n"
+        );
+        assert_eq!(
+            licensed(&engine, "a.c", "/* generated, do not edit */\nint x;\n"),
+            "/*\n * Copyright 2024 New\n */\n\n/* generated, do not edit */\nint x;\n"
+        );
+
+        // own keywords replace the defaults
+        let engine = self::engine(&["--license-keyword", "Proprietary"]);
+        assert_eq!(
+            licensed(&engine, "a.py", "# Proprietary to Acme\n\nx = 1\n"),
+            "# Copyright 2024 New\n\nx = 1\n"
+        );
+        assert_eq!(
+            licensed(&engine, "a.py", "# Copyright 2020 Old\n\nx = 1\n"),
+            "# Copyright 2024 New\n\n# Copyright 2020 Old\n\nx = 1\n"
+        );
+    }
+
+    #[test]
+    fn extensionless_scripts_go_by_their_shebang() {
+        let dir = scratch();
+        let script = dir.join("configure");
+        fs::write(&script, "#!/usr/bin/env bash\necho hi\n").unwrap();
+        let data = dir.join("LICENSE");
+        fs::write(&data, "MIT\n").unwrap();
+
+        let engine = engine(&[]);
+        engine.process_file(&script, false);
+        engine.process_file(&data, false);
+        assert_eq!(
+            fs::read_to_string(&script).unwrap(),
+            "#!/usr/bin/env bash\n# Copyright 2024 New\n\necho hi\n"
+        );
+        assert_eq!(fs::read_to_string(&data).unwrap(), "MIT\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_reports_violations_and_writes_only_the_cache() {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        let files = [
+            ("a.py", "x = 1\n"),
+            ("b.py", "# Copyright 2020 Old\n\nx = 2\n"),
+            ("c.py", "# Copyright 2024 New\n\nx = 3\n"),
+        ];
+        for (name, content) in files {
+            fs::write(dir.join("src").join(name), content).unwrap();
+        }
+        let cache = dir.join("cc").display().to_string();
+
+        let report = run_in(&dir, &["--check", "--cache", &cache]);
+        assert_eq!((report.ok, report.violations), (1, 2));
+        for (name, content) in files {
+            assert_eq!(
+                fs::read_to_string(dir.join("src").join(name)).unwrap(),
+                content
+            );
+        }
+        let saved = fs::read_to_string(&cache).unwrap();
+        assert_eq!(saved.lines().count(), 3, "{saved}");
+        assert!(saved.ends_with("c.py\n"), "{saved}");
+
+        // `--fix` after `--check` wins, and then all is well
+        let report = run_in(&dir, &["--check", "--fix"]);
+        assert_eq!((report.added, report.updated, report.ok), (1, 1, 1));
+        let report = run_in(&dir, &["--check", "--cache", &cache]);
+        assert_eq!((report.ok, report.violations), (3, 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pre_commented_template_is_not_commented_twice() {
+        assert_eq!(
+            uncomment_template("// Copyright A\n//\n// MIT\n"),
+            "Copyright A\n\nMIT\n"
+        );
+        assert_eq!(uncomment_template("# Copyright A\n"), "Copyright A\n");
+        // not every line commented: taken as text
+        assert_eq!(
+            uncomment_template("// Copyright A\nMIT\n"),
+            "// Copyright A\nMIT\n"
+        );
+        let engine = engine_with("// Copyright 2024 New\n", &[]);
+        assert_eq!(
+            licensed(&engine, "a.py", "x = 1\n"),
+            "# Copyright 2024 New\n\nx = 1\n"
+        );
+    }
+
+    #[test]
+    fn report_lists_every_file_with_its_outcome() {
+        let dir = scratch();
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("src/a.py"), "x = 1\n").unwrap();
+        fs::write(dir.join("src/b.py"), "# Copyright 2020 Old\n\nx = 2\n").unwrap();
+        fs::write(dir.join("src/c.py"), "# Copyright 2024 New\n\nx = 3\n").unwrap();
+        fs::write(dir.join("src/d.py"), b"x = \xff\n").unwrap();
+        fs::write(dir.join("src/e.xyz"), "x\n").unwrap();
+
+        let report = run_in(&dir, &[]);
+        assert_eq!(
+            (report.total, report.added, report.updated, report.ok),
+            (5, 1, 1, 1)
+        );
+        assert_eq!(
+            (report.skipped, report.errors, report.violations),
+            (1, 1, 0)
+        );
+        let mut outcomes: Vec<(String, Outcome)> = report
+            .outcomes
+            .iter()
+            .map(|(path, outcome)| {
+                (
+                    path.file_name().unwrap().to_str().unwrap().to_string(),
+                    *outcome,
+                )
+            })
+            .collect();
+        outcomes.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            outcomes,
+            [
+                ("a.py".to_string(), Outcome::Added),
+                ("b.py".to_string(), Outcome::Updated),
+                ("c.py".to_string(), Outcome::Ok),
+                ("d.py".to_string(), Outcome::Failed),
+                ("e.xyz".to_string(), Outcome::Skipped),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exclude_after_a_path_only_applies_under_it() {
+        let dir = tree(&[("a/gen/x.rs", ""), ("a/y.rs", ""), ("b/gen/x.rs", "")]);
+        let src = dir.join("src");
+        let found = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            args.splice(0..0, tree_args(&dir, &[]).into_iter().take(2));
+            let engine = LiceEngine::new(Config::from_args(args).unwrap()).unwrap();
+            let mut found: Vec<String> = engine
+                .candidates()
+                .map(|path| path.strip_prefix(&src).unwrap().display().to_string())
+                .collect();
+            found.sort();
+            found
+        };
+        let (a, b) = (src.join("a"), src.join("b"));
+        let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+        assert_eq!(found(&[a, "-e", "gen", b]), ["a/y.rs", "b/gen/x.rs"]);
+        assert_eq!(found(&[a, b, "-e", "gen"]), ["a/gen/x.rs", "a/y.rs"]);
+        // given before every path, it still applies everywhere
+        assert_eq!(found(&["-e", "gen", a, b]), ["a/y.rs"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exclude_from_reads_wildcard_patterns_from_a_file() {
+        let dir = tree(&[
+            ("main.c", ""),
+            ("parser_gen.c", ""),
+            ("out1/x.c", ""),
+            ("keep/outfile.c", ""),
+            ("vendor/lib.c", ""),
+        ]);
+        let list = dir.join("excludes.txt");
+        fs::write(&list, "# shared list\n\n  vendor  \n*_gen.c\n").unwrap();
+        assert_eq!(
+            candidates_in(
+                &dir,
+                &["--exclude-from", list.to_str().unwrap(), "-e", "out*/"]
+            ),
+            ["keep/outfile.c", "main.c"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exclude_ext_composes_with_path_excludes() {
+        let dir = tree(&[
+            ("app.js", ""),
+            ("app.min.js", ""),
+            ("min.js", ""),
+            ("vendor/lib.js", ""),
+            ("gen/api.generated.go", ""),
+            ("gen/api.go", ""),
+        ]);
+        assert_eq!(
+            candidates_in(
+                &dir,
+                &["--exclude-ext", "min.js", "--exclude-ext", ".generated.go"]
+            ),
+            ["app.js", "gen/api.go", "min.js", "vendor/lib.js"]
+        );
+        assert_eq!(
+            candidates_in(
+                &dir,
+                &["--exclude-ext", "min.js", "-e", "vendor", "-e", "gen"]
+            ),
+            ["app.js", "min.js"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn leading_whitespace_of_the_body_survives() {
+        let engine = engine(&[]);
+        assert_eq!(
+            licensed(&engine, "a.yaml", "  naïve: ✓\n  b: 2\n"),
+            "# Copyright 2024 New\n\n  naïve: ✓\n  b: 2\n"
+        );
+        assert_eq!(
+            licensed(
+                &engine,
+                "a.c",
+                "/*\n * Copyright 2020 Old\n */\n\n    int x;\n"
+            ),
+            "/*\n * Copyright 2024 New\n */\n\n    int x;\n"
+        );
+        assert_eq!(
+            licensed(&engine, "a.py", "# Copyright 2020 Old\n\n\tx = 1\n"),
+            "# Copyright 2024 New\n\n\tx = 1\n"
+        );
+    }
+
+    #[test]
+    fn force_style_applies_one_style_where_the_language_takes_it() {
+        let dir = tree(&[
+            ("a.rs", "fn main() {}\n"),
+            ("b.py", "x = 1\n"),
+            ("c", "#!/usr/bin/env node\nrun();\n"),
+        ]);
+        let report = run_in(&dir, &["--force-style", "c-like"]);
+        assert_eq!((report.added, report.skipped), (2, 1));
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.rs")).unwrap(),
+            "/*\n * Copyright 2024 New\n */\n\nfn main() {}\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("src/b.py")).unwrap(), "x = 1\n");
+        assert!(
+            fs::read_to_string(dir.join("src/c"))
+                .unwrap()
+                .starts_with("#!/usr/bin/env node\n/*\n")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dedupe_headers_collapses_stacked_blocks() {
+        let stacked = "/*\n * Copyright 2024 New\n */\n\n/*\n * Copyright 2019 Old\n */\n\n\
+            /*\n * Copyright 2018 Older\n */\n\nint x;\n";
+        let dir = tree(&[
+            ("a.c", stacked),
+            (
+                "b.rs",
+                "// Copyright 2024 New\n\n// Copyright 2019 Old\n\nfn main() {}\n",
+            ),
+        ]);
+        // left as it is, with a warning
+        let report = run_in(&dir, &[]);
+        assert_eq!(report.ok, 2);
+        assert_eq!(fs::read_to_string(dir.join("src/a.c")).unwrap(), stacked);
+        let report = run_in(&dir, &["--dedupe-headers"]);
+        assert_eq!(report.updated, 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.c")).unwrap(),
+            "/*\n * Copyright 2024 New\n */\n\nint x;\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("src/b.rs")).unwrap(),
+            "// Copyright 2024 New\n\nfn main() {}\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn final_newline_policy_is_the_same_for_block_and_line_styles() {
+        for (name, head) in [
+            ("a.c", "/*\n * Copyright 2024 New\n */\n\n"),
+            ("a.py", "# Copyright 2024 New\n\n"),
+        ] {
+            for (policy, tails) in [
+                ("preserve", ["x", "x\n", "x\n\n"]),
+                ("ensure", ["x\n", "x\n", "x\n\n"]),
+                ("strip", ["x", "x", "x"]),
+            ] {
+                let engine = engine(&["--final-newline", policy]);
+                for (content, tail) in ["x", "x\n", "x\n\n"].into_iter().zip(tails) {
+                    assert_eq!(
+                        licensed(&engine, name, content),
+                        format!("{head}{tail}"),
+                        "{name} {policy} {content:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dry_run_fails_when_something_would_change_and_writes_nothing() {
+        let files = [
+            ("a.py", "x = 1\n"),
+            ("b.py", "# Copyright 2024 New\n\nx = 2\n"),
+        ];
+        let dir = tree(&files);
+        let cache = dir.join("cc").display().to_string();
+        let out = dir.join("out").display().to_string();
+
+        let report = run_in(&dir, &["--dry-run", "--cache", &cache, "--out-dir", &out]);
+        assert_eq!((report.added, report.ok, report.violations), (1, 1, 1));
+        for (name, content) in files {
+            assert_eq!(
+                fs::read_to_string(dir.join("src").join(name)).unwrap(),
+                content
+            );
+        }
+        assert!(!dir.join("out").exists());
+        assert!(fs::read_to_string(&cache).unwrap().ends_with("b.py\n"));
+
+        run_in(&dir, &[]);
+        assert_eq!(run_in(&dir, &["--dry-run"]).violations, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn project_root_templates_win_for_their_extension() {
+        let dir = tree(&[("a.py", "x = 1\n"), ("b.rs", "fn main() {}\n")]);
+        fs::create_dir(dir.join(".lice")).unwrap();
+        fs::write(dir.join(".lice/HEADER.py"), "Python 2024 New\n").unwrap();
+        let root = dir.display().to_string();
+        let cache = dir.join("cc").display().to_string();
+        let args = ["--project-root", &root, "--cache", &cache];
+
+        assert_eq!(run_in(&dir, &args).added, 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.py")).unwrap(),
+            "# Python 2024 New\n\nx = 1\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("src/b.rs")).unwrap(),
+            "// Copyright 2024 New\n\nfn main() {}\n"
+        );
+        // an edited template is not hidden by the cache
+        fs::write(dir.join(".lice/HEADER.py"), "Python 2025 New\n").unwrap();
+        let report = run_in(&dir, &args);
+        assert_eq!((report.updated, report.ok), (1, 1));
+        assert!(
+            fs::read_to_string(dir.join("src/a.py"))
+                .unwrap()
+                .starts_with("# Python 2025 New\n")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn first_line_prefix_opens_the_header_and_is_recognized_again() {
+        let doxygen = engine_with(
+            "Copyright 2024 New\nAll rights reserved\n",
+            &["--first-line-prefix", " ** "],
+        );
+        let javadoc = "/*\n ** Copyright 2024 New\n * All rights reserved\n */\n\nint x;\n";
+        assert_eq!(licensed(&doxygen, "a.c", "int x;\n"), javadoc);
+        assert_eq!(licensed(&doxygen, "a.c", javadoc), javadoc);
+        // an old header in the plain style is replaced, not stacked upon
+        assert_eq!(
+            licensed(
+                &doxygen,
+                "a.c",
+                "/*\n * Copyright 2020 Old\n */\n\nint x;\n"
+            ),
+            javadoc
+        );
+
+        let engine = engine(&["--first-line-prefix", "//! "]);
+        let doc = "//! Copyright 2024 New\n\nfn main() {}\n";
+        assert_eq!(
+            licensed(&engine, "a.rs", "// Copyright 2020 Old\n\nfn main() {}\n"),
+            doc
+        );
+        assert_eq!(licensed(&engine, "a.rs", doc), doc);
+    }
+
+    #[test]
+    fn shebang_only_file_gets_the_header_below_it() {
+        let engine = engine(&[]);
+        for content in ["#!/bin/sh\n", "#!/bin/sh"] {
+            let once = licensed(&engine, "a.sh", content);
+            assert_eq!(once, "#!/bin/sh\n# Copyright 2024 New\n");
+            // and left alone from then on
+            assert_eq!(licensed(&engine, "a.sh", &once), once);
+        }
+        assert_eq!(
+            licensed(&engine, "a.c", ""),
+            "/*\n * Copyright 2024 New\n */\n"
+        );
+    }
+
+    #[test]
+    fn match_gates_files_after_the_excludes() {
+        let files = [
+            ("api/users_gen.rs", "fn a() {}\n"),
+            ("api/users.rs", "fn b() {}\n"),
+            ("old/api/users_gen.rs", "fn c() {}\n"),
+            ("web/page_gen.rs", "fn d() {}\n"),
+        ];
+        let dir = tree(&files);
+        let report = run_in(&dir, &["--match", r"/api/.*_gen\.rs$", "-e", "old"]);
+        assert_eq!(report.added, 1);
+        let licensed = |name: &str| {
+            fs::read_to_string(dir.join("src").join(name))
+                .unwrap()
+                .starts_with("// Copyright")
+        };
+        assert!(licensed("api/users_gen.rs"));
+        assert!(!licensed("api/users.rs"));
+        assert!(!licensed("old/api/users_gen.rs"));
+        assert!(!licensed("web/page_gen.rs"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn block_header_under_a_shebang_is_replaced() {
+        let old = "#!/usr/bin/tcc -run\n/*\n * Copyright 2020 Old\n */\n\nint main() {}\n";
+        let new = "#!/usr/bin/tcc -run\n/*\n * Copyright 2024 New\n */\n\nint main() {}\n";
+        let dir = tree(&[("a.c", old)]);
+        assert_eq!(run_in(&dir, &["--check"]).violations, 1);
+        assert_eq!(run_in(&dir, &[]).updated, 1);
+        assert_eq!(fs::read_to_string(dir.join("src/a.c")).unwrap(), new);
+        assert_eq!(run_in(&dir, &["--check"]).violations, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn enforce_fails_on_missing_headers_and_unknown_types_alike() {
+        let files = [
+            ("a.py", "x = 1\n"),
+            ("b.py", "# Copyright 2024 New\n\nx = 2\n"),
+            ("c.xyz", "?\n"),
+            ("README", "words\n"),
+        ];
+        let dir = tree(&files);
+        let report = run_in(&dir, &["--enforce"]);
+        assert_eq!((report.added, report.ok, report.violations), (1, 1, 2));
+        for (name, content) in files {
+            assert_eq!(
+                fs::read_to_string(dir.join("src").join(name)).unwrap(),
+                content
+            );
+        }
+        // a known type with a header passes, whatever else is excluded
+        let report = run_in(&dir, &["--enforce", "-e", "a.py", "-e", "c.xyz"]);
+        assert_eq!(report.violations, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fetch_errors_name_the_url() {
+        // nothing listens on port 1, and without the feature nothing is fetched
+        let url = "http://127.0.0.1:1/HEADER.txt";
+        let message = LiceEngine::fetch_license(url).unwrap_err().to_string();
+        assert!(message.contains(url), "{message}");
+    }
+
+    #[test]
+    fn line_comment_defines_a_style_and_wins_over_the_builtin() {
+        let engine = engine(&["--line-comment", "zig=//", "--line-comment", ".sql=#"]);
+        assert_eq!(
+            licensed(&engine, "a.zig", "const x = 1;\n"),
+            "// Copyright 2024 New\n\nconst x = 1;\n"
+        );
+        let hashed = "# Copyright 2024 New\n\nSELECT 1;\n";
+        assert_eq!(licensed(&engine, "a.sql", "SELECT 1;\n"), hashed);
+        assert_eq!(licensed(&engine, "a.sql", hashed), hashed);
+        for bad in ["zig", "=//", "zig="] {
+            let args = ["-f", "H", "--line-comment", bad, "src"].map(String::from);
+            assert!(Config::from_args(args).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn keep_at_top_lines_stay_above_the_header() {
+        let plain = engine(&[]);
+        let pragma = engine(&["--keep-at-top", "#pragma"]);
+        assert_eq!(
+            licensed(&plain, "a.h", "#pragma once\nint x;\n"),
+            "/*\n * Copyright 2024 New\n */\n\n#pragma once\nint x;\n"
+        );
+        assert_eq!(
+            licensed(&pragma, "a.h", "#pragma once\nint x;\n"),
+            "#pragma once\n/*\n * Copyright 2024 New\n */\n\nint x;\n"
+        );
+        // a `#region` marker in a hash-style file isn't taken for the old header
+        let region = engine(&["--keep-at-top", "#region"]);
+        let new = "#region License\n# Copyright 2024 New\n\nx = 1\n";
+        assert_eq!(
+            licensed(
+                &region,
+                "a.py",
+                "#region License\n# Copyright 2020 Old\n\nx = 1\n"
+            ),
+            new
+        );
+        assert_eq!(licensed(&region, "a.py", new), new);
+    }
+
+    #[test]
+    fn generator_markers_stay_first() {
+        let plain = engine(&[]);
+        let header = "// Copyright 2024 New\n\n";
+        // replaced as an old header before, now kept above the new one
+        assert_eq!(
+            licensed(&plain, "a.rs", "// @generated by build.rs\nfn main() {}\n"),
+            format!("// @generated by build.rs\n{header}fn main() {{}}\n")
+        );
+        let eslint = "/* eslint-disable */\n// Copyright 2020 Old\n\nlet x;\n";
+        let new = format!("/* eslint-disable */\n{header}let x;\n");
+        assert_eq!(licensed(&plain, "a.js", eslint), new);
+        assert_eq!(licensed(&plain, "a.js", &new), new);
+        // the marker has to be a comment of the file's own style
+        assert!(licensed(&plain, "a.py", "@generated = 1\n").starts_with("# Copyright"));
+        let codegen = engine(&["--keep-first", "@codegen"]);
+        assert_eq!(
+            licensed(&codegen, "a.py", "# @codegen\nx = 1\n"),
+            "# @codegen\n# Copyright 2024 New\n\nx = 1\n"
+        );
+    }
+
+    #[test]
+    fn header_detection_stops_at_the_scan_limit() {
+        let short = engine(&["--header-max-scan-lines", "3"]);
+        let long = engine(&["--header-max-scan-lines", "4"]);
+        let old = "# Copyright 2020 Old\n# one\n# two\n# three\n\nx = 1\n";
+        // running on past the limit it is no header and stays
+        assert_eq!(
+            licensed(&short, "a.py", old),
+            format!("# Copyright 2024 New\n\n{old}")
+        );
+        assert_eq!(
+            licensed(&long, "a.py", old),
+            "# Copyright 2024 New\n\nx = 1\n"
+        );
+        let block = "/*\n * Copyright 2020 Old\n * more\n */\nint x;\n";
+        // a block closing past it looks unclosed: skipped, not guessed at
+        assert_eq!(licensed(&short, "a.c", block), block);
+        assert_eq!(
+            licensed(&long, "a.c", block),
+            "/*\n * Copyright 2024 New\n */\n\nint x;\n"
+        );
+    }
+
+    #[test]
+    fn banner_style_boxes_the_text_and_is_recognised_again() {
+        let banner = engine_with(
+            "Copyright 2024 New\nAll rights reserved\n",
+            &["--style", "banner"],
+        );
+        let boxed = "# =======================\n\
+                     # | Copyright 2024 New  |\n\
+                     # | All rights reserved |\n\
+                     # =======================\n\nx = 1\n";
+        assert_eq!(
+            licensed(&banner, "a.py", "# Copyright 2020 Old\n\nx = 1\n"),
+            boxed
+        );
+        assert_eq!(licensed(&banner, "a.py", boxed), boxed);
+        let c = licensed(&banner, "a.c", "int x;\n");
+        assert!(c.starts_with("/*\n * =======================\n * | Copyright 2024 New  |\n"));
+        assert_eq!(licensed(&banner, "a.c", &c), c);
+    }
+
+    #[test]
+    fn byte_counts_read_in_binary_units() {
+        assert_eq!(human_bytes(0), "0 B");
+        assert_eq!(human_bytes(1023), "1023 B");
+        assert_eq!(human_bytes(1536), "1.5 KB");
+        assert_eq!(human_bytes(12 * 1024 * 1024), "12.0 MB");
+    }
+
+    #[test]
+    fn dep5_names_the_spdx_header_per_path() {
+        let dir = tree(&[("a.py", "x = 1\n"), ("gen/b.py", "y = 2\n")]);
+        fs::create_dir(dir.join(".reuse")).unwrap();
+        fs::write(
+            dir.join(".reuse/dep5"),
+            "Files: src/*\nCopyright: 2024 Jane\nLicense: MIT\n\n\
+             Files: src/gen/*\nX-Lice-Header: no\n",
+        )
+        .unwrap();
+        let dep5 = dir.join(".reuse/dep5").display().to_string();
+        let report = run_in(&dir, &["--dep5", &dep5]);
+        assert_eq!((report.added, report.skipped), (1, 1));
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.py")).unwrap(),
+            "# SPDX-FileCopyrightText: 2024 Jane\n# \n\
+             # SPDX-License-Identifier: MIT\n\nx = 1\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("src/gen/b.py")).unwrap(),
+            "y = 2\n"
+        );
+        assert_eq!(run_in(&dir, &["--dep5", &dep5, "--check"]).violations, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn no_guess_leaves_ambiguous_files_alone_and_fails() {
+        let ambiguous = [
+            ("a.c", "/*\n * Copyright 2020 Old\nint x;\n"),
+            ("b.py", "# fast path for x86\n\nx = 1\n"),
+            ("c.rs", "// Copyright 2020 Old\nfn main() {}\n"),
+        ];
+        let clear = [
+            ("d.py", "# Copyright 2020 Old\n\nx = 1\n"),
+            ("e.py", "x = 1\n"),
+        ];
+        let dir = tree(&[&ambiguous[..], &clear[..]].concat());
+        let report = run_in(&dir, &["--no-guess"]);
+        assert_eq!((report.errors, report.updated, report.added), (3, 1, 1));
+        assert_eq!(report.violations, 3);
+        for (name, content) in ambiguous {
+            assert_eq!(
+                fs::read_to_string(dir.join("src").join(name)).unwrap(),
+                content
+            );
+        }
+        // keeping the comment is no guess
+        let report = run_in(
+            &dir,
+            &[
+                "--no-guess",
+                "--keep-leading-comments",
+                "-e",
+                "a.c",
+                "-e",
+                "c.rs",
+            ],
+        );
+        assert_eq!(report.errors, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_ext_restricts_and_excludes_still_win() {
+        let dir = tree(&[
+            ("a.rs", ""),
+            ("b.go", ""),
+            ("c.py", ""),
+            ("vendor/d.rs", ""),
+        ]);
+        assert_eq!(
+            processed_in(
+                &dir,
+                &["--dry-run", "--only-ext", "rs", "--only-ext", ".go"]
+            ),
+            ["a.rs", "b.go", "vendor/d.rs"]
+        );
+        assert_eq!(
+            processed_in(&dir, &["--dry-run", "--only-ext", "RS", "-e", "vendor"]),
+            ["a.rs"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn extra_blank_lines_after_a_current_header_are_fine() {
+        let files = [
+            ("a.rs", "// Copyright 2024 New\n\n\n\nfn main() {}\n"),
+            ("b.c", "/*\n * Copyright 2024 New\n */\n\n\n\nint x;\n"),
+        ];
+        let dir = tree(&files);
+        assert_eq!(run_in(&dir, &["--check"]).violations, 0);
+        assert_eq!(run_in(&dir, &[]).ok, 2);
+        for (name, content) in files {
+            assert_eq!(
+                fs::read_to_string(dir.join("src").join(name)).unwrap(),
+                content
+            );
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        // but the one blank line the style asks for must be there
+        let dir = tree(&[("a.rs", "// Copyright 2024 New\nfn main() {}\n")]);
+        assert_eq!(run_in(&dir, &["--check"]).violations, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fix_blank_lines_rewrites_a_current_header_to_the_configured_count() {
+        let dir = tree(&[("a.rs", "// Copyright 2024 New\n\n\n\nfn main() {}\n")]);
+        assert_eq!(
+            run_in(&dir, &["--check", "--fix-blank-lines"]).violations,
+            1
+        );
+        run_in(&dir, &["--fix-blank-lines"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.rs")).unwrap(),
+            "// Copyright 2024 New\n\nfn main() {}\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn markdown_front_matter_stays_first() {
+        let engine = engine(&[]);
+        assert_eq!(
+            licensed(&engine, "a.md", "---\ntitle: x\n---\n# Doc\n"),
+            "---\ntitle: x\n---\n<!--\n  Copyright 2024 New\n-->\n\n# Doc\n"
+        );
+        // with an old header below it, and `...` closing the block
+        assert_eq!(
+            licensed(
+                &engine,
+                "a.md",
+                "---\ntitle: x\n...\n<!--\n  Copyright 2020 Old\n-->\n\n# Doc\n"
+            ),
+            "---\ntitle: x\n...\n<!--\n  Copyright 2024 New\n-->\n\n# Doc\n"
+        );
+        // a `---` rule that is never closed is no front matter
+        assert_eq!(
+            licensed(&engine, "a.md", "---\n# Doc\n"),
+            "<!--\n  Copyright 2024 New\n-->\n\n---\n# Doc\n"
+        );
+    }
+
+    #[test]
+    fn header_only_file_gets_just_the_new_header() {
+        let engine = engine(&[]);
+        assert_eq!(
+            licensed(&engine, "a.c", "/*\n * Copyright 2020 Old\n */\n"),
+            "/*\n * Copyright 2024 New\n */\n"
+        );
+        // blank lines after the old one don't survive either
+        assert_eq!(
+            licensed(&engine, "a.py", "# Copyright 2020 Old\n\n\n"),
+            "# Copyright 2024 New\n"
+        );
+        assert_eq!(
+            licensed(&engine, "a.rs", "// Copyright 2020 Old"),
+            "// Copyright 2024 New\n"
+        );
+    }
+
+    #[test]
+    fn header_only_file_is_emptied_by_remove() {
+        let dir = tree(&[
+            ("a.c", "/*\n * Copyright 2020 Old\n */\n\n"),
+            ("a.py", "# Copyright 2020 Old\n"),
+            ("a.sh", "#!/bin/sh\n# Copyright 2020 Old\n\n"),
+        ]);
+        assert_eq!(run_in(&dir, &["--remove"]).removed, 3);
+        let read = |name: &str| fs::read_to_string(dir.join("src").join(name)).unwrap();
+        assert_eq!(read("a.c"), "");
+        assert_eq!(read("a.py"), "");
+        assert_eq!(read("a.sh"), "#!/bin/sh\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_keeps_the_body_and_other_comments() {
+        let dir = tree(&[
+            (
+                "a.c",
+                "/*\r\n * Copyright 2020 Old\r\n */\r\n\r\nint x;\r\n",
+            ),
+            ("a.rs", "// just a note\nfn x() {}\n"),
+        ]);
+        let report = run_in(&dir, &["--remove"]);
+        assert_eq!((report.removed, report.ok), (1, 1));
+        let read = |name: &str| fs::read_to_string(dir.join("src").join(name)).unwrap();
+        assert_eq!(read("a.c"), "int x;\r\n");
+        assert_eq!(read("a.rs"), "// just a note\nfn x() {}\n");
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = tree(&[("a.py", "x = 1\n\n# Copyright 2020 Old\n")]);
+        run_in(&dir, &["--remove", "--position", "bottom"]);
+        assert_eq!(fs::read_to_string(dir.join("src/a.py")).unwrap(), "x = 1\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn first_matching_rule_picks_the_template() {
+        let dir = tree(&[
+            ("third_party/a.py", "x = 1\n"),
+            ("third_party/gen/b.py", "x = 1\n"),
+            ("c.py", "x = 1\n"),
+        ]);
+        fs::write(dir.join("VENDOR.txt"), "Copyright Vendor\n").unwrap();
+        fs::write(dir.join("GEN.txt"), "Generated\n").unwrap();
+        let rule = |glob: &str, file: &str| format!("{} => {}", glob, dir.join(file).display());
+        run_in(
+            &dir,
+            &[
+                "--rule",
+                &rule("**/third_party/gen/**", "GEN.txt"),
+                "--rule",
+                &rule("**/third_party/**", "VENDOR.txt"),
+            ],
+        );
+        let read = |name: &str| fs::read_to_string(dir.join("src").join(name)).unwrap();
+        assert_eq!(read("third_party/a.py"), "# Copyright Vendor\n\nx = 1\n");
+        assert_eq!(read("third_party/gen/b.py"), "# Generated\n\nx = 1\n");
+        assert_eq!(read("c.py"), "# Copyright 2024 New\n\nx = 1\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_directory_is_warned_about_and_the_walk_goes_on() {
+        let dir = tree(&[
+            ("a.py", "x = 1\n"),
+            ("locked/b.py", "y = 2\n"),
+            ("z/c.py", ""),
+        ]);
+        let locked = dir.join("src/locked");
+        if lock_out(&locked) {
+            let report = run_in(&dir, &[]);
+            let strict = run_in(&dir, &["--check", "--strict"]);
+            unlock(&locked);
+            assert_eq!((report.added, report.violations), (2, 0));
+            assert_eq!(fs::read(locked.join("b.py")).unwrap(), b"y = 2\n");
+            // the licensed files pass, the directory fails the run
+            assert_eq!((strict.ok, strict.violations), (2, 1));
+        }
+        unlock(&locked);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn normalize_reformats_only_headers_with_the_right_text() {
+        let dir = tree(&[
+            (
+                "messy.rs",
+                "//Copyright 2024 New   \n\n\n\nfn main() {}  \n",
+            ),
+            ("tidy.rs", "// Copyright 2024 New\n\nfn main() {}\n"),
+            ("old.rs", "//Copyright 2020 Old\nfn main() {}\n"),
+            ("bare.rs", "fn main() {}\n"),
+        ]);
+        let report = run_in(&dir, &["--normalize"]);
+        assert_eq!((report.normalized, report.ok, report.skipped), (1, 1, 2));
+        let read = |name: &str| fs::read_to_string(dir.join("src").join(name)).unwrap();
+        // the body keeps its own trailing whitespace
+        assert_eq!(
+            read("messy.rs"),
+            "// Copyright 2024 New\n\nfn main() {}  \n"
+        );
+        assert_eq!(read("old.rs"), "//Copyright 2020 Old\nfn main() {}\n");
+        assert_eq!(read("bare.rs"), "fn main() {}\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch_header_goes_below_echo_off_unless_asked() {
+        let engine = engine(&[]);
+        assert_eq!(
+            licensed(&engine, "a.bat", "@echo off\r\necho hi\r\n"),
+            "@echo off\r\nREM Copyright 2024 New\r\n\r\necho hi\r\n"
+        );
+        assert_eq!(
+            licensed(&engine, "a.bat", "echo hi\n"),
+            "REM Copyright 2024 New\n\necho hi\n"
+        );
+        assert_eq!(
+            licensed(&engine, "a.bat", "REM Copyright 2020 Old\n\necho hi\n"),
+            "REM Copyright 2024 New\n\necho hi\n"
+        );
+        let above = self::engine(&["--header-above-echo-off"]);
+        assert_eq!(
+            licensed(&above, "a.cmd", "@ECHO OFF\necho hi\n"),
+            "REM Copyright 2024 New\n\n@ECHO OFF\necho hi\n"
+        );
+    }
+
+    #[test]
+    fn license_id_brings_its_own_notice() {
+        let dir = tree(&[("a.py", "x = 1\n")]);
+        let config = Config::from_args([
+            "--license-id".to_string(),
+            "mit".to_string(),
+            "--author".to_string(),
+            "Acme".to_string(),
+            dir.join("src").display().to_string(),
+        ])
+        .unwrap();
+        LiceEngine::new(config).unwrap().run().unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.py")).unwrap(),
+            format!(
+                "# Copyright (c) {} Acme\n# \n# SPDX-License-Identifier: MIT\n\nx = 1\n",
+                current_year()
+            )
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn trailing_slash_excludes_directories_only() {
+        let dir = tree(&[
+            ("build", "#!/bin/sh\n"),
+            ("build.d/a.sh", ""),
+            ("out/build/b.sh", ""),
+        ]);
+        assert_eq!(candidates_in(&dir, &["-e", "build"]), ["build.d/a.sh"]);
+        assert_eq!(
+            candidates_in(&dir, &["-e", "build/"]),
+            ["build", "build.d/a.sh"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn post_cmd_runs_on_rewritten_files_only() {
+        let dir = tree(&[
+            ("a.py", "x = 1\n"),
+            ("b.py", "# Copyright 2024 New\n\nx = 2\n"),
+        ]);
+        let log = dir.join("log");
+        let hook = format!("echo {{}} >> '{}'", log.display());
+        let report = run_in(&dir, &["--post-cmd", &hook]);
+        assert_eq!(report.violations, 0);
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            format!("{}\n", dir.join("src/a.py").display())
+        );
+
+        // a failing hook fails the run, the file keeps its header
+        fs::write(dir.join("src/c.py"), "x = 3\n").unwrap();
+        let report = run_in(&dir, &["--post-cmd", "false"]);
+        assert_eq!((report.added, report.violations), (1, 1));
+        assert!(
+            fs::read_to_string(dir.join("src/c.py"))
+                .unwrap()
+                .starts_with("# Copyright")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn patch_collects_every_change_relative_to_the_root() {
+        let dir = tree(&[
+            ("sub/b.py", "y = 2\n"),
+            ("a.py", "# Copyright 2020 Old\n\nx = 1\n"),
+            ("ok.py", "# Copyright 2024 New\n\nz = 3\n"),
+        ]);
+        let patch = dir.join("out.diff");
+        let src = dir.join("src").display().to_string();
+        let report = run_in(
+            &dir,
+            &[
+                "--dry-run",
+                "--patch",
+                &patch.display().to_string(),
+                "--patch-root",
+                &src,
+            ],
+        );
+        assert_eq!(report.violations, 2);
+        assert_eq!(
+            fs::read_to_string(&patch).unwrap(),
+            "--- a/a.py\n+++ b/a.py\n@@ -1,3 +1,3 @@\n\
+             -# Copyright 2020 Old\n+# Copyright 2024 New\n \n x = 1\n\
+             --- a/sub/b.py\n+++ b/sub/b.py\n@@ -1 +1,3 @@\n\
+             +# Copyright 2024 New\n+\n y = 2\n"
+        );
+        // nothing in place
+        assert_eq!(
+            fs::read_to_string(dir.join("src/sub/b.py")).unwrap(),
+            "y = 2\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn block_end_is_the_one_closing_the_header() {
+        let engine = engine(&[]);
+        let old = "/*\n * Copyright 2020 Old, see src/*.c\n */\n\nconst char *end = \"*/\";\n";
+        assert_eq!(
+            licensed(&engine, "a.c", old),
+            "/*\n * Copyright 2024 New\n */\n\nconst char *end = \"*/\";\n"
+        );
+        // however the terminator is spaced, code after it on its line stays
+        assert_eq!(
+            licensed(
+                &engine,
+                "a.c",
+                "/*\n * Copyright 2020 Old\n*/ int x;\nint y;\n"
+            ),
+            "/*\n * Copyright 2024 New\n */\n\n int x;\nint y;\n"
+        );
+    }
+
+    #[test]
+    fn parallel_walk_finds_every_file_once() {
+        let names: Vec<String> = (0..20)
+            .flat_map(|d| (0..3).map(move |f| format!("d{d}/deep/f{f}.py")))
+            .chain(["skip/a.py".to_string(), "top.py".to_string()])
+            .collect();
+        let files: Vec<(&str, &str)> = names.iter().map(|n| (n.as_str(), "x = 1\n")).collect();
+        let dir = tree(&files);
+        let single = candidates_in(&dir, &["-e", "skip"]);
+        assert_eq!(single.len(), 61);
+
+        let args = tree_args(&dir, &["-j", "4", "--parallel-walk", "4", "-e", "skip"]);
+        let report = LiceEngine::new(Config::from_args(args).unwrap())
+            .unwrap()
+            .run()
+            .unwrap();
+        let src = dir.join("src");
+        let mut found: Vec<String> = report
+            .outcomes
+            .iter()
+            .map(|(path, _)| path.strip_prefix(&src).unwrap().display().to_string())
+            .collect();
+        found.sort();
+        assert_eq!(found, single);
+        assert_eq!(report.added, 61);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn spdx_policy_skip_leaves_files_under_another_license() {
+        let files = [
+            ("mit.py", "# SPDX-License-Identifier: MIT\n\nx = 1\n"),
+            (
+                "same.py",
+                "# SPDX-License-Identifier: apache-2.0\n\nx = 1\n",
+            ),
+            ("bare.py", "x = 1\n"),
+        ];
+        let dir = tree(&files);
+        fs::write(
+            dir.join("HEADER.txt"),
+            "SPDX-License-Identifier: Apache-2.0\n",
+        )
+        .unwrap();
+        let report = run_in(&dir, &["--spdx-policy", "skip"]);
+        assert_eq!((report.skipped, report.updated, report.added), (1, 1, 1));
+        let read = |name: &str| fs::read_to_string(dir.join("src").join(name)).unwrap();
+        assert_eq!(read("mit.py"), files[0].1);
+        assert_eq!(
+            read("same.py"),
+            "# SPDX-License-Identifier: Apache-2.0\n\nx = 1\n"
+        );
+
+        // the default overwrites it
+        run_in(&dir, &[]);
+        assert_eq!(
+            read("mit.py"),
+            "# SPDX-License-Identifier: Apache-2.0\n\nx = 1\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn strip_trailing_whitespace_cleans_only_the_header() {
+        let dir = tree(&[
+            (
+                "a.rs",
+                "// Copyright 2024 New  \n// \n// All rights\t\n\nfn main() {}  \n",
+            ),
+            ("b.py", "x = 1\n"),
+        ]);
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n\nAll rights\n").unwrap();
+        let report = run_in(&dir, &["--strip-trailing-whitespace"]);
+        assert_eq!((report.cleaned, report.added), (1, 1));
+        let read = |name: &str| fs::read_to_string(dir.join("src").join(name)).unwrap();
+        assert_eq!(
+            read("a.rs"),
+            "// Copyright 2024 New\n//\n// All rights\n\nfn main() {}  \n"
+        );
+        // new headers are written clean too
+        assert_eq!(
+            read("b.py"),
+            "# Copyright 2024 New\n#\n# All rights\n\nx = 1\n"
+        );
+        assert_eq!(
+            run_in(&dir, &["--check", "--strip-trailing-whitespace"]).violations,
+            0
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dry_run_leaves_the_tree_untouched() {
+        let dir = tree(&[
+            ("a.c", "int a;\n"),
+            ("b.py", "# Copyright 2020 Old\n\nb = 1\n"),
+        ]);
+        let snapshot = || {
+            let mut entries: Vec<_> = fs::read_dir(dir.join("src"))
+                .unwrap()
+                .map(|entry| {
+                    let path = entry.unwrap().path();
+                    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+                    let bytes = fs::read(&path).unwrap();
+                    (path, bytes, modified)
+                })
+                .collect();
+            entries.sort();
+            entries
+        };
+        let before = snapshot();
+
+        let out = dir.join("out").display().to_string();
+        for extra in [
+            &[][..],
+            &["--diff"],
+            &["--verify"],
+            &["--out-dir", &out],
+            &["--touch-only-changed-lines"],
+        ] {
+            let report = run_in(&dir, &[&["-q", "--dry-run"], extra].concat());
+            assert_eq!(report.violations, 2, "{extra:?}");
+            // same names (no `.lice-tmp` left over), bytes and mtimes
+            assert!(snapshot() == before, "{extra:?} touched the tree");
+        }
+        assert!(!dir.join("out").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dry_run_exit_zero_keeps_the_preview_but_not_the_failure() {
+        let dir = tree(&[("a.c", "int a;\n"), ("b.py", "b = 1\n")]);
+        let report = run_in(&dir, &["-q", "--dry-run", "--dry-run-exit-zero"]);
+        assert_eq!((report.added, report.violations), (2, 0));
+        assert_eq!(fs::read_to_string(dir.join("src/a.c")).unwrap(), "int a;\n");
+        // other violations still fail the run
+        fs::write(dir.join("src/c.py"), b"c = \xff\n").unwrap();
+        let extra = [
+            "-q",
+            "--dry-run",
+            "--dry-run-exit-zero",
+            "--max-errors",
+            "0",
+        ];
+        let report = run_in(&dir, &extra);
+        assert_eq!((report.errors, report.violations), (1, 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn several_templates_are_joined_in_order() {
+        let dir = tree(&[("a.py", "x = 1\n")]);
+        fs::write(dir.join("COMMON.txt"), "Copyright {year} Acme\n\n").unwrap();
+        fs::write(dir.join("PROJECT.txt"), "// Part of Widget\n").unwrap();
+        let template = |name: &str| dir.join(name).display().to_string();
+        let args = [
+            "-q",
+            "-f",
+            &template("COMMON.txt"),
+            "-f",
+            &template("PROJECT.txt"),
+            &template("src"),
+        ];
+        LiceEngine::new(Config::from_args(args.map(String::from)).unwrap())
+            .unwrap()
+            .run()
+            .unwrap();
+        // the pasted `//` is uncommented before the join, `{year}` filled in after
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.py")).unwrap(),
+            format!(
+                "# Copyright {} Acme\n# \n# Part of Widget\n\nx = 1\n",
+                current_year()
+            )
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn streamed_tail_writes_the_same_bytes_as_a_full_write() {
+        // well past STREAM_MIN_TAIL, so the tail really is copied
+        let body = "int x = 1;\n".repeat(STREAM_MIN_TAIL / 8);
+        let crlf_body = body.replace('\n', "\r\n");
+        let old = format!("/*\n * Copyright 2020 Old\n */\n\n{body}");
+        let cases = [
+            ("added, LF", body.clone(), &[][..]),
+            ("added, CRLF", crlf_body.clone(), &[]),
+            ("replaced, LF", old.clone(), &[]),
+            (
+                "replaced, CRLF",
+                format!("/*\r\n * Copyright 2020 Old\r\n */\r\n\r\n{crlf_body}"),
+                &[],
+            ),
+            ("removed", old, &["--remove"]),
+            ("shorter than the header", "int x;\n".to_string(), &[]),
+        ];
+        for (case, content, extra) in cases {
+            let written = |flags: &[&str]| {
+                let dir = tree(&[("a.c", &content)]);
+                run_in(&dir, &[&["-q"], extra, flags].concat());
+                let bytes = fs::read(dir.join("src/a.c")).unwrap();
+                let leftovers = fs::read_dir(dir.join("src")).unwrap().count();
+                fs::remove_dir_all(&dir).unwrap();
+                assert_eq!(leftovers, 1, "{case}: a temp file was left behind");
+                bytes
+            };
+            let naive = written(&[]);
+            assert_ne!(naive, content.as_bytes(), "{case}: nothing was written");
+            assert!(
+                naive == written(&["--touch-only-changed-lines"]),
+                "{case}: the outputs differ"
+            );
+        }
+    }
+
+    #[test]
+    fn header_checksum_spots_a_hand_edit() {
+        let sum = header_checksum("Copyright 2024 New");
+        let stamped = format!("# Copyright 2024 New\n# lice-checksum:{sum}\n\nx = 1\n");
+        let edited = stamped.replace("New", "Mine");
+        let dir = tree(&[("a.py", "x = 1\n")]);
+        let a = dir.join("src/a.py");
+        run_in(&dir, &["-q", "--header-checksum"]);
+        assert_eq!(fs::read_to_string(&a).unwrap(), stamped);
+        assert_eq!(
+            run_in(&dir, &["--header-checksum", "--check"]).violations,
+            0
+        );
+
+        let engine =
+            LiceEngine::new(Config::from_args(tree_args(&dir, &["--header-checksum"])).unwrap())
+                .unwrap();
+        assert!(engine.header_edited(&edited, STYLE_HASH));
+        assert!(!engine.header_edited(&stamped, STYLE_HASH));
+        // an edited header is outdated like any other, and put back
+        fs::write(&a, &edited).unwrap();
+        assert_eq!(
+            run_in(&dir, &["--header-checksum", "--check"]).violations,
+            1
+        );
+        run_in(&dir, &["-q", "--header-checksum"]);
+        assert_eq!(fs::read_to_string(&a).unwrap(), stamped);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ok_if_matches_copes_with_a_minified_line() {
+        let line = format!(
+            "/* Copyright 2023 Old */ var a={};\n",
+            "1+".repeat(100_000) + "1"
+        );
+        let dir = tree(&[("a.js", &line)]);
+        let report = run_in(&dir, &["--check", "--ok-if-matches", r"Copyright \d+ .*x"]);
+        assert_eq!((report.ok, report.violations), (0, 1));
+        let report = run_in(&dir, &["--check", "--ok-if-matches", r"Copyright \d+ .*1;"]);
+        assert_eq!((report.ok, report.violations), (1, 0));
+        // and the file is left as it is where it matches
+        run_in(&dir, &["-q", "--ok-if-matches", "Copyright 2023"]);
+        assert_eq!(fs::read_to_string(dir.join("src/a.js")).unwrap(), line);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ext_style_remaps_sql_files() {
+        let dir = tree(&[("a.sql", "SELECT 1;\n")]);
+        let sql = dir.join("src/a.sql");
+        run_in(&dir, &["-q"]);
+        assert_eq!(
+            fs::read_to_string(&sql).unwrap(),
+            "-- Copyright 2024 New\n\nSELECT 1;\n"
+        );
+        fs::write(&sql, "SELECT 1;\n").unwrap();
+        run_in(&dir, &["-q", "--ext-style", "sql=c-like"]);
+        assert_eq!(
+            fs::read_to_string(&sql).unwrap(),
+            "/*\n * Copyright 2024 New\n */\n\nSELECT 1;\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_directory_fails_a_strict_run() {
+        let dir = tree(&[("a.py", "x = 1\n"), ("locked/b.py", "")]);
+        let locked = dir.join("src/locked");
+        if lock_out(&locked) {
+            let lenient = run_in(&dir, &["--check"]);
+            let strict = run_in(&dir, &["--check", "--strict"]);
+            unlock(&locked);
+            // a.py is the only violation, the locked dir only counts under --strict
+            assert_eq!((lenient.unreadable, lenient.violations), (1, 1));
+            assert_eq!((strict.unreadable, strict.violations), (1, 2));
+        }
+        unlock(&locked);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn blank_lines_per_style_after_a_block_header() {
+        for (n, gap) in [(0, ""), (1, "\n"), (2, "\n\n")] {
+            let count = format!("c-like={n}");
+            let tight = ["--blank-lines", &count];
+            let new = format!("/*\n * Copyright 2024 New\n */\n{gap}int x;\n");
+            let dir = tree(&[
+                ("a.c", "int x;\n"),
+                ("b.c", "/*\n * Copyright 2020 Old\n */\nint x;\n"),
+            ]);
+            run_in(&dir, &[&["-q"], &tight[..]].concat());
+            assert_eq!(fs::read_to_string(dir.join("src/a.c")).unwrap(), new, "{n}");
+            // an old header is replaced the same way, and the result is stable
+            assert_eq!(fs::read_to_string(dir.join("src/b.c")).unwrap(), new, "{n}");
+            let again = run_in(
+                &dir,
+                &[&["--check", "--fix-blank-lines"], &tight[..]].concat(),
+            );
+            assert_eq!((again.ok, again.violations), (2, 0), "{n}");
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        // the extension wins over the style
+        let dir = tree(&[("a.h", "int x;\n")]);
+        run_in(
+            &dir,
+            &["-q", "--blank-lines", "c-like=0", "--blank-lines", "h=2"],
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.h")).unwrap(),
+            "/*\n * Copyright 2024 New\n */\n\n\nint x;\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn after_package_puts_the_header_below_the_declaration() {
+        let dir = tree(&[
+            ("A.java", "package foo;\n\nimport x;\n"),
+            ("m.go", "package main\nfunc main() {}\n"),
+            ("a.js", "x();\n"),
+        ]);
+        let flag = ["--header-position", "after-package"];
+        run_in(&dir, &[&["-q"], &flag[..]].concat());
+        let read = |name: &str| fs::read_to_string(dir.join("src").join(name)).unwrap();
+        assert_eq!(
+            read("A.java"),
+            "package foo;\n\n// Copyright 2024 New\n\nimport x;\n"
+        );
+        assert_eq!(
+            read("m.go"),
+            "package main\n\n// Copyright 2024 New\n\nfunc main() {}\n"
+        );
+        // no package line: top as usual
+        assert_eq!(read("a.js"), "// Copyright 2024 New\n\nx();\n");
+        let again = run_in(&dir, &[&["--check"], &flag[..]].concat());
+        assert_eq!((again.ok, again.violations), (3, 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn block_template_is_uncommented_before_formatting() {
+        for template in [
+            "/*\n * Copyright A\n */\n",
+            "/**\n * Copyright A\n *****/\n",
+            "/* Copyright A */\n",
+            "/*\n   Copyright A\n*/\n",
+        ] {
+            assert_eq!(
+                uncomment_template(template),
+                "Copyright A\n",
+                "{template:?}"
+            );
+        }
+        let dir = tree(&[("a.c", "int x;\n")]);
+        fs::write(dir.join("HEADER.txt"), "/*\n * Copyright 2024 New\n */\n").unwrap();
+        run_in(&dir, &["-q"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.c")).unwrap(),
+            "/*\n * Copyright 2024 New\n */\n\nint x;\n"
+        );
+
+        fs::write(dir.join("HEADER.txt"), "/*\n *\n */\n").unwrap();
+        let message = match LiceEngine::new(Config::from_args(tree_args(&dir, &[])).unwrap()) {
+            Ok(_) => panic!("a markers-only template was accepted"),
+            Err(e) => e.to_string(),
+        };
+        assert!(
+            message.ends_with("the template is only comment markers, no text"),
+            "{message}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rust_module_docs_and_inner_attributes_are_code() {
+        let engine = engine(&[]);
+        assert_eq!(
+            licensed(&engine, "lib.rs", "#![no_std]\n\nfn x() {}\n"),
+            "// Copyright 2024 New\n\n#![no_std]\n\nfn x() {}\n"
+        );
+        assert_eq!(
+            licensed(
+                &engine,
+                "lib.rs",
+                "//! Crate docs\n#![no_std]\n\nfn x() {}\n"
+            ),
+            "// Copyright 2024 New\n\n//! Crate docs\n#![no_std]\n\nfn x() {}\n"
+        );
+        // an old header right above the docs goes, the docs stay
+        assert_eq!(
+            licensed(&engine, "lib.rs", "// Copyright 2020 Old\n//! Crate docs\n"),
+            "// Copyright 2024 New\n\n//! Crate docs\n"
+        );
+        assert_eq!(
+            licensed(&engine, "lib.rs", "/// Item docs\nfn x() {}\n"),
+            "// Copyright 2024 New\n\n/// Item docs\nfn x() {}\n"
+        );
+    }
+
+    #[test]
+    fn visitor_sees_every_file_and_may_veto_a_change() {
+        /// vetoes `b.rs`, logs every final outcome
+        struct VetoB(Arc<Mutex<Vec<(String, Outcome)>>>);
+        impl Visitor for VetoB {
+            fn review(&self, path: &Path, outcome: Outcome, proposed: &str) -> Verdict {
+                assert_eq!(outcome, Outcome::Added);
+                assert!(
+                    proposed.starts_with("// Copyright 2024 New\n"),
+                    "{proposed}"
+                );
+                match path.ends_with("b.rs") {
+                    true => Verdict::Veto,
+                    false => Verdict::Proceed,
+                }
+            }
+            fn finished(&self, path: &Path, outcome: Outcome) {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                self.0.lock().unwrap().push((name, outcome));
+            }
+        }
+
+        for (extra, violations) in [(&["--check"][..], 1), (&["-j", "3"], 0)] {
+            let dir = tree(&[("a.rs", "fn a() {}\n"), ("b.rs", "fn b() {}\n")]);
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let engine = LiceEngine::new(Config::from_args(tree_args(&dir, extra)).unwrap());
+            let report = engine.unwrap().run_with(VetoB(Arc::clone(&seen))).unwrap();
+            // under --check the vetoed file is no violation
+            assert_eq!((report.skipped, report.violations), (1, violations));
+            let mut seen = seen.lock().unwrap().clone();
+            seen.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                seen,
+                [
+                    ("a.rs".to_string(), Outcome::Added),
+                    ("b.rs".to_string(), Outcome::Skipped)
+                ]
+            );
+            let b = fs::read_to_string(dir.join("src/b.rs")).unwrap();
+            assert_eq!(b, "fn b() {}\n");
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn max_errors_stops_the_run_once_exceeded() {
+        let names: Vec<String> = (0..10).map(|i| format!("bad{i}.py")).collect();
+        let files: Vec<(&str, &str)> = names.iter().map(|name| (name.as_str(), "")).collect();
+        let dir = tree(&files);
+        for name in &names {
+            fs::write(dir.join("src").join(name), b"x = \xff\n").unwrap();
+        }
+        let report = run_in(&dir, &["--max-errors", "2"]);
+        // the third error is one too many; it stops there and fails the run
+        assert_eq!(report.errors, 3);
+        assert!(report.violations > 0);
+        // under the threshold nothing is aborted
+        let report = run_in(&dir, &["--max-errors", "10"]);
+        assert_eq!((report.errors, report.violations), (10, 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn licenseignore_marker_opts_a_subtree_out() {
+        let dir = tree(&[
+            ("a.rs", ""),
+            ("third_party/.licenseignore", ""),
+            ("third_party/b.rs", ""),
+            ("third_party/deep/c.rs", ""),
+            ("other/d.rs", ""),
+        ]);
+        assert_eq!(candidates_in(&dir, &[]), ["a.rs", "other/d.rs"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn detect_wrong_style_replaces_a_foreign_header() {
+        let detect = engine(&["--detect-wrong-style"]);
+        assert_eq!(
+            licensed(&detect, "a.py", "// Copyright 2020 Old\n\nx = 1\n"),
+            "# Copyright 2024 New\n\nx = 1\n"
+        );
+        assert_eq!(
+            licensed(&detect, "a.c", "# Copyright 2020 Old\n\n\nint x;\n"),
+            "/*\n * Copyright 2024 New\n */\n\nint x;\n"
+        );
+        // neither a preprocessor line nor a comment without a license keyword
+        assert_eq!(
+            licensed(&detect, "a.c", "#include <x.h>\n"),
+            "/*\n * Copyright 2024 New\n */\n\n#include <x.h>\n"
+        );
+        assert_eq!(
+            licensed(&detect, "a.py", "// just a note\nx = 1\n"),
+            "# Copyright 2024 New\n\n// just a note\nx = 1\n"
+        );
+        // without the flag the foreign header stays below the new one
+        assert_eq!(
+            licensed(
+                &super::tests::engine(&[]),
+                "a.py",
+                "// Copyright 2020 Old\n"
+            ),
+            "# Copyright 2024 New\n\n// Copyright 2020 Old\n"
+        );
+
+        let dir = tree(&[("a.py", "// Copyright 2020 Old\n\nx = 1\n")]);
+        let report = run_in(&dir, &["--check", "--detect-wrong-style"]);
+        assert_eq!(report.outcomes[0].1, Outcome::Updated);
+        assert_eq!(report.violations, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn single_and_multi_threaded_runs_tally_the_same() {
+        let files: Vec<(String, &str)> = (0..40)
+            .map(|i| match i % 4 {
+                0 => (
+                    format!("d{}/ok{i}.py", i % 3),
+                    "# Copyright 2024 New\n\nx = 1\n",
+                ),
+                1 => (
+                    format!("d{}/old{i}.py", i % 3),
+                    "# Copyright 2020 Old\n\nx = 1\n",
+                ),
+                2 => (format!("d{}/new{i}.rs", i % 3), "fn x() {}\n"),
+                _ => (format!("d{}/skip{i}.txt", i % 3), "text\n"),
+            })
+            .collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(n, c)| (n.as_str(), *c)).collect();
+        let tally = |jobs: &str| {
+            let dir = tree(&files);
+            fs::write(dir.join("src/d0/bad.py"), b"\xff\n").unwrap();
+            let config = Config::from_args(tree_args(&dir, &["-q", "-j", jobs])).unwrap();
+            let report = LiceEngine::new(config).unwrap().run().unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+            let counts = [
+                report.total,
+                report.ok,
+                report.added,
+                report.updated,
+                report.skipped,
+            ];
+            (counts, report.errors, report.violations)
+        };
+        let single = tally("1");
+        assert_eq!(single, ([41, 10, 10, 10, 10], 1, 0));
+        assert_eq!(tally("4"), single);
+    }
+
+    #[test]
+    fn extensionless_scripts_are_styled_by_interpreter() {
+        let licensed_script = |name: &str, content: &str, extra: &[&str]| {
+            let dir = tree(&[(name, content)]);
+            let report = run_in(&dir, &[&["-q"], extra].concat());
+            let result = fs::read_to_string(dir.join("src").join(name)).unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+            (report.added, result)
+        };
+        assert_eq!(
+            licensed_script("deploy", "#!/usr/bin/env python3.11\nrun()\n", &[]).1,
+            "#!/usr/bin/env python3.11\n# Copyright 2024 New\n\nrun()\n"
+        );
+        assert_eq!(
+            licensed_script("serve", "#!/usr/bin/node\nrun();\n", &[]).1,
+            "#!/usr/bin/node\n// Copyright 2024 New\n\nrun();\n"
+        );
+        // an extension that names no language goes by the interpreter too
+        assert_eq!(
+            licensed_script("run.cgi", "#!/usr/bin/perl\nrun();\n", &[]).1,
+            "#!/usr/bin/perl\n# Copyright 2024 New\n\nrun();\n"
+        );
+        // unknown interpreters are left alone, unless mapped
+        assert_eq!(
+            licensed_script("task", "#!/usr/bin/env deno\nrun();\n", &[]),
+            (0, "#!/usr/bin/env deno\nrun();\n".to_string())
+        );
+        let mapped = ["--interpreter-style", "deno=double-slash"];
+        assert_eq!(
+            licensed_script("task", "#!/usr/bin/env deno\nrun();\n", &mapped).1,
+            "#!/usr/bin/env deno\n// Copyright 2024 New\n\nrun();\n"
+        );
+        // an override for `python` covers `python3` too
+        let mapped = ["--interpreter-style", "python=double-slash"];
+        assert_eq!(
+            licensed_script("deploy", "#!/usr/bin/python3\nrun()\n", &mapped).1,
+            "#!/usr/bin/python3\n// Copyright 2024 New\n\nrun()\n"
+        );
+    }
+
+    #[test]
+    fn template_line_endings_follow_the_target() {
+        let dir = tree(&[("a.py", "x = 1\n"), ("a.c", "int x;\r\n")]);
+        fs::write(
+            dir.join("HEADER.txt"),
+            "Copyright 2024 New\r\nAll rights\r\n",
+        )
+        .unwrap();
+        run_in(&dir, &["-q"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.py")).unwrap(),
+            "# Copyright 2024 New\n# All rights\n\nx = 1\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.c")).unwrap(),
+            "/*\r\n * Copyright 2024 New\r\n * All rights\r\n */\r\n\r\nint x;\r\n"
+        );
+        // and both are current for an LF template
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\nAll rights\n").unwrap();
+        assert_eq!(run_in(&dir, &["-q", "--check"]).violations, 0);
+        fs::remove_dir_all(&dir).unwrap();
+        // a lone `\r` (old Mac) is a line break too
+        assert_eq!(expand_template("A\rB\r\nC", &[]), "A\nB\nC");
+    }
+
+    #[test]
+    fn header_vars_fill_placeholders_and_leftovers_are_found() {
+        let vars = [
+            ("team".to_string(), "Core".to_string()),
+            ("repo".to_string(), "lice".to_string()),
+        ];
+        assert_eq!(
+            expand_template("{team} / {repo} / {teem} / {team}", &vars),
+            "Core / lice / {teem} / Core"
+        );
+        assert_eq!(
+            unset_placeholders("{teem} {x y} {a-b} {teem} {}"),
+            ["teem", "a-b"]
+        );
+        let dir = tree(&[("a.py", "")]);
+        fs::write(dir.join("HEADER.txt"), "Copyright 2024 {team}\n").unwrap();
+        run_in(&dir, &["-q", "--header-var", "team=Core"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.py")).unwrap(),
+            "# Copyright 2024 Core\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bottom_header_is_found_replaced_and_left_alone_when_current() {
+        let dir = tree(&[
+            ("a.py", "x = 1\n"),
+            ("b.c", "int x;\n\n/*\n * Copyright 2020 Old\n */\n"),
+            ("c.py", "x = 1\n\n# vim: set ts=4:\n"),
+        ]);
+        let read = |name: &str| fs::read_to_string(dir.join("src").join(name)).unwrap();
+        let report = run_in(&dir, &["-q", "--position", "bottom"]);
+        assert_eq!((report.added, report.updated), (2, 1));
+        assert_eq!(read("a.py"), "x = 1\n\n# Copyright 2024 New\n");
+        // an outdated one is replaced, not appended to
+        assert_eq!(read("b.c"), "int x;\n\n/*\n * Copyright 2024 New\n */\n");
+        // a closing comment without a license keyword is no header
+        assert_eq!(
+            read("c.py"),
+            "x = 1\n\n# vim: set ts=4:\n\n# Copyright 2024 New\n"
+        );
+        let again = run_in(&dir, &["-q", "--check", "--position", "bottom"]);
+        assert_eq!((again.ok, again.violations), (3, 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn selftest_round_trips_every_style_and_touches_nothing() {
+        let dir = tree(&[("a.py", "x = 1\n")]);
+        let report = run_in(&dir, &["--selftest", "--ext-style", "sql=c-like"]);
+        assert_eq!((report.total, report.violations), (0, 0));
+        for extra in [&["--style", "banner"][..], &["--position", "bottom"]] {
+            let report = run_in(&dir, &[&["--selftest"], extra].concat());
+            assert_eq!(report.violations, 0, "{extra:?}");
+        }
+        // at the bottom only a license comment is taken for the header
+        fs::write(dir.join("HEADER.txt"), "Hello world\n").unwrap();
+        assert_eq!(run_in(&dir, &["--selftest"]).violations, 0);
+        let report = run_in(&dir, &["--selftest", "--position", "bottom"]);
+        assert_eq!(report.violations, STYLE_SAMPLES.len());
+        assert_eq!(fs::read_to_string(dir.join("src/a.py")).unwrap(), "x = 1\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_file_is_a_clear_failure() {
+        let dir = tree(&[("a.py", "x = 1\n"), ("locked.py", "y = 2\n")]);
+        let locked = dir.join("src/locked.py");
+        if lock_out(&locked) {
+            let error = read_text(&locked).unwrap_err();
+            assert!(error.denied);
+            assert_eq!(
+                error.message,
+                format!("permission denied reading {}", locked.display())
+            );
+            // always an error; a violation too when checking, or under --strict
+            let check = run_in(&dir, &["--check"]);
+            let preview = run_in(&dir, &["--dry-run", "--dry-run-exit-zero"]);
+            let apply = run_in(&dir, &[]);
+            let strict = run_in(&dir, &["--strict"]);
+            unlock(&locked);
+            assert_eq!((check.errors, check.violations), (1, 2));
+            assert_eq!((preview.errors, preview.violations), (1, 1));
+            assert_eq!((apply.errors, apply.violations, apply.added), (1, 0, 1));
+            assert_eq!((strict.errors, strict.violations, strict.ok), (1, 1, 1));
+        }
+        unlock(&locked);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn verify_idempotent_flags_files_a_formatter_undid() {
+        let dir = tree(&[("a.py", "x = 1\n"), ("b.c", "int b;\n")]);
+        let report = run_in(&dir, &["-q", "--verify-idempotent"]);
+        assert_eq!((report.added, report.violations), (2, 0));
+
+        // a hook dropping the first header line leaves the file as it wrote it
+        fs::write(dir.join("src/a.py"), "x = 1\n").unwrap();
+        fs::write(dir.join("src/c.py"), "y = 2\n").unwrap();
+        let report = run_in(
+            &dir,
+            &["-q", "--verify-idempotent", "--post-cmd", "sed -i 1d"],
+        );
+        assert_eq!((report.added, report.ok, report.violations), (2, 1, 2));
+        assert_eq!(
+            fs::read_to_string(dir.join("src/a.py")).unwrap(),
+            "\nx = 1\n"
+        );
+        assert!(Config::from_args(tree_args(&dir, &["--check", "--verify-idempotent"])).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! `-f https://...`: fetch the template once per run (feature `http`)
+//!
+//! a blocking GET through ureq, TLS by rustls with the bundled web roots.
+//! plain `http://` still works, redirects are followed a few times.
+
+use std::io;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: u32 = 5;
+
+/// the body of `url`, which must answer 2xx with UTF-8 text
+pub(crate) fn get(url: &str) -> io::Result<String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .redirects(MAX_REDIRECTS)
+        .user_agent("lice")
+        .build();
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            return Err(io::Error::other(format!(
+                "HTTP {} {}",
+                code,
+                response.status_text()
+            )));
+        }
+        Err(ureq::Error::Transport(e)) => return Err(transport_error(&e)),
+    };
+    response.into_string().map_err(|e| {
+        if e.kind() == io::ErrorKind::InvalidData {
+            io::Error::new(io::ErrorKind::InvalidData, "template is not valid UTF-8")
+        } else {
+            e
+        }
+    })
+}
+
+/// Helper: ureq's transport failures, as the nearest `io::ErrorKind`
+///
+/// the caller names the URL already, so it is left out of the message
+fn transport_error(e: &ureq::Transport) -> io::Error {
+    let kind = match e.kind() {
+        ureq::ErrorKind::InvalidUrl | ureq::ErrorKind::UnknownScheme => io::ErrorKind::InvalidInput,
+        ureq::ErrorKind::Dns => io::ErrorKind::NotFound,
+        ureq::ErrorKind::ConnectionFailed => io::ErrorKind::ConnectionRefused,
+        _ => io::ErrorKind::Other,
+    };
+    let mut message = e.kind().to_string();
+    if let Some(detail) = e.message() {
+        message = format!("{}: {}", message, detail);
+    }
+    if let Some(cause) = std::error::Error::source(e) {
+        message = format!("{}: {}", message, cause);
+    }
+    io::Error::new(kind, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// a one-shot local server answering `response`, and its URL
+    fn serve(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/HEADER.txt", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn body_of_a_2xx_answer_is_the_template() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 19\r\n\r\nCopyright 2024 New\n");
+        assert_eq!(get(&url).unwrap(), "Copyright 2024 New\n");
+    }
+
+    #[test]
+    fn http_errors_carry_the_status() {
+        let url = serve("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+        assert_eq!(get(&url).unwrap_err().to_string(), "HTTP 404 Not Found");
+    }
+}
@@ -0,0 +1,196 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! the subset of `.gitattributes` that decides line endings (`eol`, `text`)
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::glob;
+
+/// line terminator to emit for inserted lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Eol {
+    Lf,
+    Crlf,
+}
+
+impl Eol {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Eol::Lf => "\n",
+            Eol::Crlf => "\r\n",
+        }
+    }
+
+    /// ending of the first terminated line, LF when there is none
+    pub(crate) fn detect(content: &str) -> Eol {
+        match content.find('\n') {
+            Some(i) if content[..i].ends_with('\r') => Eol::Crlf,
+            _ => Eol::Lf,
+        }
+    }
+}
+
+/// one `pattern attr...` line
+struct Rule {
+    pattern: String,
+    /// pattern contains a `/`: matched against the path relative to the file's dir
+    anchored: bool,
+    /// `Some(false)` for `-text` / `binary`
+    text: Option<bool>,
+    eol: Option<Eol>,
+}
+
+/// per-directory `.gitattributes` lookups, cached across workers
+#[derive(Default)]
+pub(crate) struct GitAttributes {
+    cache: Mutex<HashMap<PathBuf, Arc<Vec<Rule>>>>,
+}
+
+impl GitAttributes {
+    /// the `eol` git would check `path` out with, if its attributes pin one
+    ///
+    /// `.gitattributes` files are consulted from the repository root (the
+    /// nearest ancestor holding `.git`) down to the file's directory; later
+    /// and deeper lines win, as in git. `-text`/`binary` cancels any `eol`.
+    pub(crate) fn eol_for(&self, path: &Path) -> Option<Eol> {
+        let abs = fs::canonicalize(path).ok()?;
+
+        let mut dirs: Vec<&Path> = Vec::new();
+        for dir in abs.ancestors().skip(1) {
+            dirs.push(dir);
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+
+        let mut text = None;
+        let mut eol = None;
+        for dir in dirs.into_iter().rev() {
+            let Ok(relative) = abs.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            let name = abs.file_name()?.to_string_lossy();
+
+            for rule in self.rules_in(dir).iter() {
+                let subject = if rule.anchored { &relative } else { &*name };
+                if glob::matches(&rule.pattern, subject) {
+                    text = rule.text.or(text);
+                    eol = rule.eol.or(eol);
+                }
+            }
+        }
+
+        if text == Some(false) { None } else { eol }
+    }
+
+    fn rules_in(&self, dir: &Path) -> Arc<Vec<Rule>> {
+        let mut cache = self.cache.lock().unwrap();
+        Arc::clone(
+            cache
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| Arc::new(parse(dir))),
+        )
+    }
+}
+
+fn parse(dir: &Path) -> Vec<Rule> {
+    let Ok(raw) = fs::read_to_string(dir.join(".gitattributes")) else {
+        return Vec::new();
+    };
+
+    let mut rules = Vec::new();
+    for line in raw.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let Some(pattern) = fields.next() else {
+            continue;
+        };
+
+        let mut rule = Rule {
+            anchored: pattern.contains('/'),
+            pattern: pattern.trim_start_matches('/').to_string(),
+            text: None,
+            eol: None,
+        };
+        for attr in fields {
+            match attr {
+                "text" | "text=auto" => rule.text = Some(true),
+                "-text" | "binary" => rule.text = Some(false),
+                "eol=lf" => rule.eol = Some(Eol::Lf),
+                "eol=crlf" => rule.eol = Some(Eol::Crlf),
+                _ => {}
+            }
+        }
+        if rule.text.is_some() || rule.eol.is_some() {
+            rules.push(rule);
+        }
+    }
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eol_follows_the_nearest_matching_line_up_to_the_repo_root() {
+        let root = std::env::temp_dir().join(format!("lice-attrs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("docs/sub")).unwrap();
+        fs::write(
+            root.join(".gitattributes"),
+            "# line endings\n*.py eol=crlf\n*.bin binary eol=crlf\n/docs/*.md eol=lf\n*.txt text\n",
+        )
+        .unwrap();
+        fs::write(root.join("docs/sub/.gitattributes"), "b.py eol=lf\n").unwrap();
+        // above the repository root nothing is read
+        let outside = root.parent().unwrap().join(".gitattributes");
+        assert!(!outside.exists());
+
+        let attrs = GitAttributes::default();
+        let eol = |name: &str| {
+            let path = root.join(name);
+            fs::write(&path, "x\n").unwrap();
+            attrs.eol_for(&path)
+        };
+        assert_eq!(eol("a.py"), Some(Eol::Crlf));
+        assert_eq!(eol("docs/sub/a.py"), Some(Eol::Crlf));
+        // a deeper file overrides the root one
+        assert_eq!(eol("docs/sub/b.py"), Some(Eol::Lf));
+        // anchored patterns are relative to their own directory
+        assert_eq!(eol("docs/a.md"), Some(Eol::Lf));
+        assert_eq!(eol("docs/sub/a.md"), None);
+        // binary cancels eol, and text alone pins nothing
+        assert_eq!(eol("x.bin"), None);
+        assert_eq!(eol("x.txt"), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn detect_looks_at_the_first_line_ending() {
+        assert_eq!(Eol::detect("a\r\nb\n"), Eol::Crlf);
+        assert_eq!(Eol::detect("a\nb\r\n"), Eol::Lf);
+        assert_eq!(Eol::detect("no newline"), Eol::Lf);
+    }
+}
@@ -0,0 +1,229 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! gitignore-flavoured glob matching, std only
+//!
+//! `*` and `?` never cross a `/`, `**` does, `[a-z]`/`[!a-z]` are classes and
+//! `\` escapes the next character.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// does the whole of `text` match `pattern`?
+pub(crate) fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(p: &[char], t: &[char]) -> bool {
+    match p.first() {
+        None => t.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => {
+            // `**/` also matches zero directories
+            let rest = &p[2..];
+            if let Some(after_slash) = rest.strip_prefix(&['/'])
+                && match_from(after_slash, t)
+            {
+                return true;
+            }
+            (0..=t.len()).any(|i| match_from(rest, &t[i..]))
+        }
+        Some('*') => {
+            let rest = &p[1..];
+            for i in 0..=t.len() {
+                if match_from(rest, &t[i..]) {
+                    return true;
+                }
+                if t.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => t.first().is_some_and(|&c| c != '/') && match_from(&p[1..], &t[1..]),
+        Some('[') => match (t.first(), class_end(p)) {
+            (Some(&c), Some(end)) => {
+                c != '/' && class_matches(&p[1..end], c) && match_from(&p[end + 1..], &t[1..])
+            }
+            // unterminated class: treat `[` literally
+            (Some(&c), None) => c == '[' && match_from(&p[1..], &t[1..]),
+            (None, _) => false,
+        },
+        Some('\\') if p.len() > 1 => t.first() == Some(&p[1]) && match_from(&p[2..], &t[1..]),
+        Some(&c) => t.first() == Some(&c) && match_from(&p[1..], &t[1..]),
+    }
+}
+
+/// index of the `]` closing the class opened at `p[0]`
+fn class_end(p: &[char]) -> Option<usize> {
+    let mut i = 1;
+    if matches!(p.get(i), Some('!') | Some('^')) {
+        i += 1;
+    }
+    // a leading `]` is a literal member
+    if p.get(i) == Some(&']') {
+        i += 1;
+    }
+    p[i..].iter().position(|&c| c == ']').map(|pos| i + pos)
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            if class[i] <= c && c <= class[i + 2] {
+                found = true;
+            }
+            i += 3;
+        } else {
+            if class[i] == c {
+                found = true;
+            }
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+/// does `s` contain any glob metacharacter?
+pub(crate) fn has_magic(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// expand `pattern` against the filesystem, like a shell would
+///
+/// matched per path component; `**` spans any number of directories and
+/// hidden entries only match a component that starts with `.` itself.
+/// results are sorted, an empty vec means nothing matched
+pub(crate) fn expand(pattern: &str) -> Vec<PathBuf> {
+    // the literal leading components (root, drive, plain dirs) are taken as
+    // they are, the rest is matched per directory level
+    let mut base = PathBuf::new();
+    let mut rest = Vec::new();
+    for component in Path::new(pattern).components() {
+        let text = component.as_os_str().to_str().unwrap_or_default();
+        if rest.is_empty() && !has_magic(text) {
+            base.push(component);
+        } else {
+            rest.push(text);
+        }
+    }
+
+    let mut out = Vec::new();
+    expand_from(base, &rest, &mut out);
+    out.sort();
+    out.dedup();
+    out
+}
+
+fn expand_from(base: PathBuf, rest: &[&str], out: &mut Vec<PathBuf>) {
+    let Some((&component, tail)) = rest.split_first() else {
+        if base.exists() {
+            out.push(base);
+        }
+        return;
+    };
+
+    if !has_magic(component) {
+        expand_from(base.join(component), tail, out);
+        return;
+    }
+
+    let dir = if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base.clone()
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return;
+    };
+
+    if component == "**" {
+        // zero directories
+        expand_from(base.clone(), tail, out);
+    }
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.starts_with('.') && !component.starts_with('.') {
+            continue;
+        }
+        if component == "**" {
+            if entry.path().is_dir() {
+                expand_from(base.join(name), rest, out);
+            }
+        } else if matches(component, name) {
+            expand_from(base.join(name), tail, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_stay_within_a_directory_double_stars_do_not() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/main.rs"));
+        assert!(matches("**", "a/b"));
+        assert!(matches("?.c", "a.c"));
+        assert!(!matches("?", "/"));
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[!a-c]x", "bx"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        // an unterminated class is a literal `[`
+        assert!(matches("[ab", "[ab"));
+    }
+
+    #[test]
+    fn expand_walks_the_tree_like_a_shell() {
+        let dir = std::env::temp_dir().join(format!("lice-glob-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        for file in ["src/a.rs", "src/b.py", "src/sub/c.rs", ".hidden/d.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let root = dir.to_str().unwrap();
+
+        assert_eq!(
+            expand(&format!("{}/src/*.rs", root)),
+            [dir.join("src/a.rs")]
+        );
+        assert_eq!(
+            expand(&format!("{}/**/*.rs", root)),
+            [dir.join("src/a.rs"), dir.join("src/sub/c.rs")]
+        );
+        assert!(expand(&format!("{}/src/*.go", root)).is_empty());
+        assert!(has_magic("src/[ab].rs") && !has_magic("src/a.rs"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
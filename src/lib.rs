@@ -0,0 +1,44 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! lice - automate source code license headers
+//!
+//! The `lice` binary is a thin wrapper around this crate: build a [`Config`],
+//! hand it to [`LiceEngine::new`] and either [`LiceEngine::run`] it, getting
+//! a [`Report`] back, or drive the discovery yourself through
+//! [`LiceEngine::candidates`]. [`LiceEngine::run_with`] runs it with a
+//! [`Visitor`] that sees, and may veto, every change.
+
+mod bench;
+mod cache;
+mod config;
+mod dep5;
+mod diff;
+mod engine;
+#[cfg(feature = "http")]
+mod fetch;
+mod gitattributes;
+mod glob;
+mod json;
+mod licenses;
+mod regex;
+mod style;
+#[cfg(feature = "zip")]
+mod zip;
+
+pub use config::{Config, USAGE_INFO};
+pub use engine::{
+    Candidates, LiceEngine, Outcome, Report, Verdict, Visitor, apply_license_to_content,
+};
+pub use style::LanguageProfile;
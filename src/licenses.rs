@@ -0,0 +1,85 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! `--license-id`: bundled short-form notices by SPDX identifier
+//!
+//! `{author}` is filled in from `--author`, `{year}` as in any template.
+
+const APACHE_2_0: &str = "\
+Copyright {year} {author}
+
+Licensed under the Apache License, Version 2.0 (the \"License\");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an \"AS IS\" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+";
+
+const MIT: &str = "\
+Copyright (c) {year} {author}
+
+SPDX-License-Identifier: MIT
+";
+
+const BSD_3_CLAUSE: &str = "\
+Copyright (c) {year} {author}
+
+SPDX-License-Identifier: BSD-3-Clause
+";
+
+const GPL_3_0: &str = "\
+Copyright (C) {year} {author}
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+";
+
+/// every bundled notice by identifier; `GPL-3.0` is the deprecated alias
+const LICENSES: [(&str, &str); 5] = [
+    ("Apache-2.0", APACHE_2_0),
+    ("MIT", MIT),
+    ("BSD-3-Clause", BSD_3_CLAUSE),
+    ("GPL-3.0-or-later", GPL_3_0),
+    ("GPL-3.0", GPL_3_0),
+];
+
+/// the notice for SPDX identifier `id`, compared ignoring case
+pub(crate) fn template(id: &str) -> Option<&'static str> {
+    LICENSES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(id))
+        .map(|(_, text)| *text)
+}
+
+/// the supported identifiers, for error messages
+pub(crate) fn ids() -> String {
+    let names: Vec<&str> = LICENSES.iter().map(|(name, _)| *name).collect();
+    names.join(", ")
+}
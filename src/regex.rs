@@ -0,0 +1,507 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! a small regex for `--match` and `--ok-if-matches`, std only
+//!
+//! literals, `.`, `[a-z]`/`[^a-z]`, `\d` `\w` `\s` (and their negations),
+//! `^` `$`, groups `( )` with `|`, and the quantifiers `*` `+` `?` `{n}`
+//! `{n,}` `{n,m}`. no captures, no lazy quantifiers, no lookaround.
+//!
+//! patterns compile to a small program that is run over the text without
+//! backtracking or recursion, so time is linear in the text and stack use
+//! constant: `--ok-if-matches` sees file content, minified lines included.
+
+/// one pattern element
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    /// `.`
+    Any,
+    /// inclusive ranges, and whether the class is negated
+    Class(Vec<(char, char)>, bool),
+    /// `^`
+    Start,
+    /// `$`
+    End,
+    /// `( )`: alternatives, each a sequence
+    Group(Vec<Vec<Node>>),
+    /// node, min, max (`None` for unbounded)
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Regex {
+    source: String,
+    program: Vec<Inst>,
+}
+
+impl Regex {
+    pub(crate) fn new(pattern: &str) -> Result<Self, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut parser = Parser { chars, pos: 0 };
+        let alternatives = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            // only an unbalanced `)` stops the top level early
+            return Err(format!("Invalid regex {}: unmatched ')'", pattern));
+        }
+        let program = compile(&alternatives)
+            .ok_or_else(|| format!("Invalid regex {}: too large", pattern))?;
+        Ok(Regex {
+            source: pattern.to_string(),
+            program,
+        })
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// does the pattern match anywhere in `text`? anchor with `^`/`$` for all of it
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        run(&self.program, &text)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, what: &str) -> String {
+        let pattern: String = self.chars.iter().collect();
+        format!("Invalid regex {}: {}", pattern, what)
+    }
+
+    /// `a|b|c`, up to an unbalanced `)` or the end
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek()
+            && c != '|'
+            && c != ')'
+        {
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.chars[self.pos];
+        self.pos += 1;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                let alternatives = self.alternatives()?;
+                if self.peek() != Some(')') {
+                    return Err(self.error("unclosed '('"));
+                }
+                self.pos += 1;
+                Node::Group(alternatives)
+            }
+            '[' => self.class()?,
+            '\\' => self.escape()?,
+            '*' | '+' | '?' | '{' => return Err(self.error("nothing to repeat")),
+            c => Node::Char(c),
+        })
+    }
+
+    /// after a `\`
+    fn escape(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or_else(|| self.error("trailing '\\'"))?;
+        self.pos += 1;
+        let digit = vec![('0', '9')];
+        let word = vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+        let space = vec![(' ', ' '), ('\t', '\r')];
+        Ok(match c {
+            'd' => Node::Class(digit, false),
+            'D' => Node::Class(digit, true),
+            'w' => Node::Class(word, false),
+            'W' => Node::Class(word, true),
+            's' => Node::Class(space, false),
+            'S' => Node::Class(space, true),
+            c => Node::Char(c),
+        })
+    }
+
+    /// after a `[`
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unclosed '['"))?;
+            self.pos += 1;
+            // a leading `]` is a literal
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let lo = match c {
+                '\\' => {
+                    let escaped = self.peek().ok_or_else(|| self.error("unclosed '['"))?;
+                    self.pos += 1;
+                    escaped
+                }
+                c => c,
+            };
+            let hi = match (self.peek(), self.chars.get(self.pos + 1)) {
+                (Some('-'), Some(&hi)) if hi != ']' => {
+                    self.pos += 2;
+                    hi
+                }
+                _ => lo,
+            };
+            if hi < lo {
+                return Err(self.error("reversed range in '[ ]'"));
+            }
+            ranges.push((lo, hi));
+        }
+        Ok(Node::Class(ranges, negated))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => return self.counted(atom),
+            _ => return Ok(atom),
+        };
+        self.pos += 1;
+        Ok(Node::Repeat(Box::new(atom), min, max))
+    }
+
+    /// `{n}`, `{n,}` or `{n,m}`
+    fn counted(&mut self, atom: Node) -> Result<Node, String> {
+        let close = self.chars[self.pos..]
+            .iter()
+            .position(|&c| c == '}')
+            .ok_or_else(|| self.error("unclosed '{'"))?;
+        let inner: String = self.chars[self.pos + 1..self.pos + close].iter().collect();
+        let number = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| self.error("bad '{n,m}'"))
+        };
+        let (min, max) = match inner.split_once(',') {
+            None => {
+                let n = number(&inner)?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (number(min)?, None),
+            Some((min, max)) => (number(min)?, Some(number(max)?)),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("bad '{n,m}'"));
+        }
+        self.pos += close + 1;
+        Ok(Node::Repeat(Box::new(atom), min, max))
+    }
+}
+
+/// one instruction of the compiled pattern
+#[derive(Debug, Clone)]
+enum Inst {
+    /// consume one character matching the node (`Char`, `Any` or `Class`)
+    Step(Node),
+    /// `^`
+    Start,
+    /// `$`
+    End,
+    /// continue at both
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+/// compiled patterns larger than this are refused (`(a{100}){100}`)
+const MAX_PROGRAM: usize = 100_000;
+
+/// Helper: compile the alternatives into a program ending in `Match`
+fn compile(alternatives: &[Vec<Node>]) -> Option<Vec<Inst>> {
+    let mut program = Vec::new();
+    emit_alternatives(&mut program, alternatives);
+    program.push(Inst::Match);
+    (program.len() <= MAX_PROGRAM).then_some(program)
+}
+
+/// Helper: `a|b|c`, each tried through a `Split`, all jumping to the end
+fn emit_alternatives(program: &mut Vec<Inst>, alternatives: &[Vec<Node>]) {
+    let mut jumps = Vec::new();
+    for (i, alternative) in alternatives.iter().enumerate() {
+        if i + 1 == alternatives.len() {
+            emit_sequence(program, alternative);
+            break;
+        }
+        let split = program.len();
+        program.push(Inst::Split(split + 1, 0));
+        emit_sequence(program, alternative);
+        jumps.push(program.len());
+        program.push(Inst::Jump(0));
+        program[split] = Inst::Split(split + 1, program.len());
+    }
+    let end = program.len();
+    for jump in jumps {
+        program[jump] = Inst::Jump(end);
+    }
+}
+
+fn emit_sequence(program: &mut Vec<Inst>, nodes: &[Node]) {
+    for node in nodes {
+        // stop growing a program that will be refused anyway
+        if program.len() > MAX_PROGRAM {
+            return;
+        }
+        emit(program, node);
+    }
+}
+
+fn emit(program: &mut Vec<Inst>, node: &Node) {
+    match node {
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Group(alternatives) => emit_alternatives(program, alternatives),
+        Node::Repeat(inner, min, max) => {
+            let once = std::slice::from_ref(&**inner);
+            for _ in 0..*min {
+                emit_sequence(program, once);
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    emit_sequence(program, once);
+                    program.push(Inst::Jump(split));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        if program.len() > MAX_PROGRAM {
+                            break;
+                        }
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        emit_sequence(program, once);
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+        single => program.push(Inst::Step(single.clone())),
+    }
+}
+
+/// Helper: add the thread at `pc` to `threads`, following jumps, splits and
+/// anchors at `pos`; true once one reaches `Match`
+///
+/// `seen` marks what is already in `threads`, so empty loops (`(a*)*`) end
+fn add_thread(
+    program: &[Inst],
+    threads: &mut Vec<usize>,
+    seen: &mut [usize],
+    mark: usize,
+    pc: usize,
+    (pos, len): (usize, usize),
+) -> bool {
+    let mut stack = vec![pc];
+    while let Some(pc) = stack.pop() {
+        if seen[pc] == mark {
+            continue;
+        }
+        seen[pc] = mark;
+        match &program[pc] {
+            Inst::Match => return true,
+            Inst::Jump(to) => stack.push(*to),
+            Inst::Split(first, second) => {
+                stack.push(*second);
+                stack.push(*first);
+            }
+            Inst::Start if pos == 0 => stack.push(pc + 1),
+            Inst::End if pos == len => stack.push(pc + 1),
+            Inst::Start | Inst::End => {}
+            Inst::Step(_) => threads.push(pc),
+        }
+    }
+    false
+}
+
+/// Helper: every position at once, one character at a time: linear in the
+/// text, whatever the pattern
+fn run(program: &[Inst], text: &[char]) -> bool {
+    let mut seen = vec![0; program.len()];
+    let mut current = Vec::new();
+    let mut next = Vec::new();
+    for pos in 0..=text.len() {
+        // a match may start here too; marks are per position, from 1
+        let mark = pos + 1;
+        if add_thread(program, &mut current, &mut seen, mark, 0, (pos, text.len())) {
+            return true;
+        }
+        let Some(&c) = text.get(pos) else {
+            break;
+        };
+        for &pc in &current {
+            let Inst::Step(node) = &program[pc] else {
+                continue;
+            };
+            if matches_char(node, c)
+                && add_thread(
+                    program,
+                    &mut next,
+                    &mut seen,
+                    mark + 1,
+                    pc + 1,
+                    (pos + 1, text.len()),
+                )
+            {
+                return true;
+            }
+        }
+        current.clear();
+        std::mem::swap(&mut current, &mut next);
+    }
+    false
+}
+
+fn matches_char(node: &Node, c: char) -> bool {
+    match node {
+        Node::Char(expected) => c == *expected,
+        Node::Any => c != '\n',
+        Node::Class(ranges, negated) => {
+            ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert!(matches("[a-c]x", "zzbx"));
+        assert!(!matches("[a-c]x", "zzdx"));
+        assert!(matches("[^a-c]x", "dx"));
+        assert!(!matches("^[^a-c]x", "bx"));
+        assert!(matches(r"\d\d", "v12"));
+        assert!(!matches(r"\d\d", "v1a"));
+        assert!(matches(r"^\w+$", "snake_case9"));
+        assert!(!matches(r"^\w+$", "kebab-case"));
+        assert!(matches(r"a\sb", "a\tb"));
+        assert!(matches(r"\S\D\W", "x-!"));
+        // a leading `]` and an escaped `-` are literals
+        assert!(matches("[]x]", "]"));
+        assert!(matches(r"^[a\-z]$", "-"));
+        assert!(!matches(r"^[a\-z]$", "m"));
+        assert!(matches(r"a\.b", "a.b"));
+        assert!(!matches(r"a\.b", "axb"));
+        assert!(!matches("a.b", "a\nb"));
+    }
+
+    #[test]
+    fn counted_repeats() {
+        assert!(matches("^a{3}$", "aaa"));
+        assert!(!matches("^a{3}$", "aa"));
+        assert!(!matches("^a{3}$", "aaaa"));
+        assert!(matches("^a{2,}$", "aaaaa"));
+        assert!(!matches("^a{2,}$", "a"));
+        assert!(matches("^a{1,2}b$", "aab"));
+        assert!(!matches("^a{1,2}b$", "aaab"));
+        assert!(matches("^(ab){2}$", "abab"));
+        assert!(matches("^x?y+z*$", "yyy"));
+        assert!(!matches("^x?y+z*$", "xxy"));
+    }
+
+    #[test]
+    fn alternation_and_anchors() {
+        assert!(matches("^(gen|vendor)/", "vendor/a.c"));
+        assert!(!matches("^(gen|vendor)/", "src/vendor/a.c"));
+        assert!(matches("(gen|vendor)/", "src/vendor/a.c"));
+        assert!(matches(r"\.(rs|go)$", "src/main.go"));
+        assert!(!matches(r"\.(rs|go)$", "src/main.gox"));
+        // at the top level too, and with an empty alternative
+        assert!(matches("^a$|^b$", "b"));
+        assert!(matches("^x(|y)z$", "xz"));
+        assert!(matches("^$", ""));
+        assert!(!matches("^$", "a"));
+    }
+
+    #[test]
+    fn empty_loops_terminate() {
+        assert!(matches("^(a*)*$", "aaaa"));
+        assert!(!matches("^(a*)*$", "aaab"));
+        assert!(matches("^(a?)+b$", "b"));
+        assert!(matches("^(a|)*b", "aab"));
+    }
+
+    #[test]
+    fn long_text_is_matched_without_recursion() {
+        let re = Regex::new(r"Copyright \d+ .*x").unwrap();
+        let line = format!("// Copyright 2024 {}", "a".repeat(200_000));
+        assert!(!re.is_match(&line));
+        assert!(re.is_match(&format!("{line}x")));
+        // the exponential case of a backtracking matcher
+        let nested = Regex::new("^(a*)*b$").unwrap();
+        assert!(!nested.is_match(&"a".repeat(10_000)));
+    }
+
+    #[test]
+    fn malformed_patterns_are_errors() {
+        for (pattern, what) in [
+            ("(ab", "unclosed '('"),
+            ("ab)", "unmatched ')'"),
+            ("[ab", "unclosed '['"),
+            ("[z-a]", "reversed range in '[ ]'"),
+            ("*a", "nothing to repeat"),
+            ("a|+", "nothing to repeat"),
+            ("a{2", "unclosed '{'"),
+            ("a{x}", "bad '{n,m}'"),
+            ("a{3,1}", "bad '{n,m}'"),
+            ("a\\", "trailing '\\'"),
+            ("((a{100}){100}){100}", "too large"),
+        ] {
+            let err = Regex::new(pattern).err();
+            assert_eq!(err, Some(format!("Invalid regex {}: {}", pattern, what)));
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! comment styles and the extension table
+
+/// how a header is commented: block markers or a line prefix, and the
+/// blank lines after it
+///
+/// built-in styles are `'static`; one customized by `--prefix` borrows the
+/// string from its [`Config`](crate::Config)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LanguageProfile<'a> {
+    /// comment start
+    /// e.g., "/*" or "" (line comment)
+    pub(crate) start: &'a str,
+
+    /// comment prefix
+    /// e.g., " * " or "// "
+    pub(crate) prefix: &'a str,
+
+    /// prefix of the first license line, where that differs (Javadoc-style
+    /// `" ** "`); the same as `prefix` in every built-in style
+    pub(crate) first_line_prefix: &'a str,
+
+    /// comment end
+    /// e.g., " */\n" or "" (line comment)
+    pub(crate) end: &'a str,
+
+    /// per-line suffix, appended after each license line
+    /// e.g., " *" for box banners, "" for everything built in
+    pub(crate) suffix: &'a str,
+
+    /// box the text (`--style banner`): `=` rules above and below it and
+    /// `| ` ... ` |` around each line, padded to the widest one
+    pub(crate) banner: bool,
+
+    /// blank lines between the header and the code
+    /// the idempotency check expects exactly this many
+    pub(crate) blank_lines: usize,
+
+    /// a leading `---` YAML front-matter block stays above the header
+    /// (Markdown), like a shebang
+    pub(crate) front_matter: bool,
+
+    /// a first line starting with this (any case) stays above the header,
+    /// like a shebang: `@echo off` in batch files; "" for none
+    pub(crate) leading_line: &'static str,
+}
+
+/// an owned copy of a style, for the header cache, which outlives the
+/// [`Config`](crate::Config) strings a customized style borrows
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct StyleKey {
+    strings: [String; 6],
+    banner: bool,
+    blank_lines: usize,
+    front_matter: bool,
+}
+
+impl From<LanguageProfile<'_>> for StyleKey {
+    fn from(style: LanguageProfile<'_>) -> Self {
+        let LanguageProfile {
+            start,
+            prefix,
+            first_line_prefix,
+            end,
+            suffix,
+            banner,
+            blank_lines,
+            front_matter,
+            leading_line,
+        } = style;
+        StyleKey {
+            strings: [start, prefix, first_line_prefix, end, suffix, leading_line]
+                .map(str::to_string),
+            banner,
+            blank_lines,
+            front_matter,
+        }
+    }
+}
+
+/// comment styles
+pub(crate) const STYLE_C_LIKE: LanguageProfile<'static> = LanguageProfile {
+    start: "/*\n",
+    prefix: " * ",
+    first_line_prefix: " * ",
+    end: " */\n",
+    suffix: "",
+    banner: false,
+    blank_lines: 1,
+    front_matter: false,
+    leading_line: "",
+};
+pub(crate) const STYLE_HASH: LanguageProfile<'static> = LanguageProfile {
+    start: "",
+    prefix: "# ",
+    first_line_prefix: "# ",
+    end: "",
+    suffix: "",
+    banner: false,
+    blank_lines: 1,
+    front_matter: false,
+    leading_line: "",
+}; // Python, Shell, Ruby
+pub(crate) const STYLE_DOUBLE_SLASH: LanguageProfile<'static> = LanguageProfile {
+    start: "",
+    prefix: "// ",
+    first_line_prefix: "// ",
+    end: "",
+    suffix: "",
+    banner: false,
+    blank_lines: 1,
+    front_matter: false,
+    leading_line: "",
+}; // Rust, Go, Java (line mode)
+pub(crate) const STYLE_DASH: LanguageProfile<'static> = LanguageProfile {
+    start: "",
+    prefix: "-- ",
+    first_line_prefix: "-- ",
+    end: "",
+    suffix: "",
+    banner: false,
+    blank_lines: 1,
+    front_matter: false,
+    leading_line: "",
+}; // Lua, Haskell, SQL
+
+pub(crate) const STYLE_HTML: LanguageProfile = LanguageProfile {
+    start: "<!--\n",
+    prefix: "  ",
+    first_line_prefix: "  ",
+    end: "-->\n",
+    suffix: "",
+    banner: false,
+    blank_lines: 1,
+    front_matter: true,
+    leading_line: "",
+}; // Markdown
+
+pub(crate) const STYLE_BATCH: LanguageProfile<'static> = LanguageProfile {
+    start: "",
+    prefix: "REM ",
+    first_line_prefix: "REM ",
+    end: "",
+    suffix: "",
+    banner: false,
+    blank_lines: 1,
+    front_matter: false,
+    leading_line: "@echo off",
+}; // Windows batch
+
+/// no comment markers at all: the license text verbatim (`--prepend-raw`)
+pub(crate) const STYLE_RAW: LanguageProfile<'static> = LanguageProfile {
+    start: "",
+    prefix: "",
+    first_line_prefix: "",
+    end: "",
+    suffix: "",
+    banner: false,
+    blank_lines: 1,
+    front_matter: false,
+    leading_line: "",
+};
+
+/// a line style whose lines start with `prefix`, the `--line-comment`
+/// marker and a space (`"// "` for `zig=//`)
+pub(crate) fn line_comment_style(prefix: &str) -> LanguageProfile<'_> {
+    LanguageProfile {
+        prefix,
+        first_line_prefix: prefix,
+        ..STYLE_DOUBLE_SLASH
+    }
+}
+
+/// every style `get_language_style` can hand out
+pub(crate) const BUILTIN_STYLES: [LanguageProfile<'static>; 6] = [
+    STYLE_C_LIKE,
+    STYLE_HASH,
+    STYLE_DOUBLE_SLASH,
+    STYLE_DASH,
+    STYLE_HTML,
+    STYLE_BATCH,
+];
+
+/// the built-in styles by their `--force-style` name
+pub(crate) const STYLE_NAMES: [(&str, LanguageProfile); 4] = [
+    ("c-like", STYLE_C_LIKE),
+    ("hash", STYLE_HASH),
+    ("double-slash", STYLE_DOUBLE_SLASH),
+    ("dash", STYLE_DASH),
+];
+
+/// can a file of extension `ext` (`""` for a shebang script), natively
+/// commented in `native`, carry a header in `style` instead?
+///
+/// every `//` language also takes `/* */`, and so does SQL; every `/* */`
+/// language but CSS also takes `//`
+pub(crate) fn accepts_style(ext: &str, native: LanguageProfile, style: LanguageProfile) -> bool {
+    if native == style {
+        return true;
+    }
+    match style {
+        STYLE_C_LIKE => native == STYLE_DOUBLE_SLASH || ext == "sql",
+        STYLE_DOUBLE_SLASH => native == STYLE_C_LIKE && ext != "css",
+        _ => false,
+    }
+}
+
+impl LanguageProfile<'static> {
+    /// the style lice picks for files with extension `ext` (`"rs"`, `"py"`)
+    pub fn for_extension(ext: &str) -> Option<Self> {
+        get_language_style(ext)
+    }
+
+    /// a built-in style by its `--force-style` name (`"hash"`, `"c-like"`)
+    pub fn named(name: &str) -> Option<Self> {
+        STYLE_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|&(_, style)| style)
+    }
+}
+
+pub(crate) fn get_language_style(ext: &str) -> Option<LanguageProfile<'static>> {
+    match ext {
+        "c" | "h" | "cpp" | "hpp" | "css" => Some(STYLE_C_LIKE),
+        // JSONC/JSON5 allow comments; plain `.json` forbids them, a header
+        // would break every parser reading it, so it stays unsupported
+        "rs" | "go" | "java" | "js" | "ts" | "jsonc" | "json5" => Some(STYLE_DOUBLE_SLASH),
+        "py" | "sh" | "rb" | "pl" | "pm" | "yaml" | "toml" => Some(STYLE_HASH),
+        "lua" | "hs" | "sql" => Some(STYLE_DASH),
+        "md" | "markdown" => Some(STYLE_HTML),
+        "bat" | "cmd" => Some(STYLE_BATCH),
+        _ => None, // not supported
+    }
+}
+
+/// style for a script run by `interpreter` (`python3.11`, `bash`, ..), for
+/// files without an extension
+pub(crate) fn get_interpreter_style(interpreter: &str) -> Option<LanguageProfile<'static>> {
+    // python3.11 -> python
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match name {
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "python" | "ruby" | "perl" => Some(STYLE_HASH),
+        "node" => Some(STYLE_DOUBLE_SLASH),
+        "lua" => Some(STYLE_DASH),
+        _ => None,
+    }
+}
+
+/// the interpreter named by a `#!` line, looking through `env` and its flags
+///
+/// `#!/usr/bin/env -S python3 -u` gives `python3`
+pub(crate) fn shebang_interpreter(line: &str) -> Option<&str> {
+    // `#![...]` is a Rust inner attribute, not an interpreter
+    let rest = line
+        .strip_prefix("#!")
+        .filter(|rest| !rest.starts_with('['))?;
+    let mut words = rest.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program != "env" {
+        return Some(program);
+    }
+    words.find(|w| !w.starts_with('-') && !w.contains('='))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jsonc_and_json5_take_comments_but_json_does_not() {
+        assert_eq!(get_language_style("jsonc"), Some(STYLE_DOUBLE_SLASH));
+        assert_eq!(get_language_style("json5"), Some(STYLE_DOUBLE_SLASH));
+        assert_eq!(get_language_style("json"), None);
+    }
+
+    #[test]
+    fn shebangs_name_their_interpreter_through_env() {
+        assert_eq!(shebang_interpreter("#!/bin/sh\n"), Some("sh"));
+        assert_eq!(
+            shebang_interpreter("#!/usr/bin/env -S PYTHONUNBUFFERED=1 python3 -u"),
+            Some("python3")
+        );
+        assert_eq!(shebang_interpreter("print(1)"), None);
+        assert_eq!(get_interpreter_style("python3.11"), Some(STYLE_HASH));
+        assert_eq!(get_interpreter_style("node"), Some(STYLE_DOUBLE_SLASH));
+        assert_eq!(get_interpreter_style("lua5.4"), Some(STYLE_DASH));
+        assert_eq!(get_interpreter_style("tclsh"), None);
+    }
+
+    #[test]
+    fn ext_style_overrides_the_built_in_mapping() {
+        use crate::Config;
+        assert_eq!(get_language_style("sql"), Some(STYLE_DASH));
+        let args = ["-f", "H", "--ext-style", "sql=c-like", "."];
+        let config = Config::from_args(args.map(String::from)).unwrap();
+        assert_eq!(config.style_for_ext("sql"), Some(STYLE_C_LIKE));
+        // the other dash languages keep theirs
+        assert_eq!(config.style_for_ext("lua"), Some(STYLE_DASH));
+        let both = [
+            "-f",
+            "H",
+            "--ext-style",
+            "sql=hash",
+            "--line-comment",
+            "sql=%",
+            ".",
+        ];
+        assert!(Config::from_args(both.map(String::from)).is_err());
+    }
+
+    #[test]
+    fn batch_files_use_rem() {
+        assert_eq!(get_language_style("bat"), Some(STYLE_BATCH));
+        assert_eq!(get_language_style("cmd"), Some(STYLE_BATCH));
+        assert_eq!(STYLE_BATCH.prefix, "REM ");
+    }
+}
@@ -0,0 +1,712 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! `--archive`: zip files rewritten in place (feature `zip`), std only
+//!
+//! entries are found through the central directory; stored and deflated
+//! ones can be read. there is no deflate in std, so an entry that gets a
+//! header is written back stored (uncompressed). everything else, the
+//! untouched entries, names, times, attributes, extra fields and comments,
+//! is copied byte for byte. zip64 is refused, encrypted entries are left
+//! alone.
+
+use std::io;
+
+const LOCAL_SIG: u32 = 0x0403_4b50;
+const CENTRAL_SIG: u32 = 0x0201_4b50;
+const END_SIG: u32 = 0x0605_4b50;
+const DESCRIPTOR_SIG: u32 = 0x0807_4b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// general purpose flags
+const FLAG_ENCRYPTED: u16 = 0x0001;
+const FLAG_DESCRIPTOR: u16 = 0x0008;
+
+/// fixed sizes of the records, before their variable parts
+const LOCAL_LEN: usize = 30;
+const CENTRAL_LEN: usize = 46;
+const END_LEN: usize = 22;
+
+#[derive(Debug)]
+pub(crate) struct Archive {
+    entries: Vec<Entry>,
+    /// the archive comment at the very end
+    comment: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub(crate) name: String,
+    /// the central directory record, name, extra field and comment included
+    central: Vec<u8>,
+    /// the whole local record: header, data and any data descriptor
+    local: Vec<u8>,
+    /// where the (compressed) data starts in `local`
+    data_start: usize,
+    data_len: usize,
+}
+
+impl Archive {
+    pub(crate) fn read(bytes: &[u8]) -> io::Result<Self> {
+        let end = find_end(bytes)?;
+        let count = u16_at(bytes, end + 10)?;
+        let directory = u32_at(bytes, end + 16)?;
+        if count == u16::MAX || directory == u32::MAX {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "zip64 archives are not supported",
+            ));
+        }
+        let comment_len = u16_at(bytes, end + 20)? as usize;
+        let comment = slice(bytes, end + END_LEN, comment_len)?.to_vec();
+
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut pos = directory as usize;
+        for _ in 0..count {
+            if u32_at(bytes, pos)? != CENTRAL_SIG {
+                return Err(invalid("bad central directory record"));
+            }
+            let name_len = u16_at(bytes, pos + 28)? as usize;
+            let record_len = CENTRAL_LEN
+                + name_len
+                + u16_at(bytes, pos + 30)? as usize
+                + u16_at(bytes, pos + 32)? as usize;
+            let central = slice(bytes, pos, record_len)?.to_vec();
+            let name =
+                String::from_utf8_lossy(slice(&central, CENTRAL_LEN, name_len)?).into_owned();
+            let flags = u16_at(&central, 8)?;
+            let data_len = u32_at(&central, 20)? as usize;
+
+            let offset = u32_at(&central, 42)? as usize;
+            if u32_at(bytes, offset)? != LOCAL_SIG {
+                return Err(invalid("bad local file header"));
+            }
+            let data_start = LOCAL_LEN
+                + u16_at(bytes, offset + 26)? as usize
+                + u16_at(bytes, offset + 28)? as usize;
+            let mut local_len = data_start + data_len;
+            if flags & FLAG_DESCRIPTOR != 0 {
+                // crc and both sizes, after an optional signature
+                local_len += match u32_at(bytes, offset + local_len) {
+                    Ok(DESCRIPTOR_SIG) => 16,
+                    _ => 12,
+                };
+            }
+            entries.push(Entry {
+                name,
+                central,
+                local: slice(bytes, offset, local_len)?.to_vec(),
+                data_start,
+                data_len,
+            });
+            pos += record_len;
+        }
+        Ok(Archive { entries, comment })
+    }
+
+    pub(crate) fn entries_mut(&mut self) -> &mut [Entry] {
+        &mut self.entries
+    }
+
+    /// the archive as bytes, the local records first, then the directory
+    pub(crate) fn write(&self) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            let offset = to_u32(out.len())?;
+            out.extend_from_slice(&entry.local);
+            let mut central = entry.central.clone();
+            central[42..46].copy_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(&central);
+        }
+        let count = u16::try_from(self.entries.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::Unsupported, "too many entries"))?;
+        let directory_start = to_u32(out.len())?;
+        out.extend_from_slice(&directory);
+
+        out.extend_from_slice(&END_SIG.to_le_bytes());
+        out.extend_from_slice(&[0; 4]); // this disk, the directory's disk
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&to_u32(directory.len())?.to_le_bytes());
+        out.extend_from_slice(&directory_start.to_le_bytes());
+        out.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.comment);
+        Ok(out)
+    }
+}
+
+impl Entry {
+    pub(crate) fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    /// the uncompressed data, `None` if encrypted or compressed some other way
+    pub(crate) fn contents(&self) -> io::Result<Option<Vec<u8>>> {
+        let flags = u16_at(&self.central, 8)?;
+        let data = &self.local[self.data_start..self.data_start + self.data_len];
+        let out = match u16_at(&self.central, 10)? {
+            _ if flags & FLAG_ENCRYPTED != 0 => return Ok(None),
+            STORED => data.to_vec(),
+            DEFLATED => inflate(data)?,
+            _ => return Ok(None),
+        };
+        if crc32(&out) != u32_at(&self.central, 16)? {
+            return Err(invalid(&format!("{}: CRC mismatch", self.name)));
+        }
+        Ok(Some(out))
+    }
+
+    /// swap in new contents, stored; time, name and attributes stay
+    pub(crate) fn replace(&mut self, data: &[u8]) -> io::Result<()> {
+        let crc = crc32(data).to_le_bytes();
+        let size = to_u32(data.len())?.to_le_bytes();
+        let flags = (u16_at(&self.local, 6)? & !FLAG_DESCRIPTOR).to_le_bytes();
+
+        let mut local = Vec::with_capacity(self.data_start + data.len());
+        local.extend_from_slice(&LOCAL_SIG.to_le_bytes());
+        local.extend_from_slice(&self.local[4..6]); // version needed
+        local.extend_from_slice(&flags);
+        local.extend_from_slice(&STORED.to_le_bytes());
+        local.extend_from_slice(&self.local[10..14]); // time, date
+        local.extend_from_slice(&crc);
+        local.extend_from_slice(&size);
+        local.extend_from_slice(&size);
+        local.extend_from_slice(&self.local[26..self.data_start]); // name, extra
+        local.extend_from_slice(data);
+        self.local = local;
+        self.data_len = data.len();
+
+        let flags = (u16_at(&self.central, 8)? & !FLAG_DESCRIPTOR).to_le_bytes();
+        self.central[8..10].copy_from_slice(&flags);
+        self.central[10..12].copy_from_slice(&STORED.to_le_bytes());
+        self.central[16..20].copy_from_slice(&crc);
+        self.central[20..24].copy_from_slice(&size);
+        self.central[24..28].copy_from_slice(&size);
+        Ok(())
+    }
+}
+
+/// offset of the end-of-central-directory record, searched backwards past
+/// a comment of up to 64 KiB
+fn find_end(bytes: &[u8]) -> io::Result<usize> {
+    let last = bytes
+        .len()
+        .checked_sub(END_LEN)
+        .ok_or_else(|| invalid("not a zip archive"))?;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last)
+        .rev()
+        .find(|&pos| {
+            u32_at(bytes, pos).is_ok_and(|sig| sig == END_SIG)
+                && u16_at(bytes, pos + 20)
+                    .is_ok_and(|len| pos + END_LEN + len as usize == bytes.len())
+        })
+        .ok_or_else(|| invalid("not a zip archive"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn slice(bytes: &[u8], start: usize, len: usize) -> io::Result<&[u8]> {
+    bytes
+        .get(start..start + len)
+        .ok_or_else(|| invalid("truncated zip archive"))
+}
+
+fn u16_at(bytes: &[u8], pos: usize) -> io::Result<u16> {
+    let b = slice(bytes, pos, 2)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> io::Result<u32> {
+    let b = slice(bytes, pos, 4)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn to_u32(n: usize) -> io::Result<u32> {
+    u32::try_from(n).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "archive too large (zip64 is not supported)",
+        )
+    })
+}
+
+/// CRC-32 as zip uses it (IEEE, reflected)
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, slot) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *slot = c;
+    }
+    !data.iter().fold(!0u32, |crc, &b| {
+        table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+// ============================
+// inflate (RFC 1951)
+// ============================
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// the order code length code lengths come in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// bits from least significant first, as deflate packs them
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid("truncated deflate stream"))?;
+            self.pos += 1;
+            self.buf |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buf & ((1u32 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// drop the rest of the current byte
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+/// a canonical Huffman code: how many codes of each length, and the
+/// symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        // more codes of some length than there is room for
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid("bad Huffman code in deflate stream"));
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.take(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("bad code in deflate stream"))
+    }
+}
+
+fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        buf: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => stored_block(&mut bits, &mut out)?,
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths)?;
+                let distances = Huffman::new(&[5; 30])?;
+                codes(&mut bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut bits)?;
+                codes(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(invalid("bad block type in deflate stream")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn stored_block(bits: &mut Bits, out: &mut Vec<u8>) -> io::Result<()> {
+    bits.align();
+    let header = slice(bits.data, bits.pos, 4)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let check = u16::from_le_bytes([header[2], header[3]]);
+    if len != !check {
+        return Err(invalid("bad stored block in deflate stream"));
+    }
+    out.extend_from_slice(slice(bits.data, bits.pos + 4, len as usize)?);
+    bits.pos += 4 + len as usize;
+    Ok(())
+}
+
+fn dynamic_tables(bits: &mut Bits) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &slot in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[slot] = bits.take(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| invalid("repeat with no previous length"))?;
+                (previous, 3 + bits.take(2)?)
+            }
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid("too many lengths in deflate stream"));
+    }
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals)?, Huffman::new(distances)?))
+}
+
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(invalid("bad length in deflate stream"));
+                }
+                let len =
+                    LENGTH_BASE[index] as usize + bits.take(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(bits)? as usize;
+                if index >= DIST_BASE.len() {
+                    return Err(invalid("bad distance in deflate stream"));
+                }
+                let dist =
+                    DIST_BASE[index] as usize + bits.take(DIST_EXTRA[index] as u32)? as usize;
+                if dist > out.len() {
+                    return Err(invalid("distance too far back in deflate stream"));
+                }
+                // may overlap what it copies, so byte by byte
+                let start = out.len() - dist;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Copyright 2024 New\n`, one fixed Huffman block (zlib, Z_FIXED)
+    const FIXED: [u8; 21] = [
+        0x73, 0xce, 0x2f, 0xa8, 0x2c, 0xca, 0x4c, 0xcf, 0x28, 0x51, 0x30, 0x32, 0x30, 0x32, 0x51,
+        0xf0, 0x4b, 0x2d, 0xe7, 0x02, 0x00,
+    ];
+    /// `ab_text()`, one dynamic Huffman block with back references (zlib -9)
+    const DYNAMIC: [u8; 69] = [
+        0x55, 0x8e, 0x89, 0x0d, 0x00, 0x30, 0x08, 0x02, 0x67, 0x85, 0xfd, 0x87, 0xa8, 0xb9, 0x42,
+        0x1f, 0x6d, 0x02, 0x51, 0x84, 0x4a, 0x9e, 0x92, 0x35, 0x28, 0xf8, 0x80, 0x18, 0x41, 0x98,
+        0x7f, 0xb3, 0xfd, 0xb6, 0x14, 0xce, 0x96, 0xc2, 0xc8, 0x91, 0xe6, 0xf0, 0xed, 0x42, 0x1d,
+        0x8e, 0x77, 0x76, 0xbd, 0x8b, 0xb7, 0x6f, 0x46, 0xec, 0x9d, 0x7f, 0x5e, 0xe9, 0x61, 0x4d,
+        0xaf, 0xbc, 0x04, 0xc5, 0x43, 0x93, 0xdc, 0xc5, 0x02,
+    ];
+    /// `stored block\n`, one stored block (zlib -0)
+    const STORED_BLOCK: [u8; 18] = [
+        0x01, 0x0d, 0x00, 0xf2, 0xff, 0x73, 0x74, 0x6f, 0x72, 0x65, 0x64, 0x20, 0x62, 0x6c, 0x6f,
+        0x63, 0x6b, 0x0a,
+    ];
+
+    /// 256 pseudo-random `a`s and `b`s, which zlib codes dynamically
+    fn ab_text() -> Vec<u8> {
+        let mut x: u32 = 1;
+        (0..256)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345) & 0x7fff_ffff;
+                if (x >> 16) & 1 == 0 { b'a' } else { b'b' }
+            })
+            .collect()
+    }
+
+    /// a zip of `(name, method, data as stored, uncompressed data)`, laid
+    /// out as `Archive::write` does, with an archive comment
+    fn zip_of(entries: &[(&str, u16, &[u8], &[u8])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut directory = Vec::new();
+        for &(name, method, data, plain) in entries {
+            let offset = out.len() as u32;
+            let mut common = Vec::new();
+            common.extend_from_slice(&20u16.to_le_bytes()); // version needed
+            common.extend_from_slice(&0u16.to_le_bytes()); // flags
+            common.extend_from_slice(&method.to_le_bytes());
+            common.extend_from_slice(&[0x34, 0x12, 0x78, 0x56]); // time, date
+            common.extend_from_slice(&crc32(plain).to_le_bytes());
+            common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(plain.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            common.extend_from_slice(&0u16.to_le_bytes()); // extra
+
+            out.extend_from_slice(&LOCAL_SIG.to_le_bytes());
+            out.extend_from_slice(&common);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(data);
+
+            directory.extend_from_slice(&CENTRAL_SIG.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes()); // made by
+            directory.extend_from_slice(&common);
+            directory.extend_from_slice(&[0; 10]); // comment, disk, attributes
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_start = out.len() as u32;
+        out.extend_from_slice(&directory);
+        out.extend_from_slice(&END_SIG.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&directory_start.to_le_bytes());
+        out.extend_from_slice(&4u16.to_le_bytes());
+        out.extend_from_slice(b"note");
+        out
+    }
+
+    fn sample() -> Vec<u8> {
+        let ab = ab_text();
+        zip_of(&[
+            ("src/a.py", STORED, b"x = 1\n", b"x = 1\n"),
+            ("src/", STORED, b"", b""),
+            ("HEADER.txt", DEFLATED, &FIXED, b"Copyright 2024 New\n"),
+            ("ab.txt", DEFLATED, &DYNAMIC, &ab),
+        ])
+    }
+
+    #[test]
+    fn inflates_fixed_dynamic_and_stored_blocks() {
+        assert_eq!(inflate(&FIXED).unwrap(), b"Copyright 2024 New\n");
+        assert_eq!(inflate(&DYNAMIC).unwrap(), ab_text());
+        assert_eq!(inflate(&STORED_BLOCK).unwrap(), b"stored block\n");
+        assert_eq!(crc32(b"Copyright 2024 New\n"), 0xc2c2_0660);
+        assert_eq!(crc32(&ab_text()), 0x24c0_9c52);
+    }
+
+    #[test]
+    fn broken_deflate_streams_are_errors() {
+        for stream in [&FIXED[..], &DYNAMIC, &STORED_BLOCK] {
+            for len in 0..stream.len() - 1 {
+                assert!(
+                    inflate(&stream[..len]).is_err(),
+                    "{len} of {}",
+                    stream.len()
+                );
+            }
+        }
+        // block type 3
+        assert!(inflate(&[0x07]).is_err());
+        // a stored block whose length doesn't match its complement
+        assert!(inflate(&[0x01, 0x0d, 0x00, 0xf3, 0xff]).is_err());
+        // flipped bits anywhere decode to garbage or fail, but never panic
+        for i in 0..DYNAMIC.len() {
+            let mut damaged = DYNAMIC;
+            damaged[i] ^= 0x5a;
+            let _ = inflate(&damaged);
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_and_only_the_replaced_one_changes() {
+        let bytes = sample();
+        let mut archive = Archive::read(&bytes).unwrap();
+        let names: Vec<&str> = archive.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["src/a.py", "src/", "HEADER.txt", "ab.txt"]);
+        assert!(archive.entries[1].is_dir());
+        let contents: Vec<Vec<u8>> = archive
+            .entries
+            .iter()
+            .map(|e| e.contents().unwrap().unwrap())
+            .collect();
+        assert_eq!(contents[0], b"x = 1\n");
+        assert_eq!(contents[2], b"Copyright 2024 New\n");
+        assert_eq!(contents[3], ab_text());
+        // nothing replaced, nothing moved
+        assert_eq!(archive.write().unwrap(), bytes);
+
+        let licensed = b"# Copyright 2024 New\n\nx = 1\n";
+        archive.entries_mut()[0].replace(licensed).unwrap();
+        archive.entries_mut()[3].replace(b"ab\n").unwrap();
+        let written = archive.write().unwrap();
+        let reread = Archive::read(&written).unwrap();
+        assert_eq!(reread.entries[0].contents().unwrap().unwrap(), licensed);
+        assert_eq!(reread.entries[3].contents().unwrap().unwrap(), b"ab\n");
+        // replaced entries are stored, the others copied byte for byte
+        assert_eq!(u16_at(&reread.entries[3].central, 10).unwrap(), STORED);
+        assert_eq!(reread.entries[2].local, archive.entries[2].local);
+        assert_eq!(
+            reread.entries[2].central[..42],
+            archive.entries[2].central[..42]
+        );
+        assert_eq!(reread.comment, b"note");
+    }
+
+    #[test]
+    fn a_crc_mismatch_is_reported() {
+        let mut bytes = sample();
+        let directory = u32_at(&bytes, bytes.len() - 4 - 6).unwrap() as usize;
+        // the CRC of the first entry in the central directory
+        bytes[directory + 16] ^= 1;
+        let archive = Archive::read(&bytes).unwrap();
+        let error = archive.entries[0].contents().unwrap_err();
+        assert_eq!(error.to_string(), "src/a.py: CRC mismatch");
+        assert!(archive.entries[2].contents().is_ok());
+    }
+
+    #[test]
+    fn zip64_and_damaged_archives_are_refused() {
+        let bytes = sample();
+        let end = bytes.len() - END_LEN - 4;
+        let mut zip64 = bytes.clone();
+        zip64[end + 10..end + 12].copy_from_slice(&u16::MAX.to_le_bytes());
+        let error = Archive::read(&zip64).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+
+        for len in 0..bytes.len() {
+            assert!(Archive::read(&bytes[..len]).is_err(), "{len}");
+        }
+        // a directory that points past the end
+        let mut moved = bytes.clone();
+        moved[end + 16..end + 20].copy_from_slice(&u32::MAX.wrapping_sub(1).to_le_bytes());
+        assert!(Archive::read(&moved).is_err());
+        assert!(Archive::read(b"PK\x05\x06 definitely not").is_err());
+    }
+
+    #[test]
+    fn archive_mode_licenses_source_entries_in_place() {
+        let dir = std::env::temp_dir().join(format!("lice-zip-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let header = dir.join("HEADER.txt");
+        std::fs::write(&header, "Copyright 2024 New\n").unwrap();
+        let path = dir.join("src.zip");
+        std::fs::write(&path, sample()).unwrap();
+
+        let run = |extra: &[&str]| {
+            let mut args = vec!["-f", header.to_str().unwrap(), "-q"];
+            args.extend(extra);
+            args.extend(["--archive", path.to_str().unwrap()]);
+            let config = crate::Config::from_args(args.iter().map(|a| a.to_string())).unwrap();
+            crate::LiceEngine::new(config).unwrap().run().unwrap()
+        };
+        // --check reports the missing header and leaves the archive alone
+        run(&["--check"]);
+        assert_eq!(std::fs::read(&path).unwrap(), sample());
+
+        assert_eq!(run(&[]).added, 1);
+        let archive = Archive::read(&std::fs::read(&path).unwrap()).unwrap();
+        let a = archive.entries[0].contents().unwrap().unwrap();
+        assert!(
+            String::from_utf8(a)
+                .unwrap()
+                .starts_with("# Copyright 2024 New\n")
+        );
+        assert_eq!(archive.entries[3].contents().unwrap().unwrap(), ab_text());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
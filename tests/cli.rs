@@ -0,0 +1,786 @@
+//    Copyright 2025 Karesis
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! the `lice` binary end to end, for what needs a real stdin or exit code
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{fs, process};
+
+/// a fresh, empty directory under the system temp dir
+fn scratch() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("lice-cli-{}-{n}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// run `lice args...` with `input` on stdin
+fn lice(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lice"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn warnings_are_json_records_under_format_json() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let file = dir.join("notes.xyz");
+    fs::write(&file, "x\n").unwrap();
+    let template = template.to_str().unwrap();
+    let file = file.to_str().unwrap();
+
+    let output = lice(&["-f", template, file], "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("[WARN] Ignoring unsupported file type:"),
+        "{stderr}"
+    );
+
+    let output = lice(&["--format", "json", "-f", template, file], "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim_end(),
+        format!(
+            "{{\"level\":\"warn\",\"path\":\"{}\",\"message\":\"Ignoring unsupported file type\"}}",
+            file
+        )
+    );
+    // and no text banner on stdout to get in the way, only JSON
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().all(|line| line.starts_with('{')), "{stdout}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn header_from_warning_follows_the_output_format() {
+    let dir = scratch();
+    let sample = dir.join("sample.py");
+    // no space after '#': rendering the text back adds one
+    fs::write(&sample, "#Copyright 2024 New\n\nx = 1\n").unwrap();
+    let file = dir.join("a.py");
+    fs::write(&file, "y = 2\n").unwrap();
+    let sample = sample.to_str().unwrap();
+    let file = file.to_str().unwrap();
+
+    let output = lice(&["--header-from", sample, file], "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.starts_with("[WARN] --header-from:"), "{stderr}");
+    assert!(stderr.contains("sample.py"), "{stderr}");
+    assert_eq!(
+        fs::read_to_string(file).unwrap(),
+        "# Copyright 2024 New\n\ny = 2\n"
+    );
+
+    let output = lice(&["--format", "json", "--header-from", sample, file], "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let line = stderr.lines().next().unwrap_or_default();
+    assert!(
+        line.starts_with("{\"level\":\"warn\",\"path\":"),
+        "{stderr}"
+    );
+    assert!(line.contains("round-trip"), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rewritten_files_say_whether_the_header_was_added_or_updated() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let src = dir.join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(src.join("a.py"), "x = 1\n").unwrap();
+    fs::write(src.join("b.py"), "# Copyright 2020 Old\n\nx = 2\n").unwrap();
+    fs::write(src.join("c.py"), "# Copyright 2024 New\n\nx = 3\n").unwrap();
+
+    let output = lice(
+        &[
+            "-j",
+            "1",
+            "-f",
+            template.to_str().unwrap(),
+            src.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let outcome = |name: &str| {
+        stdout
+            .lines()
+            .find(|line| line.contains(name))
+            .map(|line| line.split(':').next().unwrap().trim().to_string())
+    };
+    assert_eq!(outcome("a.py").as_deref(), Some("License added"));
+    assert_eq!(outcome("b.py").as_deref(), Some("License updated"));
+    assert_eq!(outcome("c.py").as_deref(), Some("License OK"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn piped_template_gets_the_year_filled_in() {
+    let dir = scratch();
+    let file = dir.join("a.py");
+    fs::write(&file, "x = 1\n").unwrap();
+    let output = lice(
+        &["-f", "-", file.to_str().unwrap()],
+        "Copyright {year} Core\n",
+    );
+    assert!(output.status.success());
+    let written = fs::read_to_string(&file).unwrap();
+    let year = written
+        .strip_prefix("# Copyright ")
+        .and_then(|rest| rest.strip_suffix(" Core\n\nx = 1\n"))
+        .unwrap_or_else(|| panic!("unexpected result: {written:?}"));
+    assert!(year.parse::<u32>().is_ok_and(|year| year >= 2025), "{year}");
+
+    // stdin can't carry both the template and the path list
+    let output = lice(&["-f", "-", "--stdin0"], "");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("-f - conflicts"),
+        "{:?}",
+        output
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dump_config_prints_resolved_settings_and_touches_nothing() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let file = dir.join("a.py");
+    fs::write(&file, "x = 1\n").unwrap();
+    let out = dir.join("out");
+
+    let output = lice(
+        &[
+            "--dump-config",
+            "-j",
+            "3",
+            "--out-dir",
+            out.to_str().unwrap(),
+            "-f",
+            template.to_str().unwrap(),
+            file.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("{\n") && stdout.ends_with("}\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("\n  \"jobs\": 3,\n"), "{stdout}");
+    // 64 slots per worker when --queue-cap is not given
+    assert!(stdout.contains("\n  \"queue_cap\": 192,\n"), "{stdout}");
+    assert!(stdout.contains("\"mode\": \"apply\""), "{stdout}");
+    assert!(
+        stdout.contains("{\"start\":\"\",\"prefix\":\"# \""),
+        "{stdout}"
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "x = 1\n");
+    assert!(!out.exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_target_warns_and_fails_only_under_strict() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let file = dir.join("a.py");
+    fs::write(&file, "x = 1\n").unwrap();
+    let missing = dir.join("nope.py");
+    let missing_dir = dir.join("gone");
+    let args = [
+        "-f",
+        template.to_str().unwrap(),
+        file.to_str().unwrap(),
+        missing.to_str().unwrap(),
+        missing_dir.to_str().unwrap(),
+    ];
+
+    let output = lice(&args, "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.contains("target not found"), "{stderr}");
+    assert!(stderr.contains("nope.py"), "{stderr}");
+    assert!(stderr.contains("gone"), "{stderr}");
+    // the rest of the run still happened
+    assert!(
+        fs::read_to_string(&file)
+            .unwrap()
+            .starts_with("# Copyright")
+    );
+
+    let output = lice(&[&args[..], &["--strict"]].concat(), "");
+    assert_eq!(output.status.code(), Some(1));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn errors_point_at_the_line_and_byte_in_both_formats() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let binary = dir.join("a.py");
+    fs::write(&binary, b"x = 1\ny = \xff\n").unwrap();
+    let unclosed = dir.join("b.c");
+    fs::write(&unclosed, "#!/usr/bin/tcc\n/*\n * never closed\nint x;\n").unwrap();
+    let args = [
+        "-j",
+        "1",
+        "-f",
+        template.to_str().unwrap(),
+        binary.to_str().unwrap(),
+        unclosed.to_str().unwrap(),
+    ];
+
+    let stderr = String::from_utf8(lice(&args, "").stderr).unwrap();
+    assert!(
+        stderr.contains("file is not valid UTF-8 (line 2, byte 10)"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Unclosed block comment, skipping (line 2, byte 15)"),
+        "{stderr}"
+    );
+
+    let output = lice(&[&args[..], &["--format", "json"]].concat(), "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("\"message\":\"file is not valid UTF-8\",\"line\":2,\"offset\":10}"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unsupported_types_are_summarized_once_per_run() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let src = dir.join("src");
+    fs::create_dir(&src).unwrap();
+    for name in ["a.xyz", "b.xyz", "c.lock"] {
+        fs::write(src.join(name), "x\n").unwrap();
+    }
+    let args = [
+        "--summarize-unsupported",
+        "-f",
+        template.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ];
+
+    let output = lice(&args, "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert_eq!(
+        stderr.trim_end(),
+        "[WARN] Unsupported file types: .xyz (2 files), .lock (1 file)"
+    );
+
+    let output = lice(&[&args[..], &["--format", "json"]].concat(), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).trim_end(),
+        "{\"level\":\"warn\",\"message\":\"Unsupported file types\",\"unsupported\":{\"xyz\":2,\"lock\":1}}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn throughput_line_counts_scanned_files_and_written_bytes() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let src = dir.join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(src.join("a.py"), "x = 1\n").unwrap();
+    fs::write(src.join("b.py"), "# Copyright 2024 New\n\ny = 2\n").unwrap();
+    let args = ["-f", template.to_str().unwrap(), src.to_str().unwrap()];
+
+    let stdout = String::from_utf8(lice(&args, "").stdout).unwrap();
+    let last = stdout.lines().last().unwrap();
+    // "# Copyright 2024 New\n\nx = 1\n" is the only file written
+    assert!(
+        last.starts_with("Scanned 2 files, wrote 28 B in "),
+        "{stdout}"
+    );
+
+    fs::write(src.join("a.py"), "x = 1\n").unwrap();
+    let json = [&args[..], &["--format", "json"]].concat();
+    let stdout = String::from_utf8(lice(&json, "").stdout).unwrap();
+    let last = stdout.lines().last().unwrap();
+    assert!(
+        last.starts_with("{\"scanned\":2,\"bytes_written\":28,\"elapsed_secs\":"),
+        "{stdout}"
+    );
+
+    let stdout = String::from_utf8(lice(&[&args[..], &["-q"]].concat(), "").stdout).unwrap();
+    assert!(!stdout.contains("Scanned"), "{stdout}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn explain_traces_one_file_and_writes_nothing() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let file = dir.join("run.py");
+    let content = "#!/usr/bin/env python3\n# Copyright 2020 Old\n\nx = 1\n";
+    fs::write(&file, content).unwrap();
+
+    let out = lice(
+        &[
+            "-f",
+            template.to_str().unwrap(),
+            "--explain",
+            file.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    for line in [
+        "extension: py",
+        "preamble: 1 line(s) kept above the header",
+        "existing header: line comments at line 2",
+        "--- new content ---\n#!/usr/bin/env python3\n# Copyright 2024 New\n\nx = 1\n--- end ---",
+    ] {
+        assert!(stdout.contains(line), "{line:?} in {stdout}");
+    }
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn environment_fills_in_for_jobs_and_queue_cap() {
+    let dump = |args: &[&str], env: &[(&str, &str)]| {
+        let out = Command::new(env!("CARGO_BIN_EXE_lice"))
+            .args(["-f", "-", "--dump-config", "."])
+            .args(args)
+            .env_remove("LICE_JOBS")
+            .env_remove("LICE_QUEUE_CAP")
+            .envs(env.iter().copied())
+            .output()
+            .unwrap();
+        (
+            out.status.success(),
+            String::from_utf8(out.stdout).unwrap(),
+            String::from_utf8(out.stderr).unwrap(),
+        )
+    };
+    let env = [("LICE_JOBS", "3"), ("LICE_QUEUE_CAP", "7")];
+    let (ok, stdout, _) = dump(&[], &env);
+    assert!(ok);
+    assert!(stdout.contains("\"jobs\": 3,"), "{stdout}");
+    assert!(stdout.contains("\"queue_cap\": 7,"), "{stdout}");
+
+    // flags win over the environment
+    let (_, stdout, _) = dump(&["-j", "2", "--queue-cap", "5"], &env);
+    assert!(stdout.contains("\"jobs\": 2,"), "{stdout}");
+    assert!(stdout.contains("\"queue_cap\": 5,"), "{stdout}");
+
+    let (ok, _, stderr) = dump(&[], &[("LICE_JOBS", "0")]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("Invalid LICE_JOBS: 0 (expected a positive integer)"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn count_lines_sums_the_header_lines_written() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\nAll rights reserved\n").unwrap();
+    let src = dir.join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(src.join("a.py"), "x = 1\n").unwrap();
+    fs::write(src.join("b.py"), "y = 2\n").unwrap();
+    let (template, src) = (template.to_str().unwrap(), src.to_str().unwrap());
+    let run = |extra: &[&str]| {
+        let out = lice(
+            &[&["-f", template, "-q", "--count-lines"], extra, &[src]].concat(),
+            "",
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let stdout = run(&[]);
+    assert!(
+        stdout.contains("Inserted 2 header lines into 2 files (4 lines)\n"),
+        "{stdout}"
+    );
+    // compliant files now, nothing inserted
+    let stdout = run(&["--format", "json"]);
+    assert!(
+        stdout.contains("{\"header_files\":0,\"header_lines\":0,\"max_header_lines\":0}\n"),
+        "{stdout}"
+    );
+    // a block comment is two lines longer than the line one
+    fs::write(dir.join("src/c.c"), "int x;\n").unwrap();
+    fs::write(dir.join("src/d.py"), "z = 3\n").unwrap();
+    let stdout = run(&[]);
+    assert!(
+        stdout.contains("Inserted up to 4 header lines into 2 files (6 lines)\n"),
+        "{stdout}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn verify_license_file_warns_and_shows_every_style() {
+    let sloppy = lice(
+        &["-f", "-", "--verify-license-file"],
+        "Copyright 2024 New \n\tAll rights reserved\n",
+    );
+    let stdout = String::from_utf8(sloppy.stdout).unwrap();
+    assert!(!sloppy.status.success());
+    assert!(stdout.contains("line 1: trailing whitespace"), "{stdout}");
+    assert!(stdout.contains("line 2: tab character"), "{stdout}");
+    assert!(stdout.contains("line 1: hard-coded year 2024"), "{stdout}");
+
+    let clean = lice(
+        &["-f", "-", "--verify-license-file"],
+        "Copyright {year} New\n",
+    );
+    let stdout = String::from_utf8(clean.stdout).unwrap();
+    assert!(clean.status.success(), "{stdout}");
+    assert!(
+        stdout.contains("--- hash (.py) ---\n# Copyright"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("--- batch (.bat) ---\nREM Copyright"),
+        "{stdout}"
+    );
+    assert!(stdout.ends_with("Template OK\n"));
+}
+
+#[test]
+fn ordered_output_matches_a_single_threaded_run() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let src = dir.join("src");
+    fs::create_dir(&src).unwrap();
+    for i in 0..60 {
+        fs::write(src.join(format!("f{i}.py")), "x = 1\n").unwrap();
+    }
+    let template = template.to_str().unwrap();
+    let src = src.to_str().unwrap();
+    // the per-file lines only, not the banner or the summary
+    let per_file = |jobs: &[&str]| {
+        let args = [
+            &["-f", template, "--check", "--format", "json"],
+            jobs,
+            &[src],
+        ]
+        .concat();
+        let output = lice(&args, "");
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter(|line| line.contains("\"path\""))
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+    let single = per_file(&["-j", "1"]);
+    assert_eq!(single.len(), 60);
+    for _ in 0..5 {
+        assert_eq!(per_file(&["-j", "4", "--ordered"]), single);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn git_filter_picks_tracked_or_untracked_files() {
+    let dir = scratch();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(&dir)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    };
+    fs::write(dir.join("HEADER.txt"), "Copyright 2024 New\n").unwrap();
+    fs::write(dir.join("tracked.py"), "x = 1\n").unwrap();
+    fs::write(dir.join("new.py"), "x = 2\n").unwrap();
+    fs::write(dir.join("ignored.py"), "x = 3\n").unwrap();
+    fs::write(dir.join(".gitignore"), "ignored.py\n").unwrap();
+    let run = |filter: &str| {
+        Command::new(env!("CARGO_BIN_EXE_lice"))
+            .args(["-f", "HEADER.txt", "--check", "--git-filter", filter, "."])
+            .current_dir(&dir)
+            .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap())
+            .output()
+            .unwrap()
+    };
+    // outside a repository the filter has nothing to ask
+    let refused = run("tracked");
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("not inside a git repository"));
+
+    git(&["init", "-q"]);
+    git(&["add", "tracked.py"]);
+    let missing = |filter: &str| {
+        let output = run(filter);
+        let mut files: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix(" License missing: "))
+            .map(String::from)
+            .collect();
+        files.sort();
+        files
+    };
+    assert_eq!(missing("tracked"), ["\"./tracked.py\""]);
+    // what .gitignore leaves out isn't untracked work either
+    assert_eq!(missing("untracked"), ["\"./new.py\""]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn count_by_language_breaks_the_summary_down_by_extension() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let src = dir.join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(src.join("a.rs"), "// Copyright 2024 New\n\nfn a() {}\n").unwrap();
+    fs::write(src.join("b.rs"), "fn b() {}\n").unwrap();
+    fs::write(src.join("c.rs"), "// Copyright 2024 New\n\nfn c() {}\n").unwrap();
+    fs::write(src.join("d.py"), "# Copyright 2020 Old\n\nd = 1\n").unwrap();
+    let template = template.to_str().unwrap();
+    let src = src.to_str().unwrap();
+
+    let output = lice(&["-f", template, "--check", "--count-by-language", src], "");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("By language: rs: 2 ok, 1 missing; py: 1 outdated\n"),
+        "{stdout}"
+    );
+    let output = lice(
+        &[
+            "-f",
+            template,
+            "--count-by-language",
+            "--format",
+            "json",
+            src,
+        ],
+        "",
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout
+            .contains("{\"by_language\":{\"rs\":{\"ok\":2,\"added\":1},\"py\":{\"updated\":1}}}\n"),
+        "{stdout}"
+    );
+    let quiet = lice(&["-f", template, "-q", "--count-by-language", src], "");
+    assert!(
+        !String::from_utf8(quiet.stdout)
+            .unwrap()
+            .contains("language")
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn init_writes_a_starter_header_and_won_t_overwrite_it() {
+    let dir = scratch();
+    fs::write(dir.join("a.rs"), "fn a() {}\n").unwrap();
+    fs::write(dir.join("b.rs"), "fn b() {}\n").unwrap();
+    fs::write(dir.join("c.py"), "c = 1\n").unwrap();
+    fs::write(dir.join("notes.xyz"), "x\n").unwrap();
+    fs::create_dir(dir.join("vendor")).unwrap();
+    fs::write(dir.join("vendor/d.go"), "package d\n").unwrap();
+    let init = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lice"))
+            .arg("init")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+
+    let output = init(&["-e", "vendor"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let header = fs::read_to_string(dir.join("HEADER.txt")).unwrap();
+    assert!(header.starts_with("Copyright {year} COPYRIGHT HOLDER\n"));
+    assert!(
+        stdout.contains("#   .rs          2\n#   .py          1\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("#   .xyz         1\n"), "{stdout}");
+    assert!(!stdout.contains(".go"), "{stdout}");
+    assert!(
+        stdout.contains("\nlice -f HEADER.txt --dry-run -e 'vendor' '.'\n"),
+        "{stdout}"
+    );
+    // nothing but the template was written
+    assert_eq!(fs::read_to_string(dir.join("a.rs")).unwrap(), "fn a() {}\n");
+
+    fs::write(dir.join("HEADER.txt"), "mine\n").unwrap();
+    let refused = init(&[]);
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("--force overwrites it"));
+    assert_eq!(
+        fs::read_to_string(dir.join("HEADER.txt")).unwrap(),
+        "mine\n"
+    );
+    let forced = init(&["--force", "--license-id", "MIT", "--author", "Acme"]);
+    assert!(forced.status.success());
+    let header = fs::read_to_string(dir.join("HEADER.txt")).unwrap();
+    assert!(
+        header.contains("Acme") && header.contains("MIT"),
+        "{header}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn jsonl_prints_a_record_per_file_and_a_summary() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let src = dir.join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(src.join("a.rs"), "fn a() {}\n").unwrap();
+    fs::write(src.join("b.rs"), "// Copyright 2024 New\n\nfn b() {}\n").unwrap();
+    fs::write(src.join("c.unknown"), "c\n").unwrap();
+    let template = template.to_str().unwrap();
+    let src = src.to_str().unwrap();
+
+    for jobs in ["1", "4"] {
+        let output = lice(
+            &[
+                "-f", template, "--check", "--format", "jsonl", "-j", jobs, src,
+            ],
+            "",
+        );
+        assert!(!output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), 4, "{stdout}");
+        assert!(lines.iter().all(|l| l.starts_with('{') && l.ends_with('}')));
+        let file = |name: &str| {
+            lines
+                .iter()
+                .find(|l| l.contains(&format!("{name}\",")))
+                .copied()
+                .unwrap_or_default()
+        };
+        assert!(file("a.rs").contains("\"type\":\"file\""), "{stdout}");
+        assert!(file("a.rs").contains("\"outcome\":\"missing\""), "{stdout}");
+        assert!(file("b.rs").contains("\"outcome\":\"ok\""), "{stdout}");
+        assert!(
+            file("c.unknown").contains("\"outcome\":\"skipped\""),
+            "{stdout}"
+        );
+        // the summary comes last and replaces the throughput line
+        let summary = lines[3];
+        assert!(summary.starts_with("{\"type\":\"summary\",\"mode\":\"check\""));
+        assert!(summary.contains("\"outcomes\":{\"ok\":1,\"missing\":1,\"skipped\":1}"));
+        assert!(summary.contains("\"violations\":1"), "{summary}");
+        // warnings go to stderr, typed as diagnostics
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("{\"type\":\"diag\",\"level\":\"warn\""),
+            "{stderr}"
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn self_benchmark_times_a_generated_tree_and_cleans_up() {
+    let output = lice(&["--self-benchmark", "12", "-j", "1"], "");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    let first = stdout.lines().next().unwrap_or_default();
+    assert!(first.starts_with("Benchmark: 12 files"), "{stdout}");
+    assert!(stdout.contains("apply files/s"), "{stdout}");
+    assert!(stdout.contains("\n-j 1 "), "{stdout}");
+    // the generated tree is gone once the timings are printed
+    let tree = first.rsplit(" in ").next().unwrap();
+    assert!(!PathBuf::from(tree).exists(), "{tree}");
+
+    let refused = lice(&["--self-benchmark", "12", "."], "");
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("takes no paths"));
+    let refused = lice(&["--self-benchmark", "0"], "");
+    assert!(!refused.status.success());
+}
+
+#[test]
+fn stdin_filter_prints_the_licensed_input() {
+    let dir = scratch();
+    let template = dir.join("HEADER.txt");
+    fs::write(&template, "Copyright 2024 New\n").unwrap();
+    let template = template.to_str().unwrap();
+
+    let output = lice(&["-f", template, "--stdin-filename", "a.py"], "x = 1\n");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"# Copyright 2024 New\n\nx = 1\n");
+
+    let output = lice(&["-f", template, "--stdin-style", "c-like"], "int x;\r\n");
+    assert!(output.status.success());
+    assert_eq!(
+        output.stdout,
+        b"/*\r\n * Copyright 2024 New\r\n */\r\n\r\nint x;\r\n"
+    );
+
+    // already licensed input comes back as it went in
+    let licensed = "# Copyright 2024 New\n\nx = 1\n";
+    let output = lice(&["-f", template, "--stdin-filename", "b.py"], licensed);
+    assert_eq!(output.stdout, licensed.as_bytes());
+
+    // neither the name nor the content give a style
+    let output = lice(&["-f", template, "--stdin-filename", "notes"], "x\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--stdin-style"));
+
+    let output = lice(&["-f", template, "--stdin-filename", "a.py", "src"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("take no paths"));
+    fs::remove_dir_all(&dir).unwrap();
+}